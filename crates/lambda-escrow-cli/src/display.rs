@@ -0,0 +1,187 @@
+//! Human readable output for escrows.
+
+use lambda_escrow_client::state::{self, Campaign, EscrowAccount, EscrowStatus, LineItems};
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+
+pub fn status_name(status: u8) -> String {
+    match EscrowStatus::from_u8(status) {
+        Some(status) => format!("{:?}", status),
+        None => format!("Unknown({})", status),
+    }
+}
+
+/// `1d 02h 03m 04s`, the way support staff read a countdown.
+pub fn duration(seconds: i64) -> String {
+    let days = seconds / state::SECONDS_PER_DAY;
+    let hours = seconds % state::SECONDS_PER_DAY / 3600;
+    let minutes = seconds % 3600 / 60;
+    format!("{}d {:02}h {:02}m {:02}s", days, hours, minutes, seconds % 60)
+}
+
+pub fn trial_countdown(escrow: &EscrowAccount, now: i64) -> String {
+    if EscrowStatus::from_u8(escrow.status) != Some(EscrowStatus::Delivered) {
+        return format!("not started, {} day(s) after delivery", escrow.trial_day);
+    }
+    let remaining = state::trial_end(escrow) - now;
+    if remaining > 0 {
+        format!("{} left", duration(remaining))
+    } else {
+        format!("ended {} ago, ready to settle", duration(-remaining))
+    }
+}
+
+pub fn print_escrow_line(address: &Pubkey, escrow: &EscrowAccount, now: i64) {
+    println!(
+        "{}  order {:>10}  {:<9}  amount {:>12}  trial {}",
+        address,
+        escrow.order_code,
+        status_name(escrow.status),
+        escrow.amount,
+        trial_countdown(escrow, now),
+    );
+}
+
+pub fn print_escrow(address: &Pubkey, escrow: &EscrowAccount, now: i64, vault_balance: Option<String>) {
+    println!("Escrow:                       {}", address);
+    println!("Order code:                   {}", escrow.order_code);
+    println!("Status:                       {}", status_name(escrow.status));
+    println!("Frozen:                       {}", escrow.frozen);
+    println!("Buyer:                        {}", escrow.buyer_key);
+    println!("Buyer deposit token account:  {}", escrow.buyer_deposit_token_account);
+    println!("Seller:                       {}", escrow.seller_key);
+    if escrow.fulfiller_key != escrow.seller_key {
+        println!("Fulfiller:                    {}", escrow.fulfiller_key);
+    }
+    println!("Seller receive token account: {}", escrow.seller_receive_token_account);
+    if escrow.receivable_mint != Pubkey::default() {
+        println!("Receivable mint:              {}", escrow.receivable_mint);
+    }
+    if escrow.double_deposit {
+        println!("Judge:                        none, double-deposit");
+        println!("Buyer collateral:             {}", escrow.buyer_collateral);
+        println!("Seller collateral:            {}", escrow.seller_collateral);
+        if escrow.long_stop > 0 {
+            println!("Long stop:                    {}", escrow.long_stop);
+            if escrow.long_stop_recipient == Pubkey::default() {
+                println!("Long stop recipient:          burned");
+            } else {
+                println!("Long stop recipient:          {}", escrow.long_stop_recipient);
+            }
+        }
+    } else {
+        println!("Judge:                        {}", escrow.judge_key);
+    }
+    if escrow.seller_funded {
+        println!("Funded by:                    seller");
+        println!("Payment deadline:             {}", escrow.payment_deadline);
+    }
+    if escrow.rent_payer != escrow.buyer_key {
+        println!("Rent payer:                   {}", escrow.rent_payer);
+    }
+    if escrow.judge_change_time > 0 {
+        println!(
+            "Pending judge:                {} (from {})",
+            escrow.pending_judge_key, escrow.judge_change_time
+        );
+    }
+    println!("Amount:                       {}", escrow.amount);
+    println!(
+        "Vault balance:                {}",
+        vault_balance.unwrap_or_else(|| "unavailable".to_string())
+    );
+    println!("Delivery time:                {}", escrow.delivery_time);
+    println!("Trial days:                   {}", escrow.trial_day);
+    println!("Trial:                        {}", trial_countdown(escrow, now));
+    if escrow.secret_hash != [0; 32] {
+        println!("Secret hash:                  {}", Hash::new(&escrow.secret_hash));
+        println!("Reveal deadline:              {}", escrow.reveal_deadline);
+    }
+    if escrow.rental {
+        // A rental reuses delivery_time as the end of the rental and trial_day as the claim window.
+        println!("Rental end:                   {}", escrow.delivery_time);
+        if escrow.damages_claim > 0 {
+            println!("Damages claimed:              {}", escrow.damages_claim);
+            println!("Evidence hash:                {}", Hash::new(&escrow.damages_evidence_hash));
+            if escrow.dispute_time > 0 {
+                println!("Disputed:                     {}", escrow.dispute_time);
+            } else {
+                let closed = if escrow.damages_response_deadline <= now { ", closed" } else { "" };
+                println!("Response window:              until {}{}", escrow.damages_response_deadline, closed);
+            }
+        }
+    }
+    if escrow.surcharge_amount > 0 {
+        let expired = if escrow.surcharge_deadline <= now { ", expired" } else { "" };
+        println!(
+            "Pending surcharge:            {} (until {}{})",
+            escrow.surcharge_amount, escrow.surcharge_deadline, expired
+        );
+        println!("Surcharge reason hash:        {}", Hash::new(&escrow.surcharge_reason_hash));
+    }
+    if escrow.intent_nonce > 0 {
+        println!("Last intent nonce:            {}", escrow.intent_nonce);
+    }
+    if escrow.vesting {
+        println!("Withdrawn:                    {}", escrow.withdrawn);
+        if escrow.dispute_time > 0 {
+            println!("Disputed:                     {}", escrow.dispute_time);
+        } else {
+            println!("Withdrawable:                 {}", state::withdrawable(escrow, now));
+        }
+    }
+}
+
+pub fn print_campaign(address: &Pubkey, campaign: &Campaign, now: i64) {
+    let state = if campaign.participants >= campaign.threshold && campaign.conversion_deadline <= now {
+        "threshold met, not converted in time, deposits refundable or convertible by anyone".to_string()
+    } else if campaign.participants >= campaign.threshold {
+        "threshold met".to_string()
+    } else if campaign.deadline > now {
+        format!("open, {} left", duration(campaign.deadline - now))
+    } else {
+        "failed, deposits refundable".to_string()
+    };
+    println!("Campaign:                     {}", address);
+    println!("Campaign id:                  {}", campaign.campaign_id);
+    println!("Seller:                       {}", campaign.seller_key);
+    println!("Seller receive token account: {}", campaign.seller_receive_token_account);
+    println!("Judge:                        {}", campaign.judge_key);
+    println!("Mint:                         {}", campaign.mint);
+    println!("Price:                        {}", campaign.price);
+    println!("Participants:                 {} of {}", campaign.participants, campaign.threshold);
+    println!("Open deposits:                {}", campaign.open_deposits);
+    println!("Deadline:                     {} ({})", campaign.deadline, state);
+    println!("Conversion deadline:          {}", campaign.conversion_deadline);
+    println!("Trial days:                   {}", campaign.trial_day);
+}
+
+pub fn print_line_items(line_items: &LineItems, trial_day: u16, now: i64) {
+    println!("Line items:");
+    for (index, item) in line_items.items.iter().enumerate() {
+        // Line items share the escrow's status values, 3 is Closed.
+        let status = match item.status {
+            3 => "Closed".to_string(),
+            status => status_name(status),
+        };
+        let trial = if EscrowStatus::from_u8(item.status) == Some(EscrowStatus::Delivered) {
+            let remaining = state::line_item_trial_end(item, trial_day) - now;
+            if remaining > 0 {
+                format!("  trial {} left", duration(remaining))
+            } else {
+                "  ready to settle".to_string()
+            }
+        } else {
+            String::new()
+        };
+        println!(
+            "  {:>2}  {:<9}  {} x {:>12}  sku {}{}",
+            index,
+            status,
+            item.quantity,
+            item.price,
+            Hash::new(&item.sku_hash),
+            trial,
+        );
+    }
+}
@@ -0,0 +1,1368 @@
+//! Operator CLI for the lambda-escrow program.
+//!
+//! `--keypair` pays the fees and signs as the acting party (buyer, seller,
+//! judge or admin depending on the command). Works against `solana-test-validator`
+//! with the default `--url`.
+
+mod display;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::str::FromStr;
+
+use lambda_escrow_client::instruction::{self, CartItem, EscrowKeys, Intent, NewLineItem, ESCROW_ACCOUNT_SPACE};
+use lambda_escrow_client::rpc::{self, EscrowFilter};
+use lambda_escrow_client::state::{self, EscrowAccount};
+use lambda_escrow_client::{error, pda};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::{hash, Hash};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use solana_sdk::transaction::TransactionError;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(name = "lambda-escrow", about = "Manage lambda-escrow escrows on a local or remote cluster")]
+struct Opt {
+    /// RPC endpoint.
+    #[structopt(long, short = "u", default_value = "http://127.0.0.1:8899")]
+    url: String,
+    /// Fee payer, also signing as the acting party.
+    #[structopt(long, short = "k", default_value = "~/.config/solana/id.json")]
+    keypair: String,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+/// Selects an escrow by address or by order code.
+#[derive(StructOpt)]
+struct EscrowArg {
+    /// Address of the escrow account.
+    #[structopt(long)]
+    escrow: Option<Pubkey>,
+    /// Order code of the escrow.
+    #[structopt(long)]
+    order_code: Option<u64>,
+}
+
+#[derive(StructOpt)]
+struct ReceiptArg {
+    /// Also write a settlement receipt, paid by --keypair.
+    #[structopt(long)]
+    receipt: bool,
+}
+
+/// Holder of the receivable token of a tokenized escrow, who is paid instead of the seller.
+#[derive(StructOpt)]
+struct HolderArg {
+    /// The holder's token account of the escrow's receivable mint.
+    #[structopt(long)]
+    receivable_account: Option<Pubkey>,
+    /// The holder's token account receiving the payout.
+    #[structopt(long)]
+    receive_token_account: Option<Pubkey>,
+}
+
+/// `SELLER,SELLER_RECEIVE_TOKEN_ACCOUNT,ORDER_CODE,AMOUNT[,TRIAL_DAY]`, one seller of a cart.
+struct CartItemArg {
+    seller: Pubkey,
+    seller_receive_token_account: Pubkey,
+    order_code: u64,
+    amount: u64,
+    trial_day: u16,
+}
+
+impl FromStr for CartItemArg {
+    type Err = Box<dyn Error>;
+
+    fn from_str(item: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = item.split(',').collect();
+        if fields.len() != 4 && fields.len() != 5 {
+            return Err(format!("expected SELLER,SELLER_RECEIVE_TOKEN_ACCOUNT,ORDER_CODE,AMOUNT[,TRIAL_DAY], got {}", item).into());
+        }
+        Ok(CartItemArg {
+            seller: fields[0].parse()?,
+            seller_receive_token_account: fields[1].parse()?,
+            order_code: fields[2].parse()?,
+            amount: fields[3].parse()?,
+            trial_day: fields.get(4).map_or(Ok(0), |trial_day| trial_day.parse())?,
+        })
+    }
+}
+
+/// `SKU,QUANTITY,PRICE`, one line item. The program only keeps the SKU's sha256.
+struct LineItemArg {
+    sku: String,
+    quantity: u32,
+    price: u64,
+}
+
+impl FromStr for LineItemArg {
+    type Err = Box<dyn Error>;
+
+    fn from_str(item: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = item.split(',').collect();
+        if fields.len() != 3 {
+            return Err(format!("expected SKU,QUANTITY,PRICE, got {}", item).into());
+        }
+        Ok(LineItemArg {
+            sku: fields[0].to_string(),
+            quantity: fields[1].parse()?,
+            price: fields[2].parse()?,
+        })
+    }
+}
+
+/// Line items of an escrow by index, as listed by `show`.
+#[derive(StructOpt)]
+struct ItemsArg {
+    #[structopt(flatten)]
+    escrow: EscrowArg,
+    /// Index of a line item, repeated per item.
+    #[structopt(long = "index", required = true)]
+    indexes: Vec<u8>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Create an escrow, --keypair is the buyer.
+    Initialize {
+        #[structopt(long)]
+        order_code: u64,
+        #[structopt(long)]
+        amount: u64,
+        #[structopt(long, default_value = "0")]
+        trial_day: u16,
+        #[structopt(long)]
+        mint: Pubkey,
+        #[structopt(long)]
+        buyer_deposit_token_account: Pubkey,
+        #[structopt(long)]
+        seller: Pubkey,
+        #[structopt(long)]
+        seller_receive_token_account: Pubkey,
+        #[structopt(long)]
+        judge: Pubkey,
+        /// Keypair of the new escrow account, generated if omitted.
+        #[structopt(long)]
+        escrow_keypair: Option<String>,
+        /// Keypair paying the escrow and vault rent, which gets it back on close. --keypair if omitted.
+        #[structopt(long)]
+        rent_payer_keypair: Option<String>,
+        /// Mint a 1-of-1 receivable token to the seller, its holder is paid instead of the seller.
+        #[structopt(long)]
+        tokenize: bool,
+    },
+    /// Create one escrow per --item, linked by --cart-id, --keypair is the buyer.
+    Checkout {
+        #[structopt(long)]
+        cart_id: u64,
+        #[structopt(long)]
+        mint: Pubkey,
+        #[structopt(long)]
+        buyer_deposit_token_account: Pubkey,
+        #[structopt(long)]
+        judge: Pubkey,
+        /// SELLER,SELLER_RECEIVE_TOKEN_ACCOUNT,ORDER_CODE,AMOUNT[,TRIAL_DAY], repeated per seller.
+        #[structopt(long = "item", required = true)]
+        items: Vec<CartItemArg>,
+    },
+    /// Cancel a New escrow and return the funds, --keypair is the buyer.
+    Cancel {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+    },
+    /// Cancel every escrow of a cart, none may be shipping yet, --keypair is the buyer.
+    CancelCart {
+        #[structopt(long)]
+        cart_id: u64,
+    },
+    /// Return part of a New escrow, --keypair is the buyer.
+    CancelPartial {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Add funds to the escrow, --keypair is the buyer.
+    ChargeMore {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Ask the buyer for extra shipping or customs costs, --keypair is the seller.
+    RequestSurcharge {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        amount: u64,
+        /// Why the surcharge is needed, only its sha256 goes on-chain.
+        #[structopt(long)]
+        reason: String,
+        /// Seconds the buyer has to approve the surcharge in.
+        #[structopt(long, default_value = "86400")]
+        expires_in: i64,
+    },
+    /// Fund the pending surcharge, --keypair is the buyer. --amount has to be the one requested.
+    ApproveSurcharge {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Drop the pending surcharge, --keypair is the buyer.
+    RejectSurcharge {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Mark the order shipped, --keypair is the seller.
+    Ship {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Mark the order delivered and start the trial, --keypair is the buyer.
+    Deliver {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Move the order back to New, --keypair is the seller.
+    Refund {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Return part of the funds to the buyer, --keypair is the seller.
+    RefundPartial {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Pay the seller once the trial is over, --keypair is the seller, or the receivable token
+    /// holder of a tokenized escrow.
+    Settle {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+        #[structopt(flatten)]
+        holder: HolderArg,
+    },
+    /// Pay every escrow of --keypair (the seller) whose trial is over, a few per transaction.
+    SettleMatured,
+    /// Split a New escrow into line items worth its amount, --keypair is the buyer.
+    SetLineItems {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        /// SKU,QUANTITY,PRICE with the unit price, repeated per item.
+        #[structopt(long = "item", required = true)]
+        items: Vec<LineItemArg>,
+    },
+    /// Mark line items shipped, --keypair is the seller.
+    ShipItems(ItemsArg),
+    /// Mark line items delivered, starting their trial, --keypair is the buyer.
+    DeliverItems(ItemsArg),
+    /// Pay delivered line items whose trial is over, --keypair is the seller.
+    SettleItems(ItemsArg),
+    /// Cancel line items not shipped yet and return their price, --keypair is the buyer.
+    CancelItems(ItemsArg),
+    /// Unlock the escrow to the seller over the trial, before delivery, --keypair is the buyer.
+    SetVesting {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Take what vested so far, --keypair is the seller.
+    WithdrawVested {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Stop the vesting during the trial and leave the rest to the judge, --keypair is the buyer.
+    Dispute {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Print a signed intent for the relayer, --keypair is the buyer and pays nothing.
+    SignIntent {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        /// delivered, cancel or charge_more.
+        #[structopt(long)]
+        action: String,
+        /// Amount added by charge_more.
+        #[structopt(long, default_value = "0")]
+        amount: u64,
+        /// Defaults to the escrow's last relayed nonce plus one.
+        #[structopt(long)]
+        nonce: Option<u64>,
+        /// Seconds the intent stays valid.
+        #[structopt(long, default_value = "600")]
+        expires_in: i64,
+    },
+    /// Let relayed charge_more intents take up to --amount from the deposit, --keypair is the buyer.
+    ApproveIntentDelegate {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Set the status (0: New, 1: Shipping, 2: Delivered), --keypair is the judge.
+    Adjudge {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        status: u8,
+    },
+    /// Return part of the funds to the buyer, --keypair is the judge.
+    AdjudgePartial {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Close the escrow in favour of the buyer, --keypair is the judge.
+    AdjudgeForBuyer {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+    },
+    /// Close the escrow in favour of the seller, --keypair is the judge.
+    AdjudgeForSeller {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+        #[structopt(flatten)]
+        holder: HolderArg,
+    },
+    /// Change the trial length, --keypair is the judge.
+    UpdateTrialDay {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        trial_day: u16,
+    },
+    /// Set the escrow amount to the vault balance, --keypair is the judge.
+    SyncVault {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Sell the escrow's payout to --new-seller, --keypair is the seller. The seller still ships.
+    TransferReceivable {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        new_seller: Pubkey,
+        #[structopt(long)]
+        new_seller_receive_token_account: Pubkey,
+    },
+    /// Ship a digital good as the hash of --secret, which stays off-chain until `reveal`,
+    /// --keypair is the seller.
+    ShipHashLocked {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        secret: String,
+        /// Seconds to reveal the secret in, after which the buyer can take the escrow back.
+        #[structopt(long, default_value = "86400")]
+        reveal_within: i64,
+    },
+    /// Reveal the secret of a hash-locked escrow to the buyer and start the trial, --keypair is the seller.
+    Reveal {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        secret: String,
+    },
+    /// Refund a hash-locked escrow whose secret was not revealed in time, any --keypair.
+    ReclaimUnrevealed {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+    },
+    /// Lock tokens the buyer pays for off-chain, --keypair is the seller and pays the rent.
+    InitializeSellerFunded {
+        #[structopt(long)]
+        order_code: u64,
+        #[structopt(long)]
+        amount: u64,
+        #[structopt(long)]
+        mint: Pubkey,
+        #[structopt(long)]
+        seller_deposit_token_account: Pubkey,
+        #[structopt(long)]
+        buyer: Pubkey,
+        #[structopt(long)]
+        buyer_receive_token_account: Pubkey,
+        #[structopt(long)]
+        judge: Pubkey,
+        /// Seconds the buyer has to pay and `mark-paid` in, after which the seller can reclaim.
+        #[structopt(long, default_value = "3600")]
+        payment_window: i64,
+        /// Keypair of the new escrow account, generated if omitted.
+        #[structopt(long)]
+        escrow_keypair: Option<String>,
+    },
+    /// Report the off-chain payment of a seller-funded escrow, --keypair is the buyer.
+    MarkPaid {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Confirm the payment and release the tokens to the buyer, --keypair is the seller.
+    Release {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+    },
+    /// Return a seller-funded escrow not marked paid in time to the seller, any --keypair.
+    ReclaimUnpaid {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+    },
+    /// Create an escrow with no judge where both parties lock collateral, --keypair is the buyer
+    /// and pays the rent, --other-keypair the seller.
+    InitializeDoubleDeposit {
+        #[structopt(long)]
+        order_code: u64,
+        #[structopt(long)]
+        price: u64,
+        #[structopt(long)]
+        buyer_collateral: u64,
+        #[structopt(long)]
+        seller_collateral: u64,
+        #[structopt(long)]
+        mint: Pubkey,
+        #[structopt(long)]
+        buyer_deposit_token_account: Pubkey,
+        #[structopt(long)]
+        seller_receive_token_account: Pubkey,
+        #[structopt(long)]
+        other_keypair: String,
+        /// Seconds after which anyone can burn the escrow, or donate it to --long-stop-recipient.
+        #[structopt(long)]
+        long_stop_in: Option<i64>,
+        /// Token account of the mint the escrow is donated to at the long stop, burned if omitted.
+        #[structopt(long)]
+        long_stop_recipient: Option<Pubkey>,
+        /// Keypair of the new escrow account, generated if omitted.
+        #[structopt(long)]
+        escrow_keypair: Option<String>,
+    },
+    /// Pay out a double-deposit escrow to the seller, --keypair and --other-keypair are the buyer
+    /// and the seller.
+    SettleDoubleDeposit {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        other_keypair: String,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+    },
+    /// Return a double-deposit escrow to the buyer, --keypair and --other-keypair are the buyer
+    /// and the seller.
+    MutualCancel {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        other_keypair: String,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+    },
+    /// Burn or donate a double-deposit escrow past its long stop, any --keypair.
+    ExpireLongStop {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        mint: Pubkey,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+    },
+    /// Lock the security deposit of a rental, --keypair is the buyer and pays the rent.
+    InitializeRental {
+        #[structopt(long)]
+        order_code: u64,
+        #[structopt(long)]
+        amount: u64,
+        #[structopt(long)]
+        mint: Pubkey,
+        #[structopt(long)]
+        buyer_deposit_token_account: Pubkey,
+        #[structopt(long)]
+        seller: Pubkey,
+        #[structopt(long)]
+        seller_receive_token_account: Pubkey,
+        #[structopt(long)]
+        judge: Pubkey,
+        /// Seconds until the rental ends and the seller can claim damages.
+        #[structopt(long)]
+        rental_ends_in: i64,
+        /// Days after the rental the seller can claim damages in.
+        #[structopt(long, default_value = "7")]
+        claim_window_day: u16,
+        /// Keypair of the new escrow account, generated if omitted.
+        #[structopt(long)]
+        escrow_keypair: Option<String>,
+    },
+    /// Claim damages from a rental deposit, --keypair is the seller.
+    ClaimDamages {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        amount: u64,
+        /// File with the evidence, only its sha256 goes on-chain.
+        #[structopt(long)]
+        evidence: String,
+    },
+    /// Accept the claimed damages and take the rest of the deposit back, --keypair is the buyer.
+    AcceptDamages {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+    },
+    /// Escalate the claimed damages to the judge within the response window, --keypair is the buyer.
+    DisputeDamages {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Pay out damages the buyer did not answer in time as accepted, any --keypair.
+    SettleDamages {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+    },
+    /// Return an unclaimed rental deposit to the buyer after the claim window, any --keypair.
+    ReturnDeposit {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(flatten)]
+        receipt: ReceiptArg,
+    },
+    /// Replace the judge, --keypair and --other-keypair are the buyer and the seller.
+    ChangeJudge {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        new_judge: Pubkey,
+        #[structopt(long)]
+        other_keypair: String,
+    },
+    /// Schedule a new judge, --keypair is the admin.
+    RequestJudgeChange {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+        #[structopt(long)]
+        new_judge: Pubkey,
+    },
+    /// Apply the scheduled judge once the timelock is over, --keypair is the admin.
+    ExecuteJudgeChange {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Freeze the escrow, --keypair is the compliance authority.
+    Freeze {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Unfreeze the escrow, --keypair is the compliance authority.
+    Thaw {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+    /// Create the program config, --keypair must be the program upgrade authority and becomes the admin.
+    InitConfig {
+        #[structopt(long)]
+        compliance_authority: Pubkey,
+        #[structopt(long, default_value = "0")]
+        judge_change_delay: i64,
+    },
+    /// Open a group buy of --threshold buyers depositing --price each, --keypair is the seller.
+    CreateCampaign {
+        #[structopt(long)]
+        campaign_id: u64,
+        #[structopt(long)]
+        mint: Pubkey,
+        #[structopt(long)]
+        seller_receive_token_account: Pubkey,
+        #[structopt(long)]
+        judge: Pubkey,
+        #[structopt(long)]
+        price: u64,
+        #[structopt(long)]
+        threshold: u32,
+        /// Seconds buyers can join in.
+        #[structopt(long)]
+        duration: i64,
+        /// Seconds after the join deadline to convert the deposits in, then anyone can refund or convert them.
+        #[structopt(long, default_value = "604800")]
+        convert_within: i64,
+        #[structopt(long, default_value = "0")]
+        trial_day: u16,
+    },
+    /// Deposit the campaign price, --keypair is the buyer.
+    JoinCampaign {
+        #[structopt(long)]
+        campaign_id: u64,
+        #[structopt(long)]
+        buyer_deposit_token_account: Pubkey,
+    },
+    /// Turn every deposit of a campaign that met its threshold into an escrow, numbered from
+    /// --first-order-code, --keypair is the seller, or anyone past the conversion deadline.
+    ConvertCampaign {
+        #[structopt(long)]
+        campaign_id: u64,
+        #[structopt(long)]
+        first_order_code: u64,
+        /// Also ship each escrow in the same transaction, so its buyer can no longer cancel it.
+        /// Only for the seller.
+        #[structopt(long)]
+        ship: bool,
+    },
+    /// Take a deposit back from a campaign that missed its threshold or its conversion deadline,
+    /// --keypair is the buyer.
+    RefundCampaignDeposit {
+        #[structopt(long)]
+        campaign_id: u64,
+        #[structopt(long)]
+        buyer_deposit_token_account: Pubkey,
+    },
+    /// Close a campaign without deposits left, --keypair is the seller.
+    CloseCampaign {
+        #[structopt(long)]
+        campaign_id: u64,
+    },
+    /// Print a campaign and its open deposits.
+    ShowCampaign {
+        #[structopt(long)]
+        campaign_id: u64,
+    },
+    /// Update the program config, --keypair is the admin.
+    UpdateConfig {
+        #[structopt(long)]
+        new_admin: Pubkey,
+        #[structopt(long)]
+        compliance_authority: Pubkey,
+        #[structopt(long)]
+        judge_change_delay: i64,
+    },
+    /// Stop new escrows and payouts, --keypair is the admin.
+    Pause,
+    /// Resume the program, --keypair is the admin.
+    Unpause,
+    /// Print the program config.
+    ShowConfig,
+    /// List escrows, optionally filtered by party or order code.
+    List {
+        #[structopt(long)]
+        buyer: Option<Pubkey>,
+        #[structopt(long)]
+        seller: Option<Pubkey>,
+        #[structopt(long)]
+        judge: Option<Pubkey>,
+        #[structopt(long)]
+        order_code: Option<u64>,
+        #[structopt(long)]
+        cart_id: Option<u64>,
+    },
+    /// Print one escrow with its trial countdown and vault balance.
+    Show {
+        #[structopt(flatten)]
+        escrow: EscrowArg,
+    },
+}
+
+fn read_keypair(path: &str) -> Result<Keypair, Box<dyn Error>> {
+    let path = match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home, rest),
+        _ => path.to_string(),
+    };
+    read_keypair_file(&path).map_err(|err| format!("failed to read keypair {}: {}", path, err).into())
+}
+
+fn resolve_escrow(rpc: &RpcClient, arg: &EscrowArg) -> Result<(Pubkey, EscrowAccount), Box<dyn Error>> {
+    if let Some(address) = arg.escrow {
+        return Ok((address, rpc::get_escrow(rpc, &address)?));
+    }
+    let order_code = arg.order_code.ok_or("--escrow or --order-code is required")?;
+    let filter = EscrowFilter { order_code: Some(order_code), ..EscrowFilter::default() };
+    rpc::find_escrows(rpc, &filter)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("no escrow with order code {}", order_code).into())
+}
+
+fn escrow_keys(rpc: &RpcClient, arg: &EscrowArg) -> Result<EscrowKeys, Box<dyn Error>> {
+    let (address, escrow) = resolve_escrow(rpc, arg)?;
+    Ok(EscrowKeys::from_escrow(address, &escrow))
+}
+
+/// The holder's receivable and receive token accounts, required for a tokenized escrow.
+fn holder_accounts(escrow: &EscrowAccount, holder: &HolderArg) -> Result<Option<(Pubkey, Pubkey)>, Box<dyn Error>> {
+    if escrow.receivable_mint == Pubkey::default() {
+        return Ok(None);
+    }
+    match (holder.receivable_account, holder.receive_token_account) {
+        (Some(receivable_account), Some(receive_token_account)) => Ok(Some((receivable_account, receive_token_account))),
+        _ => Err("the escrow is tokenized, pass the holder's --receivable-account and --receive-token-account".into()),
+    }
+}
+
+fn closing(instruction: Instruction, keys: &EscrowKeys, receipt: &ReceiptArg, payer: &Pubkey) -> Instruction {
+    if receipt.receipt {
+        instruction::with_settlement_receipt(instruction, &keys.escrow_account, payer)
+    } else {
+        instruction
+    }
+}
+
+/// Turns a failed transaction into the program's error message when possible.
+fn describe(err: solana_client::client_error::ClientError) -> Box<dyn Error> {
+    if let Some(TransactionError::InstructionError(_, instruction_error)) = rpc::transaction_error(&err) {
+        if let Some(code) = error::decode_instruction_error(&instruction_error) {
+            return format!("{:?}: {}", code, code).into();
+        }
+    }
+    err.into()
+}
+
+fn send(
+    rpc: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&dyn Signer],
+) -> Result<(), Box<dyn Error>> {
+    let signature = rpc::send_instructions(rpc, instructions, payer, signers).map_err(describe)?;
+    println!("Signature: {}", signature);
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let opt = Opt::from_args();
+    let rpc = RpcClient::new_with_commitment(opt.url.clone(), CommitmentConfig::confirmed());
+    let payer = read_keypair(&opt.keypair)?;
+    let me = payer.pubkey();
+
+    match opt.command {
+        Command::Initialize {
+            order_code,
+            amount,
+            trial_day,
+            mint,
+            buyer_deposit_token_account,
+            seller,
+            seller_receive_token_account,
+            judge,
+            escrow_keypair,
+            rent_payer_keypair,
+            tokenize,
+        } => {
+            let escrow_keypair = match escrow_keypair {
+                Some(path) => read_keypair(&path)?,
+                None => Keypair::new(),
+            };
+            let rent_payer_keypair = match rent_payer_keypair {
+                Some(path) => Some(read_keypair(&path)?),
+                None => None,
+            };
+            let rent_payer = rent_payer_keypair.as_ref().map_or(me, |keypair| keypair.pubkey());
+            let mut signers: Vec<&dyn Signer> = vec![&escrow_keypair];
+            if let Some(keypair) = &rent_payer_keypair {
+                signers.push(keypair);
+            }
+            let keys = EscrowKeys {
+                escrow_account: escrow_keypair.pubkey(),
+                order_code,
+                buyer: me,
+                buyer_deposit_token_account,
+                seller,
+                seller_receive_token_account,
+                judge,
+                rent_payer,
+                fulfiller: seller,
+            };
+            let lamports = rpc.get_minimum_balance_for_rent_exemption(ESCROW_ACCOUNT_SPACE)?;
+            let mut initialize = instruction::initialize(&keys, &mint, amount, trial_day);
+            if tokenize {
+                initialize = instruction::with_receivable(initialize, order_code);
+            }
+            send(
+                &rpc,
+                &[instruction::create_escrow_account(&rent_payer, &keys.escrow_account, lamports), initialize],
+                &payer,
+                &signers,
+            )?;
+            println!("Escrow: {}", keys.escrow_account);
+        }
+        Command::Checkout { cart_id, mint, buyer_deposit_token_account, judge, items } => {
+            if items.len() > instruction::MAX_CART_ENTRIES {
+                return Err(format!("at most {} items fit in one checkout", instruction::MAX_CART_ENTRIES).into());
+            }
+            let escrow_keypairs: Vec<Keypair> = items.iter().map(|_| Keypair::new()).collect();
+            let items: Vec<CartItem> = items
+                .iter()
+                .zip(&escrow_keypairs)
+                .map(|(item, escrow_keypair)| CartItem {
+                    escrow_account: escrow_keypair.pubkey(),
+                    order_code: item.order_code,
+                    seller: item.seller,
+                    seller_receive_token_account: item.seller_receive_token_account,
+                    amount: item.amount,
+                    trial_day: item.trial_day,
+                })
+                .collect();
+            let lamports = rpc.get_minimum_balance_for_rent_exemption(ESCROW_ACCOUNT_SPACE)?;
+            let mut instructions: Vec<Instruction> = items
+                .iter()
+                .map(|item| instruction::create_escrow_account(&me, &item.escrow_account, lamports))
+                .collect();
+            instructions.push(instruction::checkout(&me, &buyer_deposit_token_account, &judge, &mint, cart_id, &items));
+            let signers: Vec<&dyn Signer> = escrow_keypairs.iter().map(|keypair| keypair as &dyn Signer).collect();
+            send(&rpc, &instructions, &payer, &signers)?;
+            for item in &items {
+                println!("Escrow: {} (order {})", item.escrow_account, item.order_code);
+            }
+        }
+        Command::Cancel { escrow, receipt } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let ix = closing(instruction::cancel(&keys), &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::CancelCart { cart_id } => {
+            let filter = EscrowFilter { buyer: Some(me), cart_id: Some(cart_id), ..EscrowFilter::default() };
+            let escrows: Vec<EscrowKeys> = rpc::find_escrows(&rpc, &filter)?
+                .iter()
+                .map(|(address, escrow)| EscrowKeys::from_escrow(*address, escrow))
+                .collect();
+            if escrows.is_empty() {
+                return Err(format!("no escrow in cart {}", cart_id).into());
+            }
+            // All of them must go in one transaction for the cancel to cover the whole cart.
+            let deposit = escrows[0].buyer_deposit_token_account;
+            send(&rpc, &[instruction::cancel_cart(&me, &deposit, cart_id, &escrows)], &payer, &[])?;
+        }
+        Command::CancelPartial { escrow, amount } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::cancel_partial(&keys, amount)], &payer, &[])?;
+        }
+        Command::ChargeMore { escrow, amount } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::charge_more(&keys, amount)], &payer, &[])?;
+        }
+        Command::RequestSurcharge { escrow, amount, reason, expires_in } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let deadline = rpc::cluster_time(&rpc)? + expires_in;
+            let ix = instruction::request_surcharge(&keys, amount, hash(reason.as_bytes()).to_bytes(), deadline);
+            send(&rpc, &[ix], &payer, &[])?;
+            println!("Surcharge deadline: {}", deadline);
+        }
+        Command::ApproveSurcharge { escrow, amount } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::approve_surcharge(&keys, amount)], &payer, &[])?;
+        }
+        Command::RejectSurcharge { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::reject_surcharge(&keys)], &payer, &[])?;
+        }
+        Command::Ship { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::shipping(&keys)], &payer, &[])?;
+        }
+        Command::Deliver { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::delivered(&keys)], &payer, &[])?;
+        }
+        Command::Refund { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::refund(&keys)], &payer, &[])?;
+        }
+        Command::RefundPartial { escrow, amount } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::refund_partial(&keys, amount)], &payer, &[])?;
+        }
+        Command::Settle { escrow, receipt, holder } => {
+            let (address, escrow) = resolve_escrow(&rpc, &escrow)?;
+            let keys = EscrowKeys::from_escrow(address, &escrow);
+            let ix = match holder_accounts(&escrow, &holder)? {
+                Some((receivable_account, receive_token_account)) => {
+                    instruction::exchange_to_holder(&keys, &me, &receivable_account, &receive_token_account)
+                }
+                None => instruction::exchange(&keys),
+            };
+            let ix = closing(ix, &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::SettleMatured => {
+            let now = rpc::cluster_time(&rpc)?;
+            // settle_batch pays a single receive account, group the escrows by it.
+            let mut by_receive_account: BTreeMap<Pubkey, Vec<EscrowKeys>> = BTreeMap::new();
+            for (address, escrow) in rpc::find_matured(&rpc, &me, now)? {
+                let keys = EscrowKeys::from_escrow(address, &escrow);
+                by_receive_account.entry(keys.seller_receive_token_account).or_default().push(keys);
+            }
+            for (seller_receive_token_account, escrows) in &by_receive_account {
+                for ix in instruction::settle_batches(&me, seller_receive_token_account, escrows) {
+                    send(&rpc, &[ix], &payer, &[])?;
+                }
+            }
+            println!("{} matured escrow(s) sent", by_receive_account.values().map(Vec::len).sum::<usize>());
+        }
+        Command::SetLineItems { escrow, items } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let items = items
+                .iter()
+                .map(|item| NewLineItem {
+                    sku_hash: hash(item.sku.as_bytes()).to_bytes(),
+                    quantity: item.quantity,
+                    price: item.price,
+                })
+                .collect();
+            send(&rpc, &[instruction::set_line_items(&keys, items)], &payer, &[])?;
+        }
+        Command::ShipItems(items) => {
+            let keys = escrow_keys(&rpc, &items.escrow)?;
+            send(&rpc, &[instruction::ship_items(&keys, items.indexes)], &payer, &[])?;
+        }
+        Command::DeliverItems(items) => {
+            let keys = escrow_keys(&rpc, &items.escrow)?;
+            send(&rpc, &[instruction::deliver_items(&keys, items.indexes)], &payer, &[])?;
+        }
+        Command::SettleItems(items) => {
+            let keys = escrow_keys(&rpc, &items.escrow)?;
+            send(&rpc, &[instruction::settle_items(&keys, items.indexes)], &payer, &[])?;
+        }
+        Command::CancelItems(items) => {
+            let keys = escrow_keys(&rpc, &items.escrow)?;
+            send(&rpc, &[instruction::cancel_items(&keys, items.indexes)], &payer, &[])?;
+        }
+        Command::SetVesting { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::set_vesting(&keys)], &payer, &[])?;
+        }
+        Command::WithdrawVested { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::withdraw_vested(&keys)], &payer, &[])?;
+        }
+        Command::Dispute { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::dispute(&keys)], &payer, &[])?;
+        }
+        Command::SignIntent { escrow, action, amount, nonce, expires_in } => {
+            let (address, escrow) = resolve_escrow(&rpc, &escrow)?;
+            let action_code = match action.as_str() {
+                "delivered" => Intent::DELIVERED,
+                "cancel" => Intent::CANCEL,
+                "charge_more" => Intent::CHARGE_MORE,
+                _ => return Err(format!("unknown action {}, expected delivered, cancel or charge_more", action).into()),
+            };
+            let intent = Intent {
+                action: action_code,
+                amount,
+                nonce: nonce.unwrap_or(escrow.intent_nonce + 1),
+                expiry: rpc::cluster_time(&rpc)? + expires_in,
+            };
+            let signature = payer.sign_message(&intent.message(&address));
+            // The relayer's request format, one JSON object per line.
+            println!(
+                r#"{{"escrow":"{}","action":"{}","amount":{},"nonce":{},"expiry":{},"signature":"{}"}}"#,
+                address,
+                action,
+                intent.amount,
+                intent.nonce,
+                intent.expiry,
+                signature,
+            );
+        }
+        Command::ApproveIntentDelegate { escrow, amount } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::approve_intent_delegate(&keys, amount)], &payer, &[])?;
+        }
+        Command::Adjudge { escrow, status } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::adjudge(&keys, status)], &payer, &[])?;
+        }
+        Command::AdjudgePartial { escrow, amount } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::adjudge_partial(&keys, amount)], &payer, &[])?;
+        }
+        Command::AdjudgeForBuyer { escrow, receipt } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let ix = closing(instruction::adjudge_for_buyer(&keys), &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::AdjudgeForSeller { escrow, receipt, holder } => {
+            let (address, escrow) = resolve_escrow(&rpc, &escrow)?;
+            let keys = EscrowKeys::from_escrow(address, &escrow);
+            let ix = match holder_accounts(&escrow, &holder)? {
+                Some((receivable_account, receive_token_account)) => {
+                    instruction::adjudge_for_holder(&keys, &receivable_account, &receive_token_account)
+                }
+                None => instruction::adjudge_for_seller(&keys),
+            };
+            let ix = closing(ix, &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::UpdateTrialDay { escrow, trial_day } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::update_trial_day(&keys, trial_day)], &payer, &[])?;
+        }
+        Command::SyncVault { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::sync_vault(&keys)], &payer, &[])?;
+        }
+        Command::TransferReceivable { escrow, new_seller, new_seller_receive_token_account } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let ix = instruction::transfer_receivable(&keys, &new_seller, &new_seller_receive_token_account);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::ShipHashLocked { escrow, secret, reveal_within } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let reveal_deadline = rpc::cluster_time(&rpc)? + reveal_within;
+            let ix = instruction::ship_hash_locked(&keys, hash(secret.as_bytes()).to_bytes(), reveal_deadline);
+            send(&rpc, &[ix], &payer, &[])?;
+            println!("Reveal deadline: {}", reveal_deadline);
+        }
+        Command::Reveal { escrow, secret } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::reveal_and_claim(&keys, secret.into_bytes())], &payer, &[])?;
+        }
+        Command::ReclaimUnrevealed { escrow, receipt } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let ix = closing(instruction::reclaim_unrevealed(&keys), &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::InitializeSellerFunded {
+            order_code,
+            amount,
+            mint,
+            seller_deposit_token_account,
+            buyer,
+            buyer_receive_token_account,
+            judge,
+            payment_window,
+            escrow_keypair,
+        } => {
+            let escrow_keypair = match escrow_keypair {
+                Some(path) => read_keypair(&path)?,
+                None => Keypair::new(),
+            };
+            let keys = EscrowKeys {
+                escrow_account: escrow_keypair.pubkey(),
+                order_code,
+                buyer,
+                buyer_deposit_token_account: buyer_receive_token_account,
+                seller: me,
+                seller_receive_token_account: seller_deposit_token_account,
+                judge,
+                rent_payer: me,
+                fulfiller: me,
+            };
+            let lamports = rpc.get_minimum_balance_for_rent_exemption(ESCROW_ACCOUNT_SPACE)?;
+            send(
+                &rpc,
+                &[
+                    instruction::create_escrow_account(&me, &keys.escrow_account, lamports),
+                    instruction::initialize_seller_funded(&keys, &mint, amount, payment_window),
+                ],
+                &payer,
+                &[&escrow_keypair],
+            )?;
+            println!("Escrow: {}", keys.escrow_account);
+        }
+        Command::MarkPaid { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::mark_paid(&keys)], &payer, &[])?;
+        }
+        Command::Release { escrow, receipt } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let ix = closing(instruction::release(&keys), &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::ReclaimUnpaid { escrow, receipt } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let ix = closing(instruction::reclaim_unpaid(&keys), &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::InitializeDoubleDeposit {
+            order_code,
+            price,
+            buyer_collateral,
+            seller_collateral,
+            mint,
+            buyer_deposit_token_account,
+            seller_receive_token_account,
+            other_keypair,
+            long_stop_in,
+            long_stop_recipient,
+            escrow_keypair,
+        } => {
+            let escrow_keypair = match escrow_keypair {
+                Some(path) => read_keypair(&path)?,
+                None => Keypair::new(),
+            };
+            let seller = read_keypair(&other_keypair)?;
+            let long_stop = match long_stop_in {
+                Some(seconds) => rpc::cluster_time(&rpc)? + seconds,
+                None => 0,
+            };
+            let keys = EscrowKeys {
+                escrow_account: escrow_keypair.pubkey(),
+                order_code,
+                buyer: me,
+                buyer_deposit_token_account,
+                seller: seller.pubkey(),
+                seller_receive_token_account,
+                judge: Pubkey::default(),
+                rent_payer: me,
+                fulfiller: seller.pubkey(),
+            };
+            let lamports = rpc.get_minimum_balance_for_rent_exemption(ESCROW_ACCOUNT_SPACE)?;
+            let initialize = instruction::initialize_double_deposit(
+                &keys,
+                &mint,
+                price,
+                buyer_collateral,
+                seller_collateral,
+                long_stop,
+                long_stop_recipient.as_ref(),
+            );
+            send(
+                &rpc,
+                &[instruction::create_escrow_account(&me, &keys.escrow_account, lamports), initialize],
+                &payer,
+                &[&escrow_keypair, &seller],
+            )?;
+            println!("Escrow: {}", keys.escrow_account);
+        }
+        Command::SettleDoubleDeposit { escrow, other_keypair, receipt } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let other = read_keypair(&other_keypair)?;
+            let ix = closing(instruction::settle(&keys), &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[&other])?;
+        }
+        Command::MutualCancel { escrow, other_keypair, receipt } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let other = read_keypair(&other_keypair)?;
+            let ix = closing(instruction::mutual_cancel(&keys), &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[&other])?;
+        }
+        Command::ExpireLongStop { escrow, mint, receipt } => {
+            let (address, escrow) = resolve_escrow(&rpc, &escrow)?;
+            let keys = EscrowKeys::from_escrow(address, &escrow);
+            let ix = instruction::expire_long_stop(&keys, &mint, &escrow.long_stop_recipient);
+            send(&rpc, &[closing(ix, &keys, &receipt, &me)], &payer, &[])?;
+        }
+        Command::InitializeRental {
+            order_code,
+            amount,
+            mint,
+            buyer_deposit_token_account,
+            seller,
+            seller_receive_token_account,
+            judge,
+            rental_ends_in,
+            claim_window_day,
+            escrow_keypair,
+        } => {
+            let escrow_keypair = match escrow_keypair {
+                Some(path) => read_keypair(&path)?,
+                None => Keypair::new(),
+            };
+            let keys = EscrowKeys {
+                escrow_account: escrow_keypair.pubkey(),
+                order_code,
+                buyer: me,
+                buyer_deposit_token_account,
+                seller,
+                seller_receive_token_account,
+                judge,
+                rent_payer: me,
+                fulfiller: seller,
+            };
+            let rental_end = rpc::cluster_time(&rpc)? + rental_ends_in;
+            let lamports = rpc.get_minimum_balance_for_rent_exemption(ESCROW_ACCOUNT_SPACE)?;
+            send(
+                &rpc,
+                &[
+                    instruction::create_escrow_account(&me, &keys.escrow_account, lamports),
+                    instruction::initialize_rental(&keys, &mint, amount, rental_end, claim_window_day),
+                ],
+                &payer,
+                &[&escrow_keypair],
+            )?;
+            println!("Escrow: {}", keys.escrow_account);
+            println!("Rental end: {}", rental_end);
+        }
+        Command::ClaimDamages { escrow, amount, evidence } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let evidence_hash = hash(&std::fs::read(&evidence)?).to_bytes();
+            send(&rpc, &[instruction::claim_damages(&keys, amount, evidence_hash)], &payer, &[])?;
+            println!("Evidence hash: {}", Hash::new(&evidence_hash));
+        }
+        Command::AcceptDamages { escrow, receipt } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let ix = closing(instruction::accept_damages(&keys), &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::DisputeDamages { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::dispute_damages(&keys)], &payer, &[])?;
+        }
+        Command::SettleDamages { escrow, receipt } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let ix = closing(instruction::settle_damages(&keys), &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::ReturnDeposit { escrow, receipt } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let ix = closing(instruction::return_deposit(&keys), &keys, &receipt, &me);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::ChangeJudge { escrow, new_judge, other_keypair } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            let other = read_keypair(&other_keypair)?;
+            send(&rpc, &[instruction::change_judge(&keys, &new_judge)], &payer, &[&other])?;
+        }
+        Command::RequestJudgeChange { escrow, new_judge } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::request_judge_change(&keys, &me, &new_judge)], &payer, &[])?;
+        }
+        Command::ExecuteJudgeChange { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::execute_judge_change(&keys, &me)], &payer, &[])?;
+        }
+        Command::Freeze { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::set_frozen(&keys, &me, true)], &payer, &[])?;
+        }
+        Command::Thaw { escrow } => {
+            let keys = escrow_keys(&rpc, &escrow)?;
+            send(&rpc, &[instruction::set_frozen(&keys, &me, false)], &payer, &[])?;
+        }
+        Command::InitConfig { compliance_authority, judge_change_delay } => {
+            let ix = instruction::initialize_config(&me, &compliance_authority, judge_change_delay);
+            send(&rpc, &[ix], &payer, &[])?;
+            println!("Config: {}", pda::config().0);
+        }
+        Command::UpdateConfig { new_admin, compliance_authority, judge_change_delay } => {
+            let ix = instruction::update_config(&me, &new_admin, &compliance_authority, judge_change_delay);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::Pause => send(&rpc, &[instruction::set_paused(&me, true)], &payer, &[])?,
+        Command::Unpause => send(&rpc, &[instruction::set_paused(&me, false)], &payer, &[])?,
+        Command::CreateCampaign {
+            campaign_id,
+            mint,
+            seller_receive_token_account,
+            judge,
+            price,
+            threshold,
+            duration,
+            convert_within,
+            trial_day,
+        } => {
+            let deadline = rpc::cluster_time(&rpc)? + duration;
+            let ix = instruction::create_campaign(
+                &me,
+                &judge,
+                &mint,
+                &seller_receive_token_account,
+                campaign_id,
+                price,
+                threshold,
+                deadline,
+                deadline + convert_within,
+                trial_day,
+            );
+            send(&rpc, &[ix], &payer, &[])?;
+            println!("Campaign: {}", pda::campaign(campaign_id).0);
+        }
+        Command::JoinCampaign { campaign_id, buyer_deposit_token_account } => {
+            send(&rpc, &[instruction::join_campaign(&me, &buyer_deposit_token_account, campaign_id)], &payer, &[])?;
+        }
+        Command::ConvertCampaign { campaign_id, first_order_code, ship } => {
+            let campaign = rpc::get_campaign(&rpc, campaign_id)?;
+            let lamports = rpc.get_minimum_balance_for_rent_exemption(ESCROW_ACCOUNT_SPACE)?;
+            let deposits = rpc::find_campaign_deposits(&rpc, campaign_id)?;
+            for (order_code, (_, deposit)) in (first_order_code..).zip(&deposits) {
+                let escrow_keypair = Keypair::new();
+                let keys = EscrowKeys {
+                    escrow_account: escrow_keypair.pubkey(),
+                    order_code,
+                    buyer: deposit.buyer_key,
+                    buyer_deposit_token_account: deposit.buyer_deposit_token_account,
+                    seller: campaign.seller_key,
+                    seller_receive_token_account: campaign.seller_receive_token_account,
+                    judge: campaign.judge_key,
+                    rent_payer: me,
+                    fulfiller: campaign.seller_key,
+                };
+                let mut instructions = vec![
+                    instruction::create_escrow_account(&me, &keys.escrow_account, lamports),
+                    instruction::convert_campaign_deposit(&me, &campaign, deposit, &keys.escrow_account, order_code),
+                ];
+                if ship {
+                    instructions.push(instruction::shipping(&keys));
+                }
+                send(&rpc, &instructions, &payer, &[&escrow_keypair])?;
+                println!("Order {}: escrow {} of buyer {}", order_code, keys.escrow_account, deposit.buyer_key);
+            }
+        }
+        Command::RefundCampaignDeposit { campaign_id, buyer_deposit_token_account } => {
+            let ix = instruction::refund_campaign_deposit(&me, &buyer_deposit_token_account, campaign_id);
+            send(&rpc, &[ix], &payer, &[])?;
+        }
+        Command::CloseCampaign { campaign_id } => {
+            send(&rpc, &[instruction::close_campaign(&me, campaign_id)], &payer, &[])?;
+        }
+        Command::ShowCampaign { campaign_id } => {
+            let campaign = rpc::get_campaign(&rpc, campaign_id)?;
+            let now = rpc::cluster_time(&rpc)?;
+            display::print_campaign(&pda::campaign(campaign_id).0, &campaign, now);
+            for (address, deposit) in rpc::find_campaign_deposits(&rpc, campaign_id)? {
+                println!("  {}  buyer {}  {:>12}", address, deposit.buyer_key, deposit.amount);
+            }
+        }
+        Command::ShowConfig => {
+            let address = pda::config().0;
+            let config = state::program_config(&rpc.get_account(&address)?.data)?;
+            println!("Config:               {}", address);
+            println!("Admin:                {}", config.admin);
+            println!("Compliance authority: {}", config.compliance_authority);
+            println!("Judge change delay:   {}", display::duration(config.judge_change_delay));
+            println!("Paused:               {}", config.paused);
+        }
+        Command::List { buyer, seller, judge, order_code, cart_id } => {
+            let filter = EscrowFilter { buyer, seller, judge, order_code, cart_id };
+            let now = rpc::cluster_time(&rpc)?;
+            let mut escrows = rpc::find_escrows(&rpc, &filter)?;
+            escrows.sort_by_key(|(_, escrow)| escrow.order_code);
+            for (address, escrow) in &escrows {
+                display::print_escrow_line(address, escrow, now);
+            }
+            println!("{} escrow(s)", escrows.len());
+        }
+        Command::Show { escrow } => {
+            let (address, escrow) = resolve_escrow(&rpc, &escrow)?;
+            let now = rpc::cluster_time(&rpc)?;
+            let vault_balance = rpc
+                .get_token_account_balance(&pda::vault_account(escrow.order_code).0)
+                .ok()
+                .map(|balance| balance.ui_amount_string);
+            display::print_escrow(&address, &escrow, now, vault_balance);
+            if let Ok(account) = rpc.get_account(&pda::line_items(escrow.order_code).0) {
+                display::print_line_items(&state::line_items(&account.data)?, escrow.trial_day, now);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! Decoding of `lambda_escrow::ErrorCode` from custom program errors.
+
+use anchor_lang::solana_program::instruction::InstructionError;
+use anchor_lang::solana_program::program_error::ProgramError;
+
+pub use lambda_escrow::ErrorCode;
+
+/// Every `ErrorCode` variant, in declaration order.
+const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode::InTrialDay,
+    ErrorCode::InvalidTimelock,
+    ErrorCode::InJudgeChangeTimelock,
+    ErrorCode::ProgramPaused,
+    ErrorCode::EscrowFrozen,
+    ErrorCode::InvalidReceiptAccounts,
+    ErrorCode::AmountOverflow,
+    ErrorCode::VaultMismatch,
+    ErrorCode::InvalidBatchAccounts,
+    ErrorCode::InvalidCartId,
+    ErrorCode::InvalidCartEntry,
+    ErrorCode::InvalidCartAccounts,
+    ErrorCode::CartShipped,
+    ErrorCode::InvalidLineItems,
+    ErrorCode::InvalidItemStatus,
+    ErrorCode::LineItemsMismatch,
+    ErrorCode::NotVesting,
+    ErrorCode::NothingVested,
+    ErrorCode::EscrowDisputed,
+    ErrorCode::TrialEnded,
+    ErrorCode::InvalidIntent,
+    ErrorCode::IntentExpired,
+    ErrorCode::IntentNonceUsed,
+    ErrorCode::MissingIntentSignature,
+    ErrorCode::InvalidReceivableAccounts,
+    ErrorCode::ReceivableNotHeld,
+    ErrorCode::ReceivableTokenized,
+    ErrorCode::InvalidHashLock,
+    ErrorCode::SecretMismatch,
+    ErrorCode::RevealDeadlinePassed,
+    ErrorCode::RevealDeadlineNotPassed,
+    ErrorCode::InvalidCampaign,
+    ErrorCode::CampaignEnded,
+    ErrorCode::CampaignThresholdNotMet,
+    ErrorCode::CampaignNotFailed,
+    ErrorCode::CampaignNotClosable,
+    ErrorCode::InvalidPaymentWindow,
+    ErrorCode::PaymentDeadlinePassed,
+    ErrorCode::PaymentDeadlineNotPassed,
+    ErrorCode::SellerFunded,
+    ErrorCode::InvalidCollateral,
+    ErrorCode::InvalidLongStop,
+    ErrorCode::LongStopNotReached,
+    ErrorCode::DoubleDeposit,
+    ErrorCode::InvalidRentalPeriod,
+    ErrorCode::RentalNotEnded,
+    ErrorCode::ClaimWindowClosed,
+    ErrorCode::ClaimWindowOpen,
+    ErrorCode::InvalidDamages,
+    ErrorCode::DamagesAlreadyClaimed,
+    ErrorCode::RentalEscrow,
+    ErrorCode::InvalidSurcharge,
+    ErrorCode::SurchargePending,
+    ErrorCode::NoPendingSurcharge,
+    ErrorCode::SurchargeExpired,
+    ErrorCode::SurchargeMismatch,
+    ErrorCode::NotUpgradeAuthority,
+    ErrorCode::VestingStarted,
+    ErrorCode::ConversionDeadlineNotPassed,
+    ErrorCode::ResponseWindowOpen,
+    ErrorCode::ResponseWindowClosed,
+    ErrorCode::EscrowItemized,
+];
+
+pub fn decode_error_code(code: u32) -> Option<ErrorCode> {
+    ERROR_CODES.iter().copied().find(|error| ProgramError::from(*error) == ProgramError::Custom(code))
+}
+
+pub fn decode_program_error(error: &ProgramError) -> Option<ErrorCode> {
+    match error {
+        ProgramError::Custom(code) => decode_error_code(*code),
+        _ => None,
+    }
+}
+
+pub fn decode_instruction_error(error: &InstructionError) -> Option<ErrorCode> {
+    match error {
+        InstructionError::Custom(code) => decode_error_code(*code),
+        _ => None,
+    }
+}
@@ -0,0 +1,1280 @@
+//! Instruction builders, one per `lambda_escrow` instruction.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::{ed25519_program, system_instruction, system_program, sysvar};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use lambda_escrow::{accounts, instruction as ix, Campaign, CampaignDeposit, CartEntry, EscrowAccount};
+
+pub use lambda_escrow::{Intent, NewLineItem};
+
+use crate::pda;
+
+/// Space of an `EscrowAccount`, discriminator included.
+pub const ESCROW_ACCOUNT_SPACE: usize = 8 + EscrowAccount::LEN;
+
+/// Escrows per `settle_batch` instruction. Every escrow costs two token CPIs, two PDA
+/// derivations and four account keys, more do not fit in the compute or transaction size limits.
+pub const SETTLE_BATCH_SIZE: usize = 5;
+
+/// Cart entries per `checkout`. Each one also needs a `create_escrow_account` instruction
+/// and the escrow keypair's signature in the transaction, more do not fit in its size limit.
+pub const MAX_CART_ENTRIES: usize = 3;
+
+/// Keys of one escrow and its parties, shared by most builders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EscrowKeys {
+    pub escrow_account: Pubkey,
+    pub order_code: u64,
+    pub buyer: Pubkey,
+    pub buyer_deposit_token_account: Pubkey,
+    pub seller: Pubkey,
+    pub seller_receive_token_account: Pubkey,
+    pub judge: Pubkey,
+    /// Pays the rent at `initialize` and gets it back on close, usually the buyer.
+    pub rent_payer: Pubkey,
+    /// Ships the order, `seller` unless the receivable was sold with `transfer_receivable`.
+    pub fulfiller: Pubkey,
+}
+
+impl EscrowKeys {
+    pub fn from_escrow(escrow_account: Pubkey, escrow: &EscrowAccount) -> Self {
+        EscrowKeys {
+            escrow_account,
+            order_code: escrow.order_code,
+            buyer: escrow.buyer_key,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller_key,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            judge: escrow.judge_key,
+            rent_payer: escrow.rent_payer,
+            fulfiller: escrow.fulfiller_key,
+        }
+    }
+
+    pub fn vault_account(&self) -> Pubkey {
+        pda::vault_account(self.order_code).0
+    }
+
+    pub fn vault_authority(&self) -> Pubkey {
+        pda::vault_authority(self.order_code).0
+    }
+}
+
+fn instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: lambda_escrow::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// Allocates the escrow account. Send it in the same transaction as `initialize`,
+/// signed by `escrow_account`.
+pub fn create_escrow_account(payer: &Pubkey, escrow_account: &Pubkey, lamports: u64) -> Instruction {
+    system_instruction::create_account(
+        payer,
+        escrow_account,
+        lamports,
+        ESCROW_ACCOUNT_SPACE as u64,
+        &lambda_escrow::ID,
+    )
+}
+
+/// Asks a closing instruction (`cancel`, `exchange`, `reclaim_unrevealed`, `release`,
+/// `reclaim_unpaid`, `settle`, `mutual_cancel`, `accept_damages`, `settle_damages`,
+/// `return_deposit`, `adjudge_for_buyer`, `adjudge_for_seller`) to write a `SettlementReceipt`
+/// of `escrow_account`, paid by `payer`. Goes after the long stop recipient of `expire_long_stop`.
+pub fn with_settlement_receipt(mut instruction: Instruction, escrow_account: &Pubkey, payer: &Pubkey) -> Instruction {
+    instruction.accounts.push(AccountMeta::new(pda::settlement_receipt(escrow_account).0, false));
+    instruction.accounts.push(AccountMeta::new(*payer, true));
+    instruction.accounts.push(AccountMeta::new_readonly(system_program::ID, false));
+    instruction
+}
+
+/// Asks `initialize` to mint the escrow's 1-of-1 receivable token to the seller, whoever
+/// holds it is paid by `exchange` and `adjudge_for_seller`. The rent payer pays for both accounts.
+pub fn with_receivable(mut instruction: Instruction, order_code: u64) -> Instruction {
+    instruction.accounts.push(AccountMeta::new(pda::receivable_mint(order_code).0, false));
+    instruction.accounts.push(AccountMeta::new(pda::receivable_token_account(order_code).0, false));
+    instruction
+}
+
+pub fn initialize(keys: &EscrowKeys, mint: &Pubkey, amount: u64, trial_day: u16) -> Instruction {
+    let (vault_account, vault_account_bump) = pda::vault_account(keys.order_code);
+    instruction(
+        accounts::Initialize {
+            buyer: keys.buyer,
+            payer: keys.rent_payer,
+            seller: keys.seller,
+            judge: keys.judge,
+            config: pda::config().0,
+            mint: *mint,
+            vault_account,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::Initialize {
+            _vault_account_bump: vault_account_bump,
+            amount,
+            order_code: keys.order_code,
+            trial_day,
+        },
+    )
+}
+
+/// One seller of a `checkout`, with the escrow account created for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CartItem {
+    pub escrow_account: Pubkey,
+    pub order_code: u64,
+    pub seller: Pubkey,
+    pub seller_receive_token_account: Pubkey,
+    pub amount: u64,
+    pub trial_day: u16,
+}
+
+/// Creates one escrow per item, all funded from `buyer_deposit_token_account` and
+/// linked by `cart_id`. At most `MAX_CART_ENTRIES` items.
+pub fn checkout(
+    buyer: &Pubkey,
+    buyer_deposit_token_account: &Pubkey,
+    judge: &Pubkey,
+    mint: &Pubkey,
+    cart_id: u64,
+    items: &[CartItem],
+) -> Instruction {
+    let mut instruction = instruction(
+        accounts::Checkout {
+            buyer: *buyer,
+            judge: *judge,
+            config: pda::config().0,
+            mint: *mint,
+            buyer_deposit_token_account: *buyer_deposit_token_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::Checkout {
+            cart_id,
+            entries: items
+                .iter()
+                .map(|item| CartEntry {
+                    order_code: item.order_code,
+                    amount: item.amount,
+                    trial_day: item.trial_day,
+                })
+                .collect(),
+        },
+    );
+    for item in items {
+        instruction.accounts.push(AccountMeta::new(item.escrow_account, false));
+        instruction.accounts.push(AccountMeta::new(pda::vault_account(item.order_code).0, false));
+        instruction.accounts.push(AccountMeta::new_readonly(item.seller, false));
+        instruction.accounts.push(AccountMeta::new_readonly(item.seller_receive_token_account, false));
+    }
+    instruction
+}
+
+pub fn cancel(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::Cancel {
+            buyer: keys.buyer,
+            rent_payer: keys.rent_payer,
+            config: pda::config().0,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Cancel { order_code: keys.order_code },
+    )
+}
+
+/// Cancels `escrows`, all New escrows of `cart_id` for the whole cart.
+pub fn cancel_cart(buyer: &Pubkey, buyer_deposit_token_account: &Pubkey, cart_id: u64, escrows: &[EscrowKeys]) -> Instruction {
+    let mut instruction = instruction(
+        accounts::CancelCart {
+            buyer: *buyer,
+            config: pda::config().0,
+            buyer_deposit_token_account: *buyer_deposit_token_account,
+            token_program: spl_token::ID,
+        },
+        ix::CancelCart { cart_id },
+    );
+    for keys in escrows {
+        instruction.accounts.push(AccountMeta::new(keys.escrow_account, false));
+        instruction.accounts.push(AccountMeta::new(keys.vault_account(), false));
+        instruction.accounts.push(AccountMeta::new_readonly(keys.vault_authority(), false));
+    }
+    instruction
+}
+
+pub fn cancel_partial(keys: &EscrowKeys, amount: u64) -> Instruction {
+    instruction(
+        accounts::CancelPartial {
+            buyer: keys.buyer,
+            config: pda::config().0,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::CancelPartial { order_code: keys.order_code, amount },
+    )
+}
+
+pub fn charge_more(keys: &EscrowKeys, amount: u64) -> Instruction {
+    instruction(
+        accounts::ChargeMore {
+            buyer: keys.buyer,
+            config: pda::config().0,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.seller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            token_program: spl_token::ID,
+        },
+        ix::ChargeMore { order_code: keys.order_code, amount },
+    )
+}
+
+/// Signed by `keys.fulfiller`. The buyer has until `deadline` to approve it.
+pub fn request_surcharge(keys: &EscrowKeys, amount: u64, reason_hash: [u8; 32], deadline: i64) -> Instruction {
+    instruction(
+        accounts::RequestSurcharge {
+            seller: keys.fulfiller,
+            escrow_account: keys.escrow_account,
+        },
+        ix::RequestSurcharge { order_code: keys.order_code, amount, reason_hash, deadline },
+    )
+}
+
+/// Funds the pending surcharge, `amount` has to be the one requested.
+pub fn approve_surcharge(keys: &EscrowKeys, amount: u64) -> Instruction {
+    instruction(
+        accounts::ChargeMore {
+            buyer: keys.buyer,
+            config: pda::config().0,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.seller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            token_program: spl_token::ID,
+        },
+        ix::ApproveSurcharge { order_code: keys.order_code, amount },
+    )
+}
+
+pub fn reject_surcharge(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::RejectSurcharge {
+            buyer: keys.buyer,
+            escrow_account: keys.escrow_account,
+        },
+        ix::RejectSurcharge { order_code: keys.order_code },
+    )
+}
+
+pub fn shipping(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::Shipping {
+            buyer: keys.buyer,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.fulfiller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Shipping { order_code: keys.order_code },
+    )
+}
+
+pub fn delivered(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::Delivered {
+            buyer: keys.buyer,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.seller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Delivered { order_code: keys.order_code },
+    )
+}
+
+pub fn refund(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::Refund {
+            buyer: keys.buyer,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.seller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Refund { order_code: keys.order_code },
+    )
+}
+
+pub fn refund_partial(keys: &EscrowKeys, amount: u64) -> Instruction {
+    instruction(
+        accounts::RefundPartial {
+            buyer: keys.buyer,
+            config: pda::config().0,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.seller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            token_program: spl_token::ID,
+        },
+        ix::RefundPartial { order_code: keys.order_code, amount },
+    )
+}
+
+pub fn exchange(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::Exchange {
+            buyer: keys.buyer,
+            rent_payer: keys.rent_payer,
+            config: pda::config().0,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.seller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            token_program: spl_token::ID,
+        },
+        ix::Exchange,
+    )
+}
+
+/// `exchange` of a tokenized escrow, signed by `holder` and paid to its
+/// `holder_receive_token_account`. `holder_receivable_account` holds the receivable token.
+/// Add a receipt with `with_settlement_receipt` after this.
+pub fn exchange_to_holder(
+    keys: &EscrowKeys,
+    holder: &Pubkey,
+    holder_receivable_account: &Pubkey,
+    holder_receive_token_account: &Pubkey,
+) -> Instruction {
+    let keys = EscrowKeys { seller: *holder, seller_receive_token_account: *holder_receive_token_account, ..*keys };
+    let mut instruction = exchange(&keys);
+    instruction.accounts.push(AccountMeta::new_readonly(*holder_receivable_account, false));
+    instruction
+}
+
+/// Settles matured `escrows` of `seller` paid to `seller_receive_token_account`.
+/// At most `SETTLE_BATCH_SIZE` escrows, see `settle_batches`.
+pub fn settle_batch(seller: &Pubkey, seller_receive_token_account: &Pubkey, escrows: &[EscrowKeys]) -> Instruction {
+    let mut instruction = instruction(
+        accounts::SettleBatch {
+            seller: *seller,
+            config: pda::config().0,
+            seller_receive_token_account: *seller_receive_token_account,
+            token_program: spl_token::ID,
+        },
+        ix::SettleBatch,
+    );
+    for keys in escrows {
+        instruction.accounts.push(AccountMeta::new(keys.escrow_account, false));
+        instruction.accounts.push(AccountMeta::new(keys.vault_account(), false));
+        instruction.accounts.push(AccountMeta::new_readonly(keys.vault_authority(), false));
+        instruction.accounts.push(AccountMeta::new(keys.rent_payer, false));
+    }
+    instruction
+}
+
+/// `settle_batch` instructions of `SETTLE_BATCH_SIZE` escrows, send each in its own transaction.
+pub fn settle_batches(seller: &Pubkey, seller_receive_token_account: &Pubkey, escrows: &[EscrowKeys]) -> Vec<Instruction> {
+    escrows
+        .chunks(SETTLE_BATCH_SIZE)
+        .map(|chunk| settle_batch(seller, seller_receive_token_account, chunk))
+        .collect()
+}
+
+/// Splits the New escrow into `items`, worth the escrow amount together.
+pub fn set_line_items(keys: &EscrowKeys, items: Vec<NewLineItem>) -> Instruction {
+    let (line_items, line_items_bump) = pda::line_items(keys.order_code);
+    instruction(
+        accounts::SetLineItems {
+            buyer: keys.buyer,
+            escrow_account: keys.escrow_account,
+            line_items,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        ix::SetLineItems {
+            _line_items_bump: line_items_bump,
+            order_code: keys.order_code,
+            items,
+        },
+    )
+}
+
+pub fn ship_items(keys: &EscrowKeys, indexes: Vec<u8>) -> Instruction {
+    instruction(
+        accounts::ShipItems {
+            seller: keys.fulfiller,
+            escrow_account: keys.escrow_account,
+            line_items: pda::line_items(keys.order_code).0,
+        },
+        ix::ShipItems { order_code: keys.order_code, indexes },
+    )
+}
+
+pub fn deliver_items(keys: &EscrowKeys, indexes: Vec<u8>) -> Instruction {
+    instruction(
+        accounts::DeliverItems {
+            buyer: keys.buyer,
+            escrow_account: keys.escrow_account,
+            line_items: pda::line_items(keys.order_code).0,
+        },
+        ix::DeliverItems { order_code: keys.order_code, indexes },
+    )
+}
+
+pub fn settle_items(keys: &EscrowKeys, indexes: Vec<u8>) -> Instruction {
+    instruction(
+        accounts::SettleItems {
+            seller: keys.seller,
+            config: pda::config().0,
+            buyer: keys.buyer,
+            rent_payer: keys.rent_payer,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            line_items: pda::line_items(keys.order_code).0,
+            token_program: spl_token::ID,
+        },
+        ix::SettleItems { order_code: keys.order_code, indexes },
+    )
+}
+
+pub fn cancel_items(keys: &EscrowKeys, indexes: Vec<u8>) -> Instruction {
+    instruction(
+        accounts::CancelItems {
+            buyer: keys.buyer,
+            rent_payer: keys.rent_payer,
+            config: pda::config().0,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            line_items: pda::line_items(keys.order_code).0,
+            token_program: spl_token::ID,
+        },
+        ix::CancelItems { order_code: keys.order_code, indexes },
+    )
+}
+
+/// Lets the escrow unlock to the seller over the trial, before it is Delivered.
+pub fn set_vesting(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::SetVesting {
+            buyer: keys.buyer,
+            escrow_account: keys.escrow_account,
+        },
+        ix::SetVesting { order_code: keys.order_code },
+    )
+}
+
+pub fn withdraw_vested(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::WithdrawVested {
+            seller: keys.seller,
+            config: pda::config().0,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            token_program: spl_token::ID,
+        },
+        ix::WithdrawVested { order_code: keys.order_code },
+    )
+}
+
+/// Pays the seller what vested so far and leaves the rest to the judge.
+pub fn dispute(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::Dispute {
+            buyer: keys.buyer,
+            config: pda::config().0,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            token_program: spl_token::ID,
+        },
+        ix::Dispute { order_code: keys.order_code },
+    )
+}
+
+/// Ed25519 program instruction checking `signature` of `message` by `public_key`, with all
+/// three in its own data. Goes right before the `relay_*` instruction it authorizes.
+pub fn ed25519_verify(public_key: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    const OFFSETS_START: usize = 2;
+    const OFFSETS_SIZE: usize = 14;
+    let public_key_offset = OFFSETS_START + OFFSETS_SIZE;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+    let this_instruction = u16::MAX;
+
+    let mut data = vec![1u8, 0];
+    for field in &[
+        signature_offset as u16,
+        this_instruction,
+        public_key_offset as u16,
+        this_instruction,
+        message_offset as u16,
+        message.len() as u16,
+        this_instruction,
+    ] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    data.extend_from_slice(public_key.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+    Instruction {
+        program_id: ed25519_program::ID,
+        accounts: Vec::new(),
+        data,
+    }
+}
+
+/// Lets `relay_charge_more` take up to `amount` from the buyer's deposit. Signed by the buyer
+/// once, the relayer can pay its fees.
+pub fn approve_intent_delegate(keys: &EscrowKeys, amount: u64) -> Instruction {
+    spl_token::instruction::approve(
+        &spl_token::ID,
+        &keys.buyer_deposit_token_account,
+        &pda::intent_delegate().0,
+        &keys.buyer,
+        &[],
+        amount,
+    )
+    .unwrap()
+}
+
+/// `delivered` from a buyer `intent` signed off-chain, sent and paid by `relayer`.
+pub fn relay_delivered(relayer: &Pubkey, keys: &EscrowKeys, intent: Intent, signature: &[u8; 64]) -> Vec<Instruction> {
+    vec![
+        ed25519_verify(&keys.buyer, signature, &intent.message(&keys.escrow_account)),
+        instruction(
+            accounts::RelayDelivered {
+                relayer: *relayer,
+                escrow_account: keys.escrow_account,
+                instructions: sysvar::instructions::ID,
+            },
+            ix::RelayDelivered { intent },
+        ),
+    ]
+}
+
+/// `cancel` from a buyer `intent`, see `relay_delivered`.
+pub fn relay_cancel(relayer: &Pubkey, keys: &EscrowKeys, intent: Intent, signature: &[u8; 64]) -> Vec<Instruction> {
+    vec![
+        ed25519_verify(&keys.buyer, signature, &intent.message(&keys.escrow_account)),
+        instruction(
+            accounts::RelayCancel {
+                relayer: *relayer,
+                buyer: keys.buyer,
+                rent_payer: keys.rent_payer,
+                config: pda::config().0,
+                buyer_deposit_token_account: keys.buyer_deposit_token_account,
+                vault_account: keys.vault_account(),
+                vault_authority: keys.vault_authority(),
+                escrow_account: keys.escrow_account,
+                instructions: sysvar::instructions::ID,
+                token_program: spl_token::ID,
+            },
+            ix::RelayCancel { intent },
+        ),
+    ]
+}
+
+/// `charge_more` from a buyer `intent`, see `relay_delivered` and `approve_intent_delegate`.
+pub fn relay_charge_more(relayer: &Pubkey, keys: &EscrowKeys, intent: Intent, signature: &[u8; 64]) -> Vec<Instruction> {
+    vec![
+        ed25519_verify(&keys.buyer, signature, &intent.message(&keys.escrow_account)),
+        instruction(
+            accounts::RelayChargeMore {
+                relayer: *relayer,
+                config: pda::config().0,
+                buyer_deposit_token_account: keys.buyer_deposit_token_account,
+                intent_delegate: pda::intent_delegate().0,
+                escrow_account: keys.escrow_account,
+                vault_account: keys.vault_account(),
+                instructions: sysvar::instructions::ID,
+                token_program: spl_token::ID,
+            },
+            ix::RelayChargeMore { intent },
+        ),
+    ]
+}
+
+/// Sells the escrow's payout to `new_seller`, paid to `new_seller_receive_token_account` from
+/// then on. Signed by the current `seller`.
+pub fn transfer_receivable(keys: &EscrowKeys, new_seller: &Pubkey, new_seller_receive_token_account: &Pubkey) -> Instruction {
+    instruction(
+        accounts::TransferReceivable {
+            seller: keys.seller,
+            config: pda::config().0,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            new_seller: *new_seller,
+            new_seller_receive_token_account: *new_seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+        },
+        ix::TransferReceivable { order_code: keys.order_code },
+    )
+}
+
+/// Ships a digital good as the sha256 `secret_hash` of a secret, to reveal with
+/// `reveal_and_claim` by `reveal_deadline`.
+pub fn ship_hash_locked(keys: &EscrowKeys, secret_hash: [u8; 32], reveal_deadline: i64) -> Instruction {
+    instruction(
+        accounts::Shipping {
+            buyer: keys.buyer,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.fulfiller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::ShipHashLocked { order_code: keys.order_code, secret_hash, reveal_deadline },
+    )
+}
+
+pub fn reveal_and_claim(keys: &EscrowKeys, secret: Vec<u8>) -> Instruction {
+    instruction(
+        accounts::RevealAndClaim {
+            seller: keys.fulfiller,
+            escrow_account: keys.escrow_account,
+        },
+        ix::RevealAndClaim { order_code: keys.order_code, secret },
+    )
+}
+
+/// Refunds a hash-locked escrow not revealed by its deadline. Needs no signature, any fee
+/// payer can send it.
+pub fn reclaim_unrevealed(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::ReclaimUnrevealed {
+            buyer: keys.buyer,
+            rent_payer: keys.rent_payer,
+            config: pda::config().0,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::ReclaimUnrevealed { order_code: keys.order_code },
+    )
+}
+
+/// Opens group buy `campaign_id` of `seller`, who pays its rent.
+#[allow(clippy::too_many_arguments)]
+pub fn create_campaign(
+    seller: &Pubkey,
+    judge: &Pubkey,
+    mint: &Pubkey,
+    seller_receive_token_account: &Pubkey,
+    campaign_id: u64,
+    price: u64,
+    threshold: u32,
+    deadline: i64,
+    conversion_deadline: i64,
+    trial_day: u16,
+) -> Instruction {
+    let (campaign, campaign_bump) = pda::campaign(campaign_id);
+    let (campaign_vault, campaign_vault_bump) = pda::campaign_vault(campaign_id);
+    instruction(
+        accounts::CreateCampaign {
+            seller: *seller,
+            judge: *judge,
+            config: pda::config().0,
+            mint: *mint,
+            seller_receive_token_account: *seller_receive_token_account,
+            campaign,
+            campaign_vault,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::CreateCampaign {
+            _campaign_bump: campaign_bump,
+            _campaign_vault_bump: campaign_vault_bump,
+            campaign_id,
+            price,
+            threshold,
+            deadline,
+            conversion_deadline,
+            trial_day,
+        },
+    )
+}
+
+pub fn join_campaign(buyer: &Pubkey, buyer_deposit_token_account: &Pubkey, campaign_id: u64) -> Instruction {
+    let (deposit, deposit_bump) = pda::campaign_deposit(campaign_id, buyer);
+    instruction(
+        accounts::JoinCampaign {
+            buyer: *buyer,
+            config: pda::config().0,
+            campaign: pda::campaign(campaign_id).0,
+            campaign_vault: pda::campaign_vault(campaign_id).0,
+            buyer_deposit_token_account: *buyer_deposit_token_account,
+            deposit,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::JoinCampaign { _deposit_bump: deposit_bump, campaign_id },
+    )
+}
+
+/// Turns `deposit` into a New escrow at `escrow_account`, created beforehand with
+/// `create_escrow_account` paid by `payer`, like for `initialize`. `payer` has to be the seller
+/// until the campaign's `conversion_deadline`, and is the escrow's `rent_payer`.
+pub fn convert_campaign_deposit(
+    payer: &Pubkey,
+    campaign: &Campaign,
+    deposit: &CampaignDeposit,
+    escrow_account: &Pubkey,
+    order_code: u64,
+) -> Instruction {
+    let (vault_account, vault_account_bump) = pda::vault_account(order_code);
+    instruction(
+        accounts::ConvertCampaignDeposit {
+            payer: *payer,
+            seller: campaign.seller_key,
+            buyer: deposit.buyer_key,
+            config: pda::config().0,
+            campaign: pda::campaign(campaign.campaign_id).0,
+            campaign_vault: pda::campaign_vault(campaign.campaign_id).0,
+            deposit: pda::campaign_deposit(campaign.campaign_id, &deposit.buyer_key).0,
+            mint: campaign.mint,
+            vault_account,
+            escrow_account: *escrow_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::ConvertCampaignDeposit {
+            _vault_account_bump: vault_account_bump,
+            campaign_id: campaign.campaign_id,
+            order_code,
+        },
+    )
+}
+
+pub fn refund_campaign_deposit(buyer: &Pubkey, buyer_deposit_token_account: &Pubkey, campaign_id: u64) -> Instruction {
+    instruction(
+        accounts::RefundCampaignDeposit {
+            buyer: *buyer,
+            config: pda::config().0,
+            campaign: pda::campaign(campaign_id).0,
+            campaign_vault: pda::campaign_vault(campaign_id).0,
+            buyer_deposit_token_account: *buyer_deposit_token_account,
+            deposit: pda::campaign_deposit(campaign_id, buyer).0,
+            token_program: spl_token::ID,
+        },
+        ix::RefundCampaignDeposit { campaign_id },
+    )
+}
+
+pub fn close_campaign(seller: &Pubkey, campaign_id: u64) -> Instruction {
+    instruction(
+        accounts::CloseCampaign {
+            seller: *seller,
+            campaign: pda::campaign(campaign_id).0,
+            campaign_vault: pda::campaign_vault(campaign_id).0,
+            token_program: spl_token::ID,
+        },
+        ix::CloseCampaign { campaign_id },
+    )
+}
+
+/// Seller-funded escrow of a P2P trade: the seller locks `amount` from
+/// `keys.seller_receive_token_account`, released to `keys.buyer_deposit_token_account` once the
+/// buyer paid off-chain. The buyer has `payment_window` seconds to `mark_paid`.
+pub fn initialize_seller_funded(keys: &EscrowKeys, mint: &Pubkey, amount: u64, payment_window: i64) -> Instruction {
+    let (vault_account, vault_account_bump) = pda::vault_account(keys.order_code);
+    instruction(
+        accounts::InitializeSellerFunded {
+            seller: keys.seller,
+            payer: keys.rent_payer,
+            buyer: keys.buyer,
+            judge: keys.judge,
+            config: pda::config().0,
+            mint: *mint,
+            vault_account,
+            buyer_receive_token_account: keys.buyer_deposit_token_account,
+            seller_deposit_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::InitializeSellerFunded {
+            _vault_account_bump: vault_account_bump,
+            amount,
+            order_code: keys.order_code,
+            payment_window,
+        },
+    )
+}
+
+pub fn mark_paid(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::MarkPaid {
+            buyer: keys.buyer,
+            escrow_account: keys.escrow_account,
+        },
+        ix::MarkPaid { order_code: keys.order_code },
+    )
+}
+
+pub fn release(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::Release {
+            seller: keys.seller,
+            rent_payer: keys.rent_payer,
+            config: pda::config().0,
+            buyer_receive_token_account: keys.buyer_deposit_token_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Release { order_code: keys.order_code },
+    )
+}
+
+/// Returns a seller-funded escrow not marked paid by its deadline to the seller. Needs no
+/// signature, any fee payer can send it.
+pub fn reclaim_unpaid(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::ReclaimUnpaid {
+            seller: keys.seller,
+            rent_payer: keys.rent_payer,
+            config: pda::config().0,
+            seller_deposit_token_account: keys.seller_receive_token_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::ReclaimUnpaid { order_code: keys.order_code },
+    )
+}
+
+/// Double-deposit escrow with no judge, signed by both the buyer and the seller. The buyer
+/// locks `price` and `buyer_collateral`, the seller `seller_collateral`. From `long_stop`, 0 for
+/// never, anyone can burn it, or donate it to `long_stop_recipient`, a token account of `mint`.
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_double_deposit(
+    keys: &EscrowKeys,
+    mint: &Pubkey,
+    price: u64,
+    buyer_collateral: u64,
+    seller_collateral: u64,
+    long_stop: i64,
+    long_stop_recipient: Option<&Pubkey>,
+) -> Instruction {
+    let (vault_account, vault_account_bump) = pda::vault_account(keys.order_code);
+    let mut instruction = instruction(
+        accounts::InitializeDoubleDeposit {
+            buyer: keys.buyer,
+            seller: keys.seller,
+            payer: keys.rent_payer,
+            config: pda::config().0,
+            mint: *mint,
+            vault_account,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::InitializeDoubleDeposit {
+            _vault_account_bump: vault_account_bump,
+            price,
+            order_code: keys.order_code,
+            buyer_collateral,
+            seller_collateral,
+            long_stop,
+        },
+    );
+    if let Some(recipient) = long_stop_recipient {
+        instruction.accounts.push(AccountMeta::new_readonly(*recipient, false));
+    }
+    instruction
+}
+
+fn mutual_settlement(keys: &EscrowKeys) -> accounts::MutualSettlement {
+    accounts::MutualSettlement {
+        buyer: keys.buyer,
+        seller: keys.seller,
+        rent_payer: keys.rent_payer,
+        config: pda::config().0,
+        buyer_deposit_token_account: keys.buyer_deposit_token_account,
+        seller_receive_token_account: keys.seller_receive_token_account,
+        vault_account: keys.vault_account(),
+        vault_authority: keys.vault_authority(),
+        escrow_account: keys.escrow_account,
+        token_program: spl_token::ID,
+    }
+}
+
+/// Pays the seller the price and its collateral, the buyer its collateral. Signed by both.
+pub fn settle(keys: &EscrowKeys) -> Instruction {
+    instruction(mutual_settlement(keys), ix::Settle { order_code: keys.order_code })
+}
+
+/// Pays the buyer the price and its collateral, the seller its collateral. Signed by both.
+pub fn mutual_cancel(keys: &EscrowKeys) -> Instruction {
+    instruction(mutual_settlement(keys), ix::MutualCancel { order_code: keys.order_code })
+}
+
+/// Burns a double-deposit escrow past its long stop, or donates it to its `long_stop_recipient`
+/// unless that is `Pubkey::default()`. Needs no signature, any fee payer can send it.
+pub fn expire_long_stop(keys: &EscrowKeys, mint: &Pubkey, long_stop_recipient: &Pubkey) -> Instruction {
+    let mut instruction = instruction(
+        accounts::ExpireLongStop {
+            rent_payer: keys.rent_payer,
+            config: pda::config().0,
+            mint: *mint,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::ExpireLongStop { order_code: keys.order_code },
+    );
+    if *long_stop_recipient != Pubkey::default() {
+        instruction.accounts.push(AccountMeta::new(*long_stop_recipient, false));
+    }
+    instruction
+}
+
+/// Security deposit of a rental: the buyer locks `amount` until `rental_end`, after which the
+/// seller has `claim_window_day` days to `claim_damages`.
+pub fn initialize_rental(
+    keys: &EscrowKeys,
+    mint: &Pubkey,
+    amount: u64,
+    rental_end: i64,
+    claim_window_day: u16,
+) -> Instruction {
+    let (vault_account, vault_account_bump) = pda::vault_account(keys.order_code);
+    instruction(
+        accounts::InitializeRental {
+            buyer: keys.buyer,
+            payer: keys.rent_payer,
+            seller: keys.seller,
+            judge: keys.judge,
+            config: pda::config().0,
+            mint: *mint,
+            vault_account,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::InitializeRental {
+            _vault_account_bump: vault_account_bump,
+            amount,
+            order_code: keys.order_code,
+            rental_end,
+            claim_window_day,
+        },
+    )
+}
+
+/// `evidence_hash` is the hash of the seller's evidence kept off-chain, e.g. its sha256.
+pub fn claim_damages(keys: &EscrowKeys, amount: u64, evidence_hash: [u8; 32]) -> Instruction {
+    instruction(
+        accounts::ClaimDamages {
+            seller: keys.seller,
+            escrow_account: keys.escrow_account,
+        },
+        ix::ClaimDamages { order_code: keys.order_code, amount, evidence_hash },
+    )
+}
+
+pub fn accept_damages(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::AcceptDamages {
+            buyer: keys.buyer,
+            rent_payer: keys.rent_payer,
+            config: pda::config().0,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::AcceptDamages { order_code: keys.order_code },
+    )
+}
+
+pub fn dispute_damages(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::DisputeDamages {
+            buyer: keys.buyer,
+            config: pda::config().0,
+            escrow_account: keys.escrow_account,
+        },
+        ix::DisputeDamages { order_code: keys.order_code },
+    )
+}
+
+/// Settles claimed damages the buyer did not answer as accepted once the response window is
+/// over. Needs no signature, the seller or any fee payer can send it.
+pub fn settle_damages(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::SettleDamages {
+            rent_payer: keys.rent_payer,
+            config: pda::config().0,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::SettleDamages { order_code: keys.order_code },
+    )
+}
+
+/// Returns an unclaimed rental deposit to the buyer once the claim window is over. Needs no
+/// signature, any fee payer can send it.
+pub fn return_deposit(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::ReturnDeposit {
+            rent_payer: keys.rent_payer,
+            config: pda::config().0,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::ReturnDeposit { order_code: keys.order_code },
+    )
+}
+
+pub fn adjudge(keys: &EscrowKeys, status: u8) -> Instruction {
+    instruction(
+        accounts::Adjudge {
+            judge: keys.judge,
+            buyer: keys.buyer,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.seller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Adjudge { order_code: keys.order_code, status },
+    )
+}
+
+pub fn adjudge_partial(keys: &EscrowKeys, amount: u64) -> Instruction {
+    instruction(
+        accounts::AdjudgePartial {
+            judge: keys.judge,
+            config: pda::config().0,
+            buyer: keys.buyer,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.seller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            token_program: spl_token::ID,
+        },
+        ix::AdjudgePartial { order_code: keys.order_code, amount },
+    )
+}
+
+pub fn adjudge_for_buyer(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::AdjudgeForBuyer {
+            judge: keys.judge,
+            config: pda::config().0,
+            buyer: keys.buyer,
+            rent_payer: keys.rent_payer,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.seller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            token_program: spl_token::ID,
+        },
+        ix::AdjudgeForBuyer { order_code: keys.order_code },
+    )
+}
+
+pub fn adjudge_for_seller(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::AdjudgeForSeller {
+            judge: keys.judge,
+            config: pda::config().0,
+            buyer: keys.buyer,
+            rent_payer: keys.rent_payer,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.seller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+            vault_authority: keys.vault_authority(),
+            token_program: spl_token::ID,
+        },
+        ix::AdjudgeForSeller { order_code: keys.order_code },
+    )
+}
+
+/// `adjudge_for_seller` of a tokenized escrow, paid to the `holder_receive_token_account` of
+/// whoever holds the receivable token in `holder_receivable_account`.
+pub fn adjudge_for_holder(
+    keys: &EscrowKeys,
+    holder_receivable_account: &Pubkey,
+    holder_receive_token_account: &Pubkey,
+) -> Instruction {
+    let keys = EscrowKeys { seller_receive_token_account: *holder_receive_token_account, ..*keys };
+    let mut instruction = adjudge_for_seller(&keys);
+    instruction.accounts.push(AccountMeta::new_readonly(*holder_receivable_account, false));
+    instruction
+}
+
+pub fn update_trial_day(keys: &EscrowKeys, trial_day: u16) -> Instruction {
+    instruction(
+        accounts::UpdateTrialDay {
+            judge: keys.judge,
+            buyer: keys.buyer,
+            buyer_deposit_token_account: keys.buyer_deposit_token_account,
+            seller: keys.seller,
+            seller_receive_token_account: keys.seller_receive_token_account,
+            escrow_account: keys.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::UpdateTrialDay { order_code: keys.order_code, trial_day },
+    )
+}
+
+pub fn sync_vault(keys: &EscrowKeys) -> Instruction {
+    instruction(
+        accounts::SyncVault {
+            judge: keys.judge,
+            escrow_account: keys.escrow_account,
+            vault_account: keys.vault_account(),
+        },
+        ix::SyncVault { order_code: keys.order_code },
+    )
+}
+
+pub fn initialize_config(admin: &Pubkey, compliance_authority: &Pubkey, judge_change_delay: i64) -> Instruction {
+    let (config, config_bump) = pda::config();
+    instruction(
+        accounts::InitializeConfig {
+            admin: *admin,
+            compliance_authority: *compliance_authority,
+            config,
+            program_data: pda::program_data().0,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        ix::InitializeConfig { _config_bump: config_bump, judge_change_delay },
+    )
+}
+
+pub fn update_config(
+    admin: &Pubkey,
+    new_admin: &Pubkey,
+    compliance_authority: &Pubkey,
+    judge_change_delay: i64,
+) -> Instruction {
+    instruction(
+        accounts::UpdateConfig {
+            admin: *admin,
+            new_admin: *new_admin,
+            compliance_authority: *compliance_authority,
+            config: pda::config().0,
+        },
+        ix::UpdateConfig { judge_change_delay },
+    )
+}
+
+pub fn set_paused(admin: &Pubkey, paused: bool) -> Instruction {
+    instruction(
+        accounts::SetPaused { admin: *admin, config: pda::config().0 },
+        ix::SetPaused { paused },
+    )
+}
+
+pub fn set_frozen(keys: &EscrowKeys, compliance_authority: &Pubkey, frozen: bool) -> Instruction {
+    instruction(
+        accounts::SetFrozen {
+            compliance_authority: *compliance_authority,
+            config: pda::config().0,
+            escrow_account: keys.escrow_account,
+        },
+        ix::SetFrozen { order_code: keys.order_code, frozen },
+    )
+}
+
+pub fn change_judge(keys: &EscrowKeys, new_judge: &Pubkey) -> Instruction {
+    instruction(
+        accounts::ChangeJudge {
+            buyer: keys.buyer,
+            seller: keys.seller,
+            new_judge: *new_judge,
+            escrow_account: keys.escrow_account,
+        },
+        ix::ChangeJudge { order_code: keys.order_code },
+    )
+}
+
+pub fn request_judge_change(keys: &EscrowKeys, admin: &Pubkey, new_judge: &Pubkey) -> Instruction {
+    instruction(
+        accounts::RequestJudgeChange {
+            admin: *admin,
+            config: pda::config().0,
+            new_judge: *new_judge,
+            escrow_account: keys.escrow_account,
+        },
+        ix::RequestJudgeChange { order_code: keys.order_code },
+    )
+}
+
+pub fn execute_judge_change(keys: &EscrowKeys, admin: &Pubkey) -> Instruction {
+    instruction(
+        accounts::ExecuteJudgeChange {
+            admin: *admin,
+            config: pda::config().0,
+            escrow_account: keys.escrow_account,
+        },
+        ix::ExecuteJudgeChange { order_code: keys.order_code },
+    )
+}
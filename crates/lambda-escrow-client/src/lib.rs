@@ -0,0 +1,14 @@
+//! Rust client for the `lambda_escrow` program.
+//!
+//! Mirrors what `tests/lambda-escrow.ts` does by hand: instruction builders,
+//! PDA derivation, account deserializers and `ErrorCode` decoding.
+//! The `rpc` feature adds blocking helpers on top of `solana-client`.
+
+pub mod error;
+pub mod instruction;
+pub mod pda;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+pub mod state;
+
+pub use lambda_escrow::ID as PROGRAM_ID;
@@ -0,0 +1,89 @@
+//! PDA derivation, using the same seeds as the program.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::bpf_loader_upgradeable;
+
+/// Token account holding the escrowed funds, `token-seed-{order_code}`.
+pub fn vault_account(order_code: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[format!("{}{}", "token-seed-", order_code).as_bytes()],
+        &lambda_escrow::ID,
+    )
+}
+
+/// Owner of the vault, `escrow-{order_code}`. The program signs transfers out of the vault with it.
+pub fn vault_authority(order_code: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[format!("{}{}", "escrow-", order_code).as_bytes()],
+        &lambda_escrow::ID,
+    )
+}
+
+/// `LineItems` of an itemized escrow, `line-items-{order_code}`.
+pub fn line_items(order_code: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[format!("{}{}", "line-items-", order_code).as_bytes()],
+        &lambda_escrow::ID,
+    )
+}
+
+/// Mint of the 1-of-1 receivable token of a tokenized escrow, `receivable-mint-{order_code}`.
+pub fn receivable_mint(order_code: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[format!("{}{}", "receivable-mint-", order_code).as_bytes()],
+        &lambda_escrow::ID,
+    )
+}
+
+/// Token account the receivable token is minted to, owned by the seller, `receivable-{order_code}`.
+pub fn receivable_token_account(order_code: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[format!("{}{}", "receivable-", order_code).as_bytes()],
+        &lambda_escrow::ID,
+    )
+}
+
+/// `Campaign` of a group buy, `campaign-{campaign_id}`. It owns the campaign vault.
+pub fn campaign(campaign_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[format!("{}{}", "campaign-", campaign_id).as_bytes()],
+        &lambda_escrow::ID,
+    )
+}
+
+/// Token account holding a campaign's deposits, `campaign-vault-{campaign_id}`.
+pub fn campaign_vault(campaign_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[format!("{}{}", "campaign-vault-", campaign_id).as_bytes()],
+        &lambda_escrow::ID,
+    )
+}
+
+/// `CampaignDeposit` of `buyer`, `campaign-deposit-{campaign_id}` and the buyer's key.
+pub fn campaign_deposit(campaign_id: u64, buyer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[format!("{}{}", "campaign-deposit-", campaign_id).as_bytes(), buyer.as_ref()],
+        &lambda_escrow::ID,
+    )
+}
+
+/// Delegate the buyer approves on the deposit token account, `relay_charge_more` moves the
+/// buyer's tokens with it.
+pub fn intent_delegate() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"intent-delegate".as_ref()], &lambda_escrow::ID)
+}
+
+/// Marketplace wide `ProgramConfig`.
+pub fn config() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config".as_ref()], &lambda_escrow::ID)
+}
+
+/// ProgramData of the deployed program, whose upgrade authority can initialize the config.
+pub fn program_data() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[lambda_escrow::ID.as_ref()], &bpf_loader_upgradeable::ID)
+}
+
+/// `SettlementReceipt` written when an escrow is closed, `receipt` and the escrow account key.
+pub fn settlement_receipt(escrow_account: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"receipt".as_ref(), escrow_account.as_ref()], &lambda_escrow::ID)
+}
@@ -0,0 +1,190 @@
+//! Blocking RPC helpers, enabled with the `rpc` feature.
+
+use anchor_lang::Discriminator;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType};
+use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use solana_sdk::account::from_account;
+use solana_sdk::clock::Clock;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::sysvar;
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+use crate::instruction::ESCROW_ACCOUNT_SPACE;
+use crate::pda;
+use crate::state::{
+    self, Campaign, CampaignDeposit, EscrowAccount, EscrowStatus, BUYER_KEY_OFFSET, CART_ID_OFFSET,
+    DEPOSIT_CAMPAIGN_KEY_OFFSET, JUDGE_KEY_OFFSET, ORDER_CODE_OFFSET, SELLER_KEY_OFFSET,
+};
+
+/// Narrows `find_escrows`, every field set must match.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EscrowFilter {
+    pub buyer: Option<Pubkey>,
+    pub seller: Option<Pubkey>,
+    pub judge: Option<Pubkey>,
+    pub order_code: Option<u64>,
+    pub cart_id: Option<u64>,
+}
+
+fn memcmp(offset: usize, bytes: &[u8]) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp {
+        offset,
+        bytes: MemcmpEncodedBytes::Base58(bs58::encode(bytes).into_string()),
+        encoding: None,
+    })
+}
+
+fn decode_error(error: impl ToString) -> ClientError {
+    ClientErrorKind::Custom(error.to_string()).into()
+}
+
+pub fn get_escrow(rpc: &RpcClient, address: &Pubkey) -> ClientResult<EscrowAccount> {
+    let account = rpc.get_account(address)?;
+    state::escrow_account(&account.data).map_err(decode_error)
+}
+
+pub fn find_escrows(rpc: &RpcClient, filter: &EscrowFilter) -> ClientResult<Vec<(Pubkey, EscrowAccount)>> {
+    let mut filters = vec![
+        RpcFilterType::DataSize(ESCROW_ACCOUNT_SPACE as u64),
+        memcmp(0, &EscrowAccount::discriminator()),
+    ];
+    if let Some(buyer) = filter.buyer {
+        filters.push(memcmp(BUYER_KEY_OFFSET, buyer.as_ref()));
+    }
+    if let Some(seller) = filter.seller {
+        filters.push(memcmp(SELLER_KEY_OFFSET, seller.as_ref()));
+    }
+    if let Some(judge) = filter.judge {
+        filters.push(memcmp(JUDGE_KEY_OFFSET, judge.as_ref()));
+    }
+    if let Some(order_code) = filter.order_code {
+        filters.push(memcmp(ORDER_CODE_OFFSET, &order_code.to_le_bytes()));
+    }
+    if let Some(cart_id) = filter.cart_id {
+        filters.push(memcmp(CART_ID_OFFSET, &cart_id.to_le_bytes()));
+    }
+
+    let accounts = rpc.get_program_accounts_with_config(
+        &lambda_escrow::ID,
+        RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            with_context: None,
+        },
+    )?;
+    accounts
+        .into_iter()
+        .map(|(address, account)| {
+            let escrow = state::escrow_account(&account.data).map_err(decode_error)?;
+            Ok((address, escrow))
+        })
+        .collect()
+}
+
+pub fn get_campaign(rpc: &RpcClient, campaign_id: u64) -> ClientResult<Campaign> {
+    let account = rpc.get_account(&pda::campaign(campaign_id).0)?;
+    state::campaign(&account.data).map_err(decode_error)
+}
+
+/// Deposits of a campaign neither converted nor refunded yet.
+pub fn find_campaign_deposits(rpc: &RpcClient, campaign_id: u64) -> ClientResult<Vec<(Pubkey, CampaignDeposit)>> {
+    let filters = vec![
+        RpcFilterType::DataSize((8 + CampaignDeposit::LEN) as u64),
+        memcmp(0, &CampaignDeposit::discriminator()),
+        memcmp(DEPOSIT_CAMPAIGN_KEY_OFFSET, pda::campaign(campaign_id).0.as_ref()),
+    ];
+    let accounts = rpc.get_program_accounts_with_config(
+        &lambda_escrow::ID,
+        RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            with_context: None,
+        },
+    )?;
+    accounts
+        .into_iter()
+        .map(|(address, account)| {
+            let deposit = state::campaign_deposit(&account.data).map_err(decode_error)?;
+            Ok((address, deposit))
+        })
+        .collect()
+}
+
+/// Delivered escrows of `seller` whose trial is over at `now`, what `exchange` and `settle_batch`
+/// accept. Frozen ones are left out, both would only fail or skip them.
+pub fn find_matured(rpc: &RpcClient, seller: &Pubkey, now: i64) -> ClientResult<Vec<(Pubkey, EscrowAccount)>> {
+    let filter = EscrowFilter {
+        seller: Some(*seller),
+        ..EscrowFilter::default()
+    };
+    let escrows = find_escrows(rpc, &filter)?;
+    Ok(escrows
+        .into_iter()
+        .filter(|(_, escrow)| {
+            EscrowStatus::from_u8(escrow.status) == Some(EscrowStatus::Delivered)
+                && state::trial_end(escrow) <= now
+                && !escrow.frozen
+                && escrow.dispute_time == 0
+                && escrow.receivable_mint == Pubkey::default()
+                && !escrow.seller_funded
+                && !escrow.double_deposit
+                && !escrow.rental
+                && !escrow.itemized
+        })
+        .collect())
+}
+
+/// Cluster time, which is what the program compares trial days against.
+pub fn cluster_time(rpc: &RpcClient) -> ClientResult<i64> {
+    let account = rpc.get_account(&sysvar::clock::ID)?;
+    let clock: Clock = from_account(&account).ok_or_else(|| decode_error("invalid clock sysvar"))?;
+    Ok(clock.unix_timestamp)
+}
+
+/// Signs with `payer` and `signers`, sends and waits for confirmation.
+pub fn send_instructions(
+    rpc: &RpcClient,
+    instructions: &[Instruction],
+    payer: &dyn Signer,
+    signers: &[&dyn Signer],
+) -> ClientResult<Signature> {
+    let mut all_signers: Vec<&dyn Signer> = vec![payer];
+    for signer in signers {
+        if signer.pubkey() != payer.pubkey() {
+            all_signers.push(*signer);
+        }
+    }
+    let recent_blockhash = rpc.get_latest_blockhash()?;
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &all_signers,
+        recent_blockhash,
+    );
+    rpc.send_and_confirm_transaction(&transaction)
+}
+
+/// The transaction error behind `error`, whether it failed in preflight or on-chain.
+/// `None` means the transaction never ran, e.g. the RPC node was unreachable.
+pub fn transaction_error(error: &ClientError) -> Option<TransactionError> {
+    match error.kind() {
+        ClientErrorKind::TransactionError(error) => Some(error.clone()),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+            ..
+        }) => result.err.clone(),
+        _ => None,
+    }
+}
@@ -0,0 +1,91 @@
+//! Account deserializers.
+
+use anchor_lang::solana_program::program_error::ProgramError;
+use anchor_lang::AccountDeserialize;
+
+pub use lambda_escrow::{Campaign, CampaignDeposit, EscrowAccount, LineItem, LineItems, ProgramConfig, SettlementReceipt};
+pub use lambda_escrow_model::SECONDS_PER_DAY;
+
+/// Offsets of `EscrowAccount` fields in the account data, for `memcmp` filters.
+pub const BUYER_KEY_OFFSET: usize = 8;
+pub const SELLER_KEY_OFFSET: usize = 8 + 32 * 2;
+pub const JUDGE_KEY_OFFSET: usize = 8 + 32 * 4;
+pub const ORDER_CODE_OFFSET: usize = 8 + 32 * 5 + 8;
+pub const CART_ID_OFFSET: usize = 8 + 32 * 5 + 8 + 8 + 1 + 8 + 2 + 32 + 8 + 1;
+
+/// Offset of `CampaignDeposit.campaign_key`.
+pub const DEPOSIT_CAMPAIGN_KEY_OFFSET: usize = 8;
+
+/// `EscrowAccount.status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscrowStatus {
+    New,
+    Shipping,
+    Delivered,
+}
+
+impl EscrowStatus {
+    pub fn from_u8(status: u8) -> Option<Self> {
+        match status {
+            lambda_escrow_model::NEW => Some(EscrowStatus::New),
+            lambda_escrow_model::SHIPPING => Some(EscrowStatus::Shipping),
+            lambda_escrow_model::DELIVERED => Some(EscrowStatus::Delivered),
+            _ => None,
+        }
+    }
+}
+
+pub fn escrow_account(mut data: &[u8]) -> Result<EscrowAccount, ProgramError> {
+    EscrowAccount::try_deserialize(&mut data)
+}
+
+pub fn program_config(mut data: &[u8]) -> Result<ProgramConfig, ProgramError> {
+    ProgramConfig::try_deserialize(&mut data)
+}
+
+/// The fields `lambda_escrow_model` tracks, e.g. to check what an instruction would do before sending it.
+pub fn escrow_model(escrow: &EscrowAccount) -> lambda_escrow_model::Escrow {
+    lambda_escrow_model::Escrow {
+        status: escrow.status,
+        amount: escrow.amount,
+        delivery_time: escrow.delivery_time,
+        trial_day: escrow.trial_day,
+        vesting: escrow.vesting,
+    }
+}
+
+pub fn line_items(mut data: &[u8]) -> Result<LineItems, ProgramError> {
+    LineItems::try_deserialize(&mut data)
+}
+
+pub fn campaign(mut data: &[u8]) -> Result<Campaign, ProgramError> {
+    Campaign::try_deserialize(&mut data)
+}
+
+pub fn campaign_deposit(mut data: &[u8]) -> Result<CampaignDeposit, ProgramError> {
+    CampaignDeposit::try_deserialize(&mut data)
+}
+
+pub fn settlement_receipt(mut data: &[u8]) -> Result<SettlementReceipt, ProgramError> {
+    SettlementReceipt::try_deserialize(&mut data)
+}
+
+/// Time from which `exchange` is allowed, once the escrow is Delivered.
+pub fn trial_end(escrow: &EscrowAccount) -> i64 {
+    lambda_escrow_model::trial_end(escrow.delivery_time, escrow.trial_day)
+}
+
+/// What `withdraw_vested` would pay the seller at `now`, 0 unless the escrow is vesting,
+/// Delivered and not disputed.
+pub fn withdrawable(escrow: &EscrowAccount, now: i64) -> u64 {
+    if !escrow.vesting || escrow.dispute_time > 0 || escrow.status != lambda_escrow_model::DELIVERED {
+        return 0;
+    }
+    lambda_escrow_model::withdrawable(escrow.amount, escrow.withdrawn, escrow.delivery_time, escrow.trial_day, now)
+        .unwrap_or(0)
+}
+
+/// Time from which `settle_items` accepts a Delivered line item, `trial_day` is the escrow's.
+pub fn line_item_trial_end(item: &LineItem, trial_day: u16) -> i64 {
+    lambda_escrow_model::trial_end(item.delivery_time, trial_day)
+}
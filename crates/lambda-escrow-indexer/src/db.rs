@@ -0,0 +1,496 @@
+//! SQLite schema and writes.
+//!
+//! Escrows are keyed by order code, the only identifier events carry. An order
+//! code can be reused once its escrow is closed, so each `EscrowCreated` starts
+//! a new `escrows` row and later events go to the latest row of their order code.
+//! Amounts and order codes are stored as SQLite integers.
+
+use anchor_lang::prelude::Pubkey;
+use rusqlite::{params, Connection, OptionalExtension, Result, Transaction};
+
+use crate::decode::EscrowEvent;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS cursor (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    slot INTEGER NOT NULL,
+    signature TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT PRIMARY KEY,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    instructions TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS escrows (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    order_code INTEGER NOT NULL,
+    buyer_key TEXT NOT NULL,
+    seller_key TEXT NOT NULL,
+    judge_key TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    status INTEGER NOT NULL,
+    trial_day INTEGER NOT NULL,
+    frozen INTEGER NOT NULL DEFAULT 0,
+    created_slot INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    closed_at INTEGER
+);
+CREATE INDEX IF NOT EXISTS escrows_order_code ON escrows (order_code);
+CREATE TABLE IF NOT EXISTS transitions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    escrow_id INTEGER REFERENCES escrows (id),
+    signature TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    order_code INTEGER NOT NULL,
+    old_status INTEGER NOT NULL,
+    new_status INTEGER NOT NULL,
+    amount INTEGER NOT NULL,
+    escrow_amount INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS payouts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    escrow_id INTEGER REFERENCES escrows (id),
+    signature TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    order_code INTEGER NOT NULL,
+    recipient_key TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS disputes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    escrow_id INTEGER REFERENCES escrows (id),
+    signature TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    order_code INTEGER NOT NULL,
+    judge_key TEXT NOT NULL,
+    recipient_key TEXT,
+    amount INTEGER NOT NULL,
+    detail TEXT,
+    timestamp INTEGER NOT NULL
+);
+";
+
+/// Status reported by the closing instructions, the escrow account is gone after it.
+const CLOSED: u8 = 3;
+
+pub fn open(path: &str) -> Result<Connection> {
+    let connection = Connection::open(path)?;
+    connection.execute_batch(SCHEMA)?;
+    Ok(connection)
+}
+
+/// Last indexed transaction, `(slot, signature)`.
+pub fn cursor(connection: &Connection) -> Result<Option<(u64, String)>> {
+    connection
+        .query_row("SELECT slot, signature FROM cursor WHERE id = 0", [], |row| {
+            Ok((row.get::<_, i64>(0)? as u64, row.get(1)?))
+        })
+        .optional()
+}
+
+fn set_cursor(tx: &Transaction, slot: u64, signature: &str) -> Result<()> {
+    tx.execute(
+        "INSERT INTO cursor (id, slot, signature) VALUES (0, ?1, ?2)
+         ON CONFLICT (id) DO UPDATE SET slot = excluded.slot, signature = excluded.signature",
+        params![slot as i64, signature],
+    )?;
+    Ok(())
+}
+
+/// Indexes one transaction and moves the cursor to it, all or nothing.
+/// Returns false if the transaction was already indexed.
+pub fn index_transaction(
+    connection: &mut Connection,
+    signature: &str,
+    slot: u64,
+    block_time: Option<i64>,
+    instructions: &[&str],
+    events: &[EscrowEvent],
+) -> Result<bool> {
+    let tx = connection.transaction()?;
+    let inserted = tx.execute(
+        "INSERT OR IGNORE INTO transactions (signature, slot, block_time, instructions) VALUES (?1, ?2, ?3, ?4)",
+        params![signature, slot as i64, block_time, instructions.join(",")],
+    )?;
+    if inserted > 0 {
+        let writer = Writer { tx: &tx, signature, slot };
+        for event in events {
+            writer.apply(event)?;
+        }
+    }
+    set_cursor(&tx, slot, signature)?;
+    tx.commit()?;
+    Ok(inserted > 0)
+}
+
+/// Fields shared by the events that move an escrow.
+struct Transition {
+    event: &'static str,
+    order_code: u64,
+    old_status: u8,
+    new_status: u8,
+    amount: u64,
+    escrow_amount: u64,
+    timestamp: i64,
+}
+
+struct Writer<'a> {
+    tx: &'a Transaction<'a>,
+    signature: &'a str,
+    slot: u64,
+}
+
+impl Writer<'_> {
+    fn escrow_id(&self, order_code: u64) -> Result<Option<i64>> {
+        self.tx.query_row(
+            "SELECT MAX(id) FROM escrows WHERE order_code = ?1",
+            params![order_code as i64],
+            |row| row.get(0),
+        )
+    }
+
+    /// Records the transition and brings the escrow row to its new status and amount.
+    fn transition(&self, transition: &Transition) -> Result<Option<i64>> {
+        let escrow_id = self.escrow_id(transition.order_code)?;
+        self.tx.execute(
+            "INSERT INTO transitions (escrow_id, signature, slot, event, order_code, old_status, new_status, amount, escrow_amount, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                escrow_id,
+                self.signature,
+                self.slot as i64,
+                transition.event,
+                transition.order_code as i64,
+                transition.old_status,
+                transition.new_status,
+                transition.amount as i64,
+                transition.escrow_amount as i64,
+                transition.timestamp,
+            ],
+        )?;
+        let closed_at = if transition.new_status == CLOSED { Some(transition.timestamp) } else { None };
+        self.tx.execute(
+            "UPDATE escrows SET status = ?2, amount = ?3, updated_at = ?4, closed_at = COALESCE(?5, closed_at) WHERE id = ?1",
+            params![
+                escrow_id,
+                transition.new_status,
+                transition.escrow_amount as i64,
+                transition.timestamp,
+                closed_at,
+            ],
+        )?;
+        Ok(escrow_id)
+    }
+
+    fn payout(&self, escrow_id: Option<i64>, transition: &Transition, recipient_key: &Pubkey) -> Result<()> {
+        self.tx.execute(
+            "INSERT INTO payouts (escrow_id, signature, slot, event, order_code, recipient_key, amount, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                escrow_id,
+                self.signature,
+                self.slot as i64,
+                transition.event,
+                transition.order_code as i64,
+                recipient_key.to_string(),
+                transition.amount as i64,
+                transition.timestamp,
+            ],
+        )?;
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn dispute(
+        &self,
+        event: &str,
+        order_code: u64,
+        judge_key: &Pubkey,
+        recipient_key: Option<&Pubkey>,
+        amount: u64,
+        detail: Option<String>,
+        timestamp: i64,
+    ) -> Result<()> {
+        let escrow_id = self.escrow_id(order_code)?;
+        self.tx.execute(
+            "INSERT INTO disputes (escrow_id, signature, slot, event, order_code, judge_key, recipient_key, amount, detail, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                escrow_id,
+                self.signature,
+                self.slot as i64,
+                event,
+                order_code as i64,
+                judge_key.to_string(),
+                recipient_key.map(|key| key.to_string()),
+                amount as i64,
+                detail,
+                timestamp,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn apply(&self, event: &EscrowEvent) -> Result<()> {
+        // Builds a Transition from any event carrying the shared fields.
+        macro_rules! transition {
+            ($name:literal, $event:expr) => {
+                Transition {
+                    event: $name,
+                    order_code: $event.order_code,
+                    old_status: $event.old_status,
+                    new_status: $event.new_status,
+                    amount: $event.amount,
+                    escrow_amount: $event.escrow_amount,
+                    timestamp: $event.timestamp,
+                }
+            };
+        }
+
+        match event {
+            EscrowEvent::EscrowCreated(event) => {
+                self.tx.execute(
+                    "INSERT INTO escrows (order_code, buyer_key, seller_key, judge_key, amount, status, trial_day, created_slot, created_at, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
+                    params![
+                        event.order_code as i64,
+                        event.buyer_key.to_string(),
+                        event.seller_key.to_string(),
+                        event.judge_key.to_string(),
+                        event.escrow_amount as i64,
+                        event.new_status,
+                        event.trial_day,
+                        self.slot as i64,
+                        event.timestamp,
+                    ],
+                )?;
+                self.transition(&transition!("EscrowCreated", event))?;
+            }
+            EscrowEvent::Charged(event) => {
+                self.transition(&transition!("Charged", event))?;
+            }
+            EscrowEvent::Shipped(event) => {
+                self.transition(&transition!("Shipped", event))?;
+            }
+            EscrowEvent::DeliveredEvent(event) => {
+                self.transition(&transition!("Delivered", event))?;
+            }
+            EscrowEvent::Refunded(event) => {
+                self.transition(&transition!("Refunded", event))?;
+            }
+            EscrowEvent::PartialRefund(event) => {
+                let transition = transition!("PartialRefund", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &transition, &event.buyer_key)?;
+            }
+            EscrowEvent::Cancelled(event) => {
+                let transition = transition!("Cancelled", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &transition, &event.buyer_key)?;
+            }
+            EscrowEvent::Settled(event) => {
+                let transition = transition!("Settled", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &transition, &event.seller_key)?;
+            }
+            EscrowEvent::MarkedPaid(event) => {
+                self.transition(&transition!("MarkedPaid", event))?;
+            }
+            // A seller-funded escrow pays the buyer on release and the seller on reclaim.
+            EscrowEvent::Released(event) => {
+                let transition = transition!("Released", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &transition, &event.buyer_key)?;
+            }
+            EscrowEvent::Reclaimed(event) => {
+                let transition = transition!("Reclaimed", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &transition, &event.seller_key)?;
+            }
+            // A double-deposit escrow pays both parties at once, one payout each.
+            EscrowEvent::MutuallySettled(event) => {
+                let transition = transition!("MutuallySettled", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &Transition { amount: event.buyer_payout, ..transition }, &event.buyer_key)?;
+                self.payout(escrow_id, &Transition { amount: event.seller_payout, ..transition }, &event.seller_key)?;
+            }
+            EscrowEvent::MutuallyCancelled(event) => {
+                let transition = transition!("MutuallyCancelled", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &Transition { amount: event.buyer_payout, ..transition }, &event.buyer_key)?;
+                self.payout(escrow_id, &Transition { amount: event.seller_payout, ..transition }, &event.seller_key)?;
+            }
+            // Burned funds have no recipient, a donation is paid to its token account.
+            EscrowEvent::LongStopExpired(event) => {
+                let transition = transition!("LongStopExpired", event);
+                let escrow_id = self.transition(&transition)?;
+                if event.recipient_key != Pubkey::default() {
+                    self.payout(escrow_id, &transition, &event.recipient_key)?;
+                }
+            }
+            // A claim moves nothing until the buyer accepts it or the judge rules on it.
+            EscrowEvent::DamagesClaimed(event) => {
+                self.transition(&transition!("DamagesClaimed", event))?;
+            }
+            EscrowEvent::DamagesAccepted(event) => {
+                let transition = transition!("DamagesAccepted", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &Transition { amount: event.seller_payout, ..transition }, &event.seller_key)?;
+                if event.buyer_payout > 0 {
+                    self.payout(escrow_id, &Transition { amount: event.buyer_payout, ..transition }, &event.buyer_key)?;
+                }
+            }
+            EscrowEvent::DepositReturned(event) => {
+                let transition = transition!("DepositReturned", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &transition, &event.buyer_key)?;
+            }
+            // A pending surcharge moves nothing, approve_surcharge emits Charged.
+            EscrowEvent::SurchargeRequested(event) => {
+                self.transition(&transition!("SurchargeRequested", event))?;
+            }
+            EscrowEvent::SurchargeRejected(event) => {
+                self.transition(&transition!("SurchargeRejected", event))?;
+            }
+            EscrowEvent::ItemsShipped(event) => {
+                self.transition(&transition!("ItemsShipped", event))?;
+            }
+            EscrowEvent::ItemsDelivered(event) => {
+                self.transition(&transition!("ItemsDelivered", event))?;
+            }
+            EscrowEvent::ItemsSettled(event) => {
+                let transition = transition!("ItemsSettled", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &transition, &event.seller_key)?;
+            }
+            EscrowEvent::ItemsCancelled(event) => {
+                let transition = transition!("ItemsCancelled", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &transition, &event.buyer_key)?;
+            }
+            EscrowEvent::VestedWithdrawn(event) => {
+                let transition = transition!("VestedWithdrawn", event);
+                let escrow_id = self.transition(&transition)?;
+                self.payout(escrow_id, &transition, &event.seller_key)?;
+            }
+            EscrowEvent::Disputed(event) => {
+                let transition = transition!("Disputed", event);
+                let escrow_id = self.transition(&transition)?;
+                // What vested up to the dispute goes to the seller, the judge settles the rest.
+                if event.amount > 0 {
+                    self.payout(escrow_id, &transition, &event.seller_key)?;
+                }
+                self.dispute(
+                    "Disputed",
+                    event.order_code,
+                    &event.judge_key,
+                    None,
+                    event.escrow_amount,
+                    None,
+                    event.timestamp,
+                )?;
+            }
+            EscrowEvent::Adjudged(event) => {
+                let transition = transition!("Adjudged", event);
+                let escrow_id = self.transition(&transition)?;
+                // `adjudge` only sets the status, the other judge instructions pay someone.
+                let recipient_key = if event.amount > 0 { Some(&event.recipient_key) } else { None };
+                if let Some(recipient_key) = recipient_key {
+                    self.payout(escrow_id, &transition, recipient_key)?;
+                }
+                self.dispute(
+                    "Adjudged",
+                    event.order_code,
+                    &event.judge_key,
+                    recipient_key,
+                    event.amount,
+                    Some(format!("status {} -> {}", event.old_status, event.new_status)),
+                    event.timestamp,
+                )?;
+            }
+            EscrowEvent::TrialUpdated(event) => {
+                self.transition(&transition!("TrialUpdated", event))?;
+                self.tx.execute(
+                    "UPDATE escrows SET trial_day = ?2 WHERE id = ?1",
+                    params![self.escrow_id(event.order_code)?, event.new_trial_day],
+                )?;
+                self.dispute(
+                    "TrialUpdated",
+                    event.order_code,
+                    &event.judge_key,
+                    None,
+                    0,
+                    Some(format!("trial_day {} -> {}", event.old_trial_day, event.new_trial_day)),
+                    event.timestamp,
+                )?;
+            }
+            EscrowEvent::VaultSynced(event) => {
+                self.tx.execute(
+                    "UPDATE escrows SET amount = ?2, updated_at = ?3 WHERE id = ?1",
+                    params![self.escrow_id(event.order_code)?, event.new_amount as i64, event.timestamp],
+                )?;
+                self.dispute(
+                    "VaultSynced",
+                    event.order_code,
+                    &event.judge_key,
+                    None,
+                    event.new_amount,
+                    Some(format!("amount {} -> {}", event.old_amount, event.new_amount)),
+                    event.timestamp,
+                )?;
+            }
+            EscrowEvent::FreezeUpdated(event) => {
+                self.tx.execute(
+                    "UPDATE escrows SET frozen = ?2, updated_at = ?3 WHERE id = ?1",
+                    params![self.escrow_id(event.order_code)?, event.frozen, event.timestamp],
+                )?;
+            }
+            EscrowEvent::ReceivableTransferred(event) => {
+                self.tx.execute(
+                    "UPDATE escrows SET seller_key = ?2, updated_at = ?3 WHERE id = ?1",
+                    params![self.escrow_id(event.order_code)?, event.new_seller_key.to_string(), event.timestamp],
+                )?;
+            }
+            EscrowEvent::JudgeChanged(event) => {
+                self.tx.execute(
+                    "UPDATE escrows SET judge_key = ?2, updated_at = ?3 WHERE id = ?1",
+                    params![self.escrow_id(event.order_code)?, event.new_judge_key.to_string(), event.timestamp],
+                )?;
+            }
+            // Nothing stored per escrow until the change is executed, nor for the global pause.
+            // Each escrow of a batch or cart has its own Settled, EscrowCreated or Cancelled event,
+            // and a relayed intent its DeliveredEvent, Cancelled or Charged event.
+            // Line items only matter once they move, see the Items* events, and vesting once the
+            // seller withdraws.
+            // The holder of a receivable token shows up as the payee of Settled or Adjudged.
+            // A hash lock moves the escrow with its Shipped, DeliveredEvent or Cancelled event.
+            // Campaign deposits are not escrows until converted, which emits EscrowCreated.
+            // The collaterals are part of the amount of a double-deposit EscrowCreated.
+            EscrowEvent::LineItemsSet(_)
+            | EscrowEvent::VestingSet(_)
+            | EscrowEvent::JudgeChangeRequested(_)
+            | EscrowEvent::PauseUpdated(_)
+            | EscrowEvent::BatchSettled(_)
+            | EscrowEvent::CartCheckedOut(_)
+            | EscrowEvent::CartCancelled(_)
+            | EscrowEvent::IntentRelayed(_)
+            | EscrowEvent::ReceivableMinted(_)
+            | EscrowEvent::SecretCommitted(_)
+            | EscrowEvent::SecretRevealed(_)
+            | EscrowEvent::CampaignCreated(_)
+            | EscrowEvent::CampaignJoined(_)
+            | EscrowEvent::CampaignDepositConverted(_)
+            | EscrowEvent::CampaignDepositRefunded(_)
+            | EscrowEvent::CampaignClosed(_)
+            | EscrowEvent::CollateralLocked(_) => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+//! Decoding of `lambda_escrow` instructions and events from transactions.
+
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use lambda_escrow::{
+    Adjudged, BatchSettled, CampaignClosed, CampaignCreated, CampaignDepositConverted, CampaignDepositRefunded,
+    CampaignJoined, Cancelled, CartCancelled, CartCheckedOut, Charged, CollateralLocked, DamagesAccepted,
+    DamagesClaimed, DeliveredEvent, DepositReturned, Disputed, EscrowCreated, FreezeUpdated, IntentRelayed,
+    ItemsCancelled, ItemsDelivered, ItemsSettled, ItemsShipped, JudgeChangeRequested, JudgeChanged, LineItemsSet,
+    LongStopExpired, MarkedPaid, MutuallyCancelled, MutuallySettled, PartialRefund, PauseUpdated, ReceivableMinted,
+    ReceivableTransferred, Reclaimed, Refunded, Released, SecretCommitted, SecretRevealed, Settled, Shipped,
+    SurchargeRejected, SurchargeRequested, TrialUpdated, VaultSynced, VestedWithdrawn, VestingSet,
+};
+use solana_sdk::transaction::Transaction;
+
+/// Every instruction of the program, in declaration order.
+const INSTRUCTION_NAMES: &[&str] = &[
+    "initialize",
+    "checkout",
+    "cancel",
+    "cancel_cart",
+    "cancel_partial",
+    "charge_more",
+    "shipping",
+    "delivered",
+    "refund",
+    "refund_partial",
+    "exchange",
+    "settle_batch",
+    "set_line_items",
+    "ship_items",
+    "deliver_items",
+    "settle_items",
+    "cancel_items",
+    "set_vesting",
+    "withdraw_vested",
+    "dispute",
+    "relay_delivered",
+    "relay_cancel",
+    "relay_charge_more",
+    "transfer_receivable",
+    "ship_hash_locked",
+    "reveal_and_claim",
+    "reclaim_unrevealed",
+    "create_campaign",
+    "join_campaign",
+    "convert_campaign_deposit",
+    "refund_campaign_deposit",
+    "close_campaign",
+    "initialize_seller_funded",
+    "mark_paid",
+    "release",
+    "reclaim_unpaid",
+    "initialize_double_deposit",
+    "settle",
+    "mutual_cancel",
+    "expire_long_stop",
+    "initialize_rental",
+    "claim_damages",
+    "accept_damages",
+    "dispute_damages",
+    "settle_damages",
+    "return_deposit",
+    "request_surcharge",
+    "approve_surcharge",
+    "reject_surcharge",
+    "adjudge",
+    "adjudge_partial",
+    "adjudge_for_buyer",
+    "adjudge_for_seller",
+    "update_trial_day",
+    "sync_vault",
+    "initialize_config",
+    "update_config",
+    "set_paused",
+    "set_frozen",
+    "change_judge",
+    "request_judge_change",
+    "execute_judge_change",
+];
+
+/// Anchor prefixes instruction data with the first 8 bytes of `sha256("global:<name>")`.
+fn sighash(name: &str) -> [u8; 8] {
+    let mut sighash = [0u8; 8];
+    sighash.copy_from_slice(&hash(format!("global:{}", name).as_bytes()).to_bytes()[..8]);
+    sighash
+}
+
+pub fn instruction_name(data: &[u8]) -> Option<&'static str> {
+    if data.len() < 8 {
+        return None;
+    }
+    INSTRUCTION_NAMES.iter().copied().find(|name| sighash(name) == data[..8])
+}
+
+/// Names of the top-level `lambda_escrow` instructions of `transaction`, in order.
+pub fn instruction_names(transaction: &Transaction) -> Vec<&'static str> {
+    let message = &transaction.message;
+    message
+        .instructions
+        .iter()
+        .filter(|instruction| message.account_keys.get(usize::from(instruction.program_id_index)) == Some(&lambda_escrow::ID))
+        .filter_map(|instruction| instruction_name(&instruction.data))
+        .collect()
+}
+
+pub enum EscrowEvent {
+    EscrowCreated(EscrowCreated),
+    CartCheckedOut(CartCheckedOut),
+    Charged(Charged),
+    Shipped(Shipped),
+    DeliveredEvent(DeliveredEvent),
+    Refunded(Refunded),
+    PartialRefund(PartialRefund),
+    Cancelled(Cancelled),
+    CartCancelled(CartCancelled),
+    Settled(Settled),
+    BatchSettled(BatchSettled),
+    LineItemsSet(LineItemsSet),
+    ItemsShipped(ItemsShipped),
+    ItemsDelivered(ItemsDelivered),
+    ItemsSettled(ItemsSettled),
+    ItemsCancelled(ItemsCancelled),
+    VestingSet(VestingSet),
+    VestedWithdrawn(VestedWithdrawn),
+    Disputed(Disputed),
+    IntentRelayed(IntentRelayed),
+    ReceivableMinted(ReceivableMinted),
+    ReceivableTransferred(ReceivableTransferred),
+    SecretCommitted(SecretCommitted),
+    SecretRevealed(SecretRevealed),
+    CampaignCreated(CampaignCreated),
+    CampaignJoined(CampaignJoined),
+    CampaignDepositConverted(CampaignDepositConverted),
+    CampaignDepositRefunded(CampaignDepositRefunded),
+    CampaignClosed(CampaignClosed),
+    MarkedPaid(MarkedPaid),
+    Released(Released),
+    Reclaimed(Reclaimed),
+    CollateralLocked(CollateralLocked),
+    MutuallySettled(MutuallySettled),
+    MutuallyCancelled(MutuallyCancelled),
+    LongStopExpired(LongStopExpired),
+    DamagesClaimed(DamagesClaimed),
+    DamagesAccepted(DamagesAccepted),
+    DepositReturned(DepositReturned),
+    SurchargeRequested(SurchargeRequested),
+    SurchargeRejected(SurchargeRejected),
+    Adjudged(Adjudged),
+    TrialUpdated(TrialUpdated),
+    VaultSynced(VaultSynced),
+    PauseUpdated(PauseUpdated),
+    FreezeUpdated(FreezeUpdated),
+    JudgeChangeRequested(JudgeChangeRequested),
+    JudgeChanged(JudgeChanged),
+}
+
+/// An `emit!` payload: event discriminator followed by the borsh encoded event.
+pub fn decode_event(data: &[u8]) -> Option<EscrowEvent> {
+    if data.len() < 8 {
+        return None;
+    }
+    let (discriminator, mut body) = data.split_at(8);
+    macro_rules! decode {
+        ($($event:ident),*) => {
+            $(
+                if discriminator == $event::discriminator() {
+                    return $event::deserialize(&mut body).ok().map(EscrowEvent::$event);
+                }
+            )*
+        };
+    }
+    decode!(
+        EscrowCreated,
+        CartCheckedOut,
+        Charged,
+        Shipped,
+        DeliveredEvent,
+        Refunded,
+        PartialRefund,
+        Cancelled,
+        CartCancelled,
+        Settled,
+        BatchSettled,
+        LineItemsSet,
+        ItemsShipped,
+        ItemsDelivered,
+        ItemsSettled,
+        ItemsCancelled,
+        VestingSet,
+        VestedWithdrawn,
+        Disputed,
+        IntentRelayed,
+        ReceivableMinted,
+        ReceivableTransferred,
+        SecretCommitted,
+        SecretRevealed,
+        CampaignCreated,
+        CampaignJoined,
+        CampaignDepositConverted,
+        CampaignDepositRefunded,
+        CampaignClosed,
+        MarkedPaid,
+        Released,
+        Reclaimed,
+        CollateralLocked,
+        MutuallySettled,
+        MutuallyCancelled,
+        LongStopExpired,
+        DamagesClaimed,
+        DamagesAccepted,
+        DepositReturned,
+        SurchargeRequested,
+        SurchargeRejected,
+        Adjudged,
+        TrialUpdated,
+        VaultSynced,
+        PauseUpdated,
+        FreezeUpdated,
+        JudgeChangeRequested,
+        JudgeChanged
+    );
+    None
+}
+
+/// Events logged by `lambda_escrow` itself. `Program log:` lines of the programs it calls,
+/// like the token program, are told apart by following the invoke/success lines.
+pub fn decode_events(logs: &[String]) -> Vec<EscrowEvent> {
+    let program_id = lambda_escrow::ID.to_string();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+    for log in logs {
+        if let Some(data) = log.strip_prefix("Program log: ") {
+            if stack.last() == Some(&program_id.as_str()) {
+                if let Some(event) = base64::decode(data).ok().and_then(|data| decode_event(&data)) {
+                    events.push(event);
+                }
+            }
+        } else if let Some(rest) = log.strip_prefix("Program ") {
+            let mut words = rest.split(' ');
+            let (program, action) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+            match action {
+                "invoke" => stack.push(program),
+                "success" | "failed:" => {
+                    stack.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+    events
+}
@@ -0,0 +1,137 @@
+//! Indexer writing `lambda_escrow` history into SQLite.
+//!
+//! Walks the program's transactions oldest first, decodes its instructions and
+//! the events in the logs, and keeps escrows, transitions, payouts and disputes
+//! queryable after the accounts are closed. The last indexed slot and signature
+//! are stored with the data, a restart resumes from there.
+
+mod db;
+mod decode;
+
+use std::error::Error;
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::Duration;
+
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(name = "lambda-escrow-indexer", about = "Index lambda-escrow history into SQLite")]
+struct Opt {
+    /// RPC endpoint.
+    #[structopt(long, short = "u", default_value = "http://127.0.0.1:8899")]
+    url: String,
+    /// SQLite database, created if missing.
+    #[structopt(long, default_value = "lambda-escrow.sqlite")]
+    db: String,
+    /// Index from this slot instead of the stored cursor. Transactions already indexed are skipped.
+    #[structopt(long)]
+    from_slot: Option<u64>,
+    /// Signatures fetched per RPC call.
+    #[structopt(long, default_value = "1000")]
+    page_size: usize,
+    /// Seconds between polls.
+    #[structopt(long, default_value = "10")]
+    interval: u64,
+    /// Index what is there and exit.
+    #[structopt(long)]
+    once: bool,
+}
+
+/// Signatures of the program newer than `until` and at or after `from_slot`, oldest first.
+fn new_signatures(
+    rpc: &RpcClient,
+    until: Option<Signature>,
+    from_slot: u64,
+    page_size: usize,
+) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, Box<dyn Error>> {
+    let mut signatures = Vec::new();
+    let mut before = None;
+    loop {
+        // Pages come newest first.
+        let page = rpc.get_signatures_for_address_with_config(
+            &lambda_escrow::ID,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until,
+                limit: Some(page_size),
+                commitment: Some(CommitmentConfig::confirmed()),
+            },
+        )?;
+        let done = page.len() < page_size || page.last().map_or(true, |status| status.slot < from_slot);
+        before = match page.last() {
+            Some(status) => Some(Signature::from_str(&status.signature)?),
+            None => None,
+        };
+        signatures.extend(page.into_iter().filter(|status| status.slot >= from_slot));
+        if done || before.is_none() {
+            break;
+        }
+    }
+    signatures.reverse();
+    Ok(signatures)
+}
+
+fn index(rpc: &RpcClient, connection: &mut rusqlite::Connection, opt: &Opt) -> Result<usize, Box<dyn Error>> {
+    let (until, from_slot) = match (opt.from_slot, db::cursor(connection)?) {
+        (Some(from_slot), _) => (None, from_slot),
+        (None, Some((_, signature))) => (Some(Signature::from_str(&signature)?), 0),
+        (None, None) => (None, 0),
+    };
+
+    let mut indexed = 0;
+    for status in new_signatures(rpc, until, from_slot, opt.page_size)? {
+        let signature = Signature::from_str(&status.signature)?;
+        // Failed transactions changed nothing, only the cursor moves past them.
+        let (instructions, events) = if status.err.is_some() {
+            (Vec::new(), Vec::new())
+        } else {
+            let confirmed = rpc.get_transaction(&signature, UiTransactionEncoding::Base64)?;
+            let instructions = confirmed
+                .transaction
+                .transaction
+                .decode()
+                .map(|transaction| decode::instruction_names(&transaction))
+                .unwrap_or_default();
+            let logs = confirmed
+                .transaction
+                .meta
+                .and_then(|meta| meta.log_messages)
+                .unwrap_or_default();
+            (instructions, decode::decode_events(&logs))
+        };
+        if db::index_transaction(connection, &status.signature, status.slot, status.block_time, &instructions, &events)? {
+            indexed += 1;
+        }
+    }
+    Ok(indexed)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut opt = Opt::from_args();
+    let rpc = RpcClient::new_with_commitment(opt.url.clone(), CommitmentConfig::confirmed());
+    let mut connection = db::open(&opt.db)?;
+
+    loop {
+        match index(&rpc, &mut connection, &opt) {
+            Ok(indexed) => {
+                if indexed > 0 {
+                    println!("Indexed {} transaction(s)", indexed);
+                }
+                // --from-slot is where to start, later polls go on from the cursor.
+                opt.from_slot = None;
+            }
+            Err(err) if opt.once => return Err(err),
+            Err(err) => eprintln!("Indexing failed: {}", err),
+        }
+        if opt.once {
+            return Ok(());
+        }
+        sleep(Duration::from_secs(opt.interval));
+    }
+}
@@ -0,0 +1,197 @@
+//! Keeper daemon settling escrows once their trial is over.
+//!
+//! `exchange` has to be signed by the seller, so the keeper runs with the
+//! seller's keypair and settles that seller's Delivered escrows whose
+//! `delivery_time + trial_day * 86400` has passed. The program has no
+//! auto-confirm window for Shipping escrows, those are left to the parties.
+//! Settled and failing escrows are tracked in a JSON state file.
+
+mod settle;
+mod state;
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::Duration;
+
+use lambda_escrow_client::rpc;
+use lambda_escrow_client::{pda, state as escrow_state};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use structopt::StructOpt;
+
+use settle::{Matured, Retry};
+use state::KeeperState;
+
+#[derive(StructOpt)]
+#[structopt(name = "lambda-escrow-keeper", about = "Settle lambda-escrow escrows once their trial is over")]
+struct Opt {
+    /// RPC endpoint.
+    #[structopt(long, short = "u", default_value = "http://127.0.0.1:8899")]
+    url: String,
+    /// Seller keypair, pays the fees and signs `exchange`.
+    #[structopt(long, short = "k", default_value = "~/.config/solana/id.json")]
+    keypair: String,
+    /// JSON file keeping settled and failing escrows across restarts.
+    #[structopt(long, default_value = "keeper-state.json")]
+    state_file: PathBuf,
+    /// Seconds between passes.
+    #[structopt(long, default_value = "60")]
+    interval: u64,
+    /// Escrows settled per transaction, more do not fit in the transaction size limit.
+    #[structopt(long, default_value = "3")]
+    batch_size: usize,
+    /// Retries of a transaction whose send failed before it ran.
+    #[structopt(long, default_value = "5")]
+    max_retries: u32,
+    /// First retry delay in milliseconds, doubled after every retry.
+    #[structopt(long, default_value = "500")]
+    retry_backoff_ms: u64,
+    /// Seconds before an escrow that the program rejected is tried again, doubled after every failure.
+    #[structopt(long, default_value = "300")]
+    failure_backoff: i64,
+    /// Upper bound of the failure backoff in seconds.
+    #[structopt(long, default_value = "86400")]
+    max_failure_backoff: i64,
+    /// Also write a settlement receipt for each escrow, paid by --keypair.
+    #[structopt(long)]
+    receipt: bool,
+    /// Only print what would be settled, sends nothing and leaves the state file untouched.
+    #[structopt(long)]
+    dry_run: bool,
+    /// Run a single pass and exit.
+    #[structopt(long)]
+    once: bool,
+}
+
+fn read_keypair(path: &str) -> Result<Keypair, Box<dyn Error>> {
+    let path = match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home, rest),
+        _ => path.to_string(),
+    };
+    read_keypair_file(&path).map_err(|err| format!("failed to read keypair {}: {}", path, err).into())
+}
+
+struct Keeper {
+    opt: Opt,
+    rpc: RpcClient,
+    payer: Keypair,
+    state: KeeperState,
+}
+
+impl Keeper {
+    fn retry(&self) -> Retry {
+        Retry {
+            max_retries: self.opt.max_retries,
+            backoff: Duration::from_millis(self.opt.retry_backoff_ms),
+        }
+    }
+
+    fn instructions(&self, batch: &[Matured]) -> Vec<Instruction> {
+        let receipt_payer = self.payer.pubkey();
+        let receipt_payer = if self.opt.receipt { Some(&receipt_payer) } else { None };
+        batch.iter().map(|matured| settle::exchange_instruction(matured, receipt_payer)).collect()
+    }
+
+    fn pass(&mut self) -> Result<(), Box<dyn Error>> {
+        let now = rpc::cluster_time(&self.rpc)?;
+        let config = escrow_state::program_config(&self.rpc.get_account(&pda::config().0)?.data)?;
+        if config.paused {
+            println!("Program is paused, nothing to do");
+            return Ok(());
+        }
+
+        let state = &self.state;
+        let matured: Vec<Matured> = settle::find_matured(&self.rpc, &self.payer.pubkey(), now)?
+            .into_iter()
+            .filter(|matured| !state.is_backing_off(&matured.address.to_string(), now))
+            .collect();
+        println!("{} escrow(s) ready to settle", matured.len());
+
+        for batch in matured.chunks(self.opt.batch_size.max(1)) {
+            if self.opt.dry_run {
+                for matured in batch {
+                    println!(
+                        "Would settle {} (order {}, {} to the seller)",
+                        matured.address, matured.escrow.order_code, matured.escrow.amount
+                    );
+                }
+                continue;
+            }
+
+            let instructions = self.instructions(batch);
+            match settle::send_with_retry(&self.rpc, &instructions, &self.payer, &self.retry()) {
+                Ok(signature) => {
+                    for matured in batch {
+                        self.settled(matured, signature.to_string(), now);
+                    }
+                }
+                // One bad escrow fails the whole batch, settle the others one by one.
+                Err(_) if batch.len() > 1 => {
+                    for (matured, instruction) in batch.iter().zip(instructions) {
+                        match settle::send_with_retry(&self.rpc, &[instruction], &self.payer, &self.retry()) {
+                            Ok(signature) => self.settled(matured, signature.to_string(), now),
+                            Err(err) => self.failed(matured, settle::describe(&err), now),
+                        }
+                    }
+                }
+                Err(err) => self.failed(&batch[0], settle::describe(&err), now),
+            }
+        }
+
+        self.state.last_pass = now;
+        Ok(())
+    }
+
+    fn settled(&mut self, matured: &Matured, signature: String, now: i64) {
+        println!("Settled {} (order {}): {}", matured.address, matured.escrow.order_code, signature);
+        self.state
+            .record_settled(matured.address.to_string(), matured.escrow.order_code, signature, now);
+    }
+
+    fn failed(&mut self, matured: &Matured, error: String, now: i64) {
+        eprintln!("Failed to settle {} (order {}): {}", matured.address, matured.escrow.order_code, error);
+        self.state.record_failure(
+            matured.address.to_string(),
+            matured.escrow.order_code,
+            error,
+            now,
+            self.opt.failure_backoff,
+            self.opt.max_failure_backoff,
+        );
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if self.opt.dry_run {
+            return Ok(());
+        }
+        self.state.save(path)
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let opt = Opt::from_args();
+    let rpc = RpcClient::new_with_commitment(opt.url.clone(), CommitmentConfig::confirmed());
+    let payer = read_keypair(&opt.keypair)?;
+    let state = KeeperState::load(&opt.state_file)?;
+    let state_file = opt.state_file.clone();
+    let interval = Duration::from_secs(opt.interval);
+    let mut keeper = Keeper { opt, rpc, payer, state };
+
+    loop {
+        let result = keeper.pass();
+        // Keep what was settled even if the pass stopped half way.
+        keeper.save(&state_file)?;
+        match result {
+            Err(err) if keeper.opt.once => return Err(err),
+            Err(err) => eprintln!("Pass failed: {}", err),
+            Ok(()) => {}
+        }
+        if keeper.opt.once {
+            return Ok(());
+        }
+        sleep(interval);
+    }
+}
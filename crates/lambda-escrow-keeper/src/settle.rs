@@ -0,0 +1,81 @@
+//! Finding matured escrows and sending `exchange` for them.
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use lambda_escrow_client::error;
+use lambda_escrow_client::instruction::{self, EscrowKeys};
+use lambda_escrow_client::rpc;
+use lambda_escrow_client::state::EscrowAccount;
+use solana_client::client_error::{ClientError, Result as ClientResult};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::transaction::TransactionError;
+
+pub struct Matured {
+    pub address: Pubkey,
+    pub escrow: EscrowAccount,
+}
+
+/// Matured escrows of `seller` at `now`, see `rpc::find_matured`.
+pub fn find_matured(rpc: &RpcClient, seller: &Pubkey, now: i64) -> ClientResult<Vec<Matured>> {
+    let escrows = rpc::find_matured(rpc, seller, now)?;
+    Ok(escrows
+        .into_iter()
+        .map(|(address, escrow)| Matured { address, escrow })
+        .collect())
+}
+
+pub fn exchange_instruction(matured: &Matured, receipt_payer: Option<&Pubkey>) -> Instruction {
+    let keys = EscrowKeys::from_escrow(matured.address, &matured.escrow);
+    let exchange = instruction::exchange(&keys);
+    match receipt_payer {
+        Some(payer) => instruction::with_settlement_receipt(exchange, &keys.escrow_account, payer),
+        None => exchange,
+    }
+}
+
+pub struct Retry {
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+/// Errors where the transaction never ran, or ran against an expired blockhash.
+/// Program errors are final, sending again would fail the same way.
+fn is_transient(error: &ClientError) -> bool {
+    matches!(rpc::transaction_error(error), None | Some(TransactionError::BlockhashNotFound))
+}
+
+/// Sends `instructions` in one transaction, retrying transient errors with exponential backoff.
+pub fn send_with_retry(
+    rpc: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    retry: &Retry,
+) -> ClientResult<Signature> {
+    let mut attempt = 0;
+    loop {
+        match rpc::send_instructions(rpc, instructions, payer, &[]) {
+            Ok(signature) => return Ok(signature),
+            Err(err) if attempt < retry.max_retries && is_transient(&err) => {
+                let backoff = retry.backoff * 2u32.pow(attempt);
+                eprintln!("Retrying in {:?}: {}", backoff, err);
+                sleep(backoff);
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// `ErrorCode` name when the program rejected the transaction, the raw error otherwise.
+pub fn describe(err: &ClientError) -> String {
+    if let Some(TransactionError::InstructionError(_, instruction_error)) = rpc::transaction_error(err) {
+        if let Some(code) = error::decode_instruction_error(&instruction_error) {
+            return format!("{:?}: {}", code, code);
+        }
+    }
+    err.to_string()
+}
@@ -0,0 +1,84 @@
+//! Local JSON state, so a restarted keeper skips what it settled and keeps backing off failures.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct KeeperState {
+    /// Cluster time of the last completed pass.
+    pub last_pass: i64,
+    /// Settled escrows, by escrow address.
+    pub settled: BTreeMap<String, Settled>,
+    /// Escrows whose settlement failed, by escrow address.
+    pub failures: BTreeMap<String, Failure>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Settled {
+    pub order_code: u64,
+    pub signature: String,
+    pub time: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Failure {
+    pub order_code: u64,
+    pub attempts: u32,
+    pub last_error: String,
+    /// Cluster time before which the escrow is not tried again.
+    pub retry_after: i64,
+}
+
+impl KeeperState {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    /// Writes a temporary file and renames it, a crash never leaves half a state file.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn is_backing_off(&self, escrow: &str, now: i64) -> bool {
+        self.failures.get(escrow).map_or(false, |failure| failure.retry_after > now)
+    }
+
+    pub fn record_settled(&mut self, escrow: String, order_code: u64, signature: String, now: i64) {
+        self.failures.remove(&escrow);
+        self.settled.insert(escrow, Settled { order_code, signature, time: now });
+    }
+
+    /// Doubles the wait after every failed attempt, from `base_backoff` up to `max_backoff` seconds.
+    pub fn record_failure(
+        &mut self,
+        escrow: String,
+        order_code: u64,
+        error: String,
+        now: i64,
+        base_backoff: i64,
+        max_backoff: i64,
+    ) {
+        let attempts = self.failures.get(&escrow).map_or(0, |failure| failure.attempts) + 1;
+        let backoff = base_backoff.saturating_mul(1 << (attempts - 1).min(16)).min(max_backoff);
+        self.failures.insert(
+            escrow,
+            Failure {
+                order_code,
+                attempts,
+                last_error: error,
+                retry_after: now + backoff,
+            },
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Off-chain model of `EscrowAccount` transitions.
+//!
+//! Plain Rust, no Solana dependencies, so the program, the client and the
+//! test harnesses agree on what each instruction is allowed to do with
+//! `status`, `amount`, `delivery_time`, `trial_day` and `vesting`. Signers, token
+//! accounts, pause and freeze are left to the program.
+
+/// Seconds in a trial day.
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// `EscrowAccount.status` values.
+pub const NEW: u8 = 0;
+pub const SHIPPING: u8 = 1;
+pub const DELIVERED: u8 = 2;
+/// Never stored, the account is closed. Reported as `new_status` in events.
+pub const CLOSED: u8 = 3;
+
+/// Why the program would reject a transition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejected {
+    /// The escrow is not in a status this instruction accepts.
+    InvalidStatus,
+    /// Zero, or a partial amount not strictly below the escrow amount.
+    InvalidAmount,
+    /// `exchange` before `trial_end`.
+    InTrialDay,
+    Overflow,
+    /// `charge_more` on a vesting escrow once its trial started.
+    VestingStarted,
+    /// The escrow was already closed.
+    Closed,
+}
+
+/// Time from which `exchange` is allowed, once the escrow is Delivered.
+pub fn trial_end(delivery_time: i64, trial_day: u16) -> i64 {
+    delivery_time + i64::from(trial_day) * SECONDS_PER_DAY
+}
+
+pub fn in_trial_day(delivery_time: i64, trial_day: u16, now: i64) -> bool {
+    trial_end(delivery_time, trial_day) > now
+}
+
+/// Part of `total` unlocked at `now`, linearly from `start` to `end`.
+pub fn vested(total: u64, start: i64, end: i64, now: i64) -> u64 {
+    if now >= end {
+        return total;
+    }
+    if now <= start {
+        return 0;
+    }
+    // Below `total` since `now < end`, the cast back can not truncate.
+    (u128::from(total) * (now - start) as u128 / (end - start) as u128) as u64
+}
+
+/// Vesting runs over what the escrow holds from delivery on, so a top-up during the trial
+/// would count as vested for the time already elapsed.
+pub fn vesting_started(status: u8, vesting: bool) -> bool {
+    vesting && status == DELIVERED
+}
+
+/// What the seller of a vesting escrow can still withdraw at `now`. `amount` is what is left
+/// in the escrow and `withdrawn` what the seller already took, vesting runs over both.
+pub fn withdrawable(amount: u64, withdrawn: u64, delivery_time: i64, trial_day: u16, now: i64) -> Option<u64> {
+    let total = amount.checked_add(withdrawn)?;
+    let vested = vested(total, delivery_time, trial_end(delivery_time, trial_day), now);
+    Some(vested.saturating_sub(withdrawn).min(amount))
+}
+
+/// The part of `EscrowAccount` the instructions move between states.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Escrow {
+    pub status: u8,
+    pub amount: u64,
+    pub delivery_time: i64,
+    pub trial_day: u16,
+    pub vesting: bool,
+}
+
+impl Escrow {
+    pub fn initialize(amount: u64, trial_day: u16, now: i64) -> Result<Escrow, Rejected> {
+        if amount == 0 {
+            return Err(Rejected::InvalidAmount);
+        }
+        Ok(Escrow {
+            status: NEW,
+            amount,
+            delivery_time: now,
+            trial_day,
+            vesting: false,
+        })
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.status == CLOSED
+    }
+
+    pub fn trial_end(&self) -> i64 {
+        trial_end(self.delivery_time, self.trial_day)
+    }
+
+    fn open(&self) -> Result<(), Rejected> {
+        if self.is_closed() {
+            return Err(Rejected::Closed);
+        }
+        Ok(())
+    }
+
+    fn expect_status(&self, allowed: impl Fn(u8) -> bool) -> Result<(), Rejected> {
+        self.open()?;
+        if !allowed(self.status) {
+            return Err(Rejected::InvalidStatus);
+        }
+        Ok(())
+    }
+
+    /// Partial payouts have to leave something behind, the full amount goes through a closing instruction.
+    fn take_partial(&mut self, amount: u64) -> Result<(), Rejected> {
+        if amount == 0 || amount >= self.amount {
+            return Err(Rejected::InvalidAmount);
+        }
+        self.amount -= amount;
+        Ok(())
+    }
+
+    /// Marks the escrow closed and returns what was left in it.
+    fn close(&mut self) -> u64 {
+        let amount = self.amount;
+        self.status = CLOSED;
+        self.amount = 0;
+        amount
+    }
+
+    /// Buyer adds to the escrow, in any status but the trial of a vesting escrow.
+    pub fn charge_more(&mut self, amount: u64) -> Result<(), Rejected> {
+        self.open()?;
+        if vesting_started(self.status, self.vesting) {
+            return Err(Rejected::VestingStarted);
+        }
+        if amount == 0 {
+            return Err(Rejected::InvalidAmount);
+        }
+        self.amount = self.amount.checked_add(amount).ok_or(Rejected::Overflow)?;
+        Ok(())
+    }
+
+    /// Buyer takes part of the escrow back before shipping.
+    pub fn cancel_partial(&mut self, amount: u64) -> Result<(), Rejected> {
+        self.expect_status(|status| status == NEW)?;
+        self.take_partial(amount)
+    }
+
+    /// Seller gives part of the escrow back once shipped.
+    pub fn refund_partial(&mut self, amount: u64) -> Result<(), Rejected> {
+        self.expect_status(|status| status != NEW)?;
+        self.take_partial(amount)
+    }
+
+    /// Judge gives part of the escrow back, in any status.
+    pub fn adjudge_partial(&mut self, amount: u64) -> Result<(), Rejected> {
+        self.open()?;
+        self.take_partial(amount)
+    }
+
+    /// Buyer lets the escrow unlock over the trial, before delivery.
+    pub fn set_vesting(&mut self) -> Result<(), Rejected> {
+        self.expect_status(|status| status != DELIVERED)?;
+        self.vesting = true;
+        Ok(())
+    }
+
+    pub fn shipping(&mut self) -> Result<(), Rejected> {
+        self.expect_status(|status| status == NEW)?;
+        self.status = SHIPPING;
+        Ok(())
+    }
+
+    /// Starts the trial.
+    pub fn delivered(&mut self, now: i64) -> Result<(), Rejected> {
+        self.expect_status(|status| status == SHIPPING)?;
+        self.status = DELIVERED;
+        self.delivery_time = now;
+        Ok(())
+    }
+
+    /// Seller moves a Shipping or Delivered escrow back to New.
+    pub fn refund(&mut self) -> Result<(), Rejected> {
+        self.expect_status(|status| status != NEW)?;
+        self.status = NEW;
+        Ok(())
+    }
+
+    /// Judge sets any stored status, other values are ignored like on-chain.
+    pub fn adjudge(&mut self, status: u8, now: i64) -> Result<(), Rejected> {
+        self.open()?;
+        if status <= DELIVERED {
+            self.status = status;
+            if status == DELIVERED {
+                self.delivery_time = now;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn update_trial_day(&mut self, trial_day: u16) -> Result<(), Rejected> {
+        self.open()?;
+        self.trial_day = trial_day;
+        Ok(())
+    }
+
+    /// Closes to the buyer, returns the buyer payout.
+    pub fn cancel(&mut self) -> Result<u64, Rejected> {
+        self.expect_status(|status| status == NEW)?;
+        Ok(self.close())
+    }
+
+    /// Closes to the seller once the trial is over, returns the seller payout.
+    pub fn exchange(&mut self, now: i64) -> Result<u64, Rejected> {
+        self.expect_status(|status| status == DELIVERED)?;
+        if in_trial_day(self.delivery_time, self.trial_day, now) {
+            return Err(Rejected::InTrialDay);
+        }
+        Ok(self.close())
+    }
+
+    /// Closes to the buyer, returns the buyer payout.
+    pub fn adjudge_for_buyer(&mut self) -> Result<u64, Rejected> {
+        self.open()?;
+        Ok(self.close())
+    }
+
+    /// Closes to the seller, returns the seller payout.
+    pub fn adjudge_for_seller(&mut self) -> Result<u64, Rejected> {
+        self.open()?;
+        Ok(self.close())
+    }
+}
@@ -0,0 +1,123 @@
+//! Reference relayer for buyers without SOL.
+//!
+//! Buyers sign a `lambda_escrow::Intent` off-chain and send it here as a
+//! JSON line over TCP. The relayer checks the signature, nonce and expiry
+//! against the escrow, then sends the Ed25519 verification and the
+//! matching `relay_*` instruction with its own keypair as fee payer, and
+//! answers with the transaction signature or the error.
+
+mod request;
+
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+
+use lambda_escrow_client::instruction::EscrowKeys;
+use lambda_escrow_client::rpc;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use structopt::StructOpt;
+
+use request::{IntentRequest, IntentResponse};
+
+#[derive(StructOpt)]
+#[structopt(name = "lambda-escrow-relayer", about = "Relay buyer-signed lambda-escrow intents and pay their fees")]
+struct Opt {
+    /// RPC endpoint.
+    #[structopt(long, short = "u", default_value = "http://127.0.0.1:8899")]
+    url: String,
+    /// Relayer keypair, pays the fees of every relayed intent.
+    #[structopt(long, short = "k", default_value = "~/.config/solana/id.json")]
+    keypair: String,
+    /// Address to accept intents on.
+    #[structopt(long, default_value = "127.0.0.1:9900")]
+    listen: String,
+    /// Largest `charge_more` amount relayed, 0 for no limit.
+    #[structopt(long, default_value = "0")]
+    max_charge: u64,
+}
+
+fn read_keypair(path: &str) -> Result<Keypair, Box<dyn Error>> {
+    let path = match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home, rest),
+        _ => path.to_string(),
+    };
+    read_keypair_file(&path).map_err(|err| format!("failed to read keypair {}: {}", path, err).into())
+}
+
+struct Relayer {
+    opt: Opt,
+    rpc: RpcClient,
+    payer: Keypair,
+}
+
+impl Relayer {
+    fn relay(&self, request: &IntentRequest) -> Result<String, Box<dyn Error>> {
+        let address = request.escrow()?;
+        let intent = request.intent()?;
+        let signature = request.signature()?;
+        let escrow = rpc::get_escrow(&self.rpc, &address)?;
+        let keys = EscrowKeys::from_escrow(address, &escrow);
+
+        // Everything the program would reject is turned down before paying for it.
+        if !request::verify(&keys, &intent, &signature) {
+            return Err("signature does not match the escrow's buyer".into());
+        }
+        if intent.nonce <= escrow.intent_nonce {
+            return Err(format!("nonce must be above {}", escrow.intent_nonce).into());
+        }
+        if intent.expiry <= rpc::cluster_time(&self.rpc)? {
+            return Err("intent expired".into());
+        }
+        if self.opt.max_charge > 0 && intent.amount > self.opt.max_charge {
+            return Err(format!("amount above the relayer limit of {}", self.opt.max_charge).into());
+        }
+
+        let instructions = request::instructions(&self.payer.pubkey(), &keys, intent, &signature);
+        let signature = rpc::send_instructions(&self.rpc, &instructions, &self.payer, &[])?;
+        Ok(signature.to_string())
+    }
+
+    fn handle(&self, stream: TcpStream) -> Result<(), Box<dyn Error>> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let result = serde_json::from_str::<IntentRequest>(&line)
+                .map_err(|err| format!("invalid request: {}", err).into())
+                .and_then(|request| {
+                    println!("Relaying {} for {} (nonce {})", request.action, request.escrow, request.nonce);
+                    self.relay(&request)
+                });
+            let response = match result {
+                Ok(signature) => IntentResponse { signature: Some(signature), ..IntentResponse::default() },
+                Err(err) => {
+                    eprintln!("Rejected: {}", err);
+                    IntentResponse { error: Some(err.to_string()), ..IntentResponse::default() }
+                }
+            };
+            writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let opt = Opt::from_args();
+    let rpc = RpcClient::new_with_commitment(opt.url.clone(), CommitmentConfig::confirmed());
+    let payer = read_keypair(&opt.keypair)?;
+    let listener = TcpListener::bind(&opt.listen)?;
+    println!("Relaying intents on {} with fee payer {}", opt.listen, payer.pubkey());
+    let relayer = Relayer { opt, rpc, payer };
+
+    // One connection at a time, a reference relayer does not need more.
+    for stream in listener.incoming() {
+        if let Err(err) = stream.map_err(Into::into).and_then(|stream| relayer.handle(stream)) {
+            eprintln!("Connection failed: {}", err);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,79 @@
+//! Intents as buyers send them to the relayer, one JSON object per line.
+
+use std::error::Error;
+
+use lambda_escrow_client::instruction::{self, EscrowKeys, Intent};
+use serde::{Deserialize, Serialize};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+
+/// `{"escrow": "...", "action": "delivered", "amount": 0, "nonce": 1, "expiry": 1700000000, "signature": "..."}`,
+/// with the buyer's base58 signature of `Intent::message(escrow)`.
+#[derive(Debug, Deserialize)]
+pub struct IntentRequest {
+    pub escrow: String,
+    /// `delivered`, `cancel` or `charge_more`.
+    pub action: String,
+    #[serde(default)]
+    pub amount: u64,
+    pub nonce: u64,
+    pub expiry: i64,
+    pub signature: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct IntentResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub fn action(name: &str) -> Option<u8> {
+    match name {
+        "delivered" => Some(Intent::DELIVERED),
+        "cancel" => Some(Intent::CANCEL),
+        "charge_more" => Some(Intent::CHARGE_MORE),
+        _ => None,
+    }
+}
+
+impl IntentRequest {
+    pub fn escrow(&self) -> Result<Pubkey, Box<dyn Error>> {
+        self.escrow.parse().map_err(|_| format!("invalid escrow address {}", self.escrow).into())
+    }
+
+    pub fn intent(&self) -> Result<Intent, Box<dyn Error>> {
+        Ok(Intent {
+            action: action(&self.action).ok_or_else(|| format!("unknown action {}", self.action))?,
+            amount: self.amount,
+            nonce: self.nonce,
+            expiry: self.expiry,
+        })
+    }
+
+    pub fn signature(&self) -> Result<[u8; 64], Box<dyn Error>> {
+        let bytes = bs58::decode(&self.signature).into_vec().map_err(|_| "invalid signature encoding")?;
+        if bytes.len() != 64 {
+            return Err("signature must be 64 bytes".into());
+        }
+        let mut signature = [0u8; 64];
+        signature.copy_from_slice(&bytes);
+        Ok(signature)
+    }
+}
+
+/// Checks the buyer's signature off-chain first, the relayer would pay for a rejected transaction.
+pub fn verify(keys: &EscrowKeys, intent: &Intent, signature: &[u8; 64]) -> bool {
+    Signature::new(signature).verify(keys.buyer.as_ref(), &intent.message(&keys.escrow_account))
+}
+
+/// The Ed25519 verification and the `relay_*` instruction, in one transaction.
+pub fn instructions(relayer: &Pubkey, keys: &EscrowKeys, intent: Intent, signature: &[u8; 64]) -> Vec<Instruction> {
+    match intent.action {
+        Intent::DELIVERED => instruction::relay_delivered(relayer, keys, intent, signature),
+        Intent::CANCEL => instruction::relay_cancel(relayer, keys, intent, signature),
+        _ => instruction::relay_charge_more(relayer, keys, intent, signature),
+    }
+}
@@ -1,29 +1,46 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, CloseAccount, Mint, SetAuthority, TokenAccount, Transfer};
+use anchor_lang::AccountsClose;
+use anchor_lang::solana_program::{
+    bpf_loader_upgradeable, ed25519_program, hash::hash, program::invoke_signed, program_pack::Pack,
+    system_instruction, system_program, sysvar,
+};
+use anchor_spl::token::{self, Burn, CloseAccount, InitializeAccount, InitializeMint, Mint, MintTo, SetAuthority, TokenAccount, Transfer};
 use spl_token::instruction::AuthorityType;
 
 
 // version 1.0.0
 declare_id!("CXWCr2nFZ5yXuewf5t2GFYTT337XmaH8UrhUbS2Hy8tL");
 
+// A workspace build turns on the `no-entrypoint` feature the client crates ask for, which drops
+// the generated `entry`. The program-test suite still needs it to run the program natively.
+#[cfg(feature = "no-entrypoint")]
+pub fn entry(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if data.len() < 8 {
+        return Err(anchor_lang::__private::ErrorCode::InstructionMissing.into())
+    }
+    dispatch(program_id, accounts, data)
+}
+
 
 #[program]
 pub mod lambda_escrow {
     use super::*;
 
 
-    pub fn initialize(
-        ctx: Context<Initialize>,
+    pub fn initialize<'info>(
+        ctx: Context<'_, '_, '_, 'info, Initialize<'info>>,
         _vault_account_bump: u8,
         amount: u64,
         order_code: u64,
         trial_day: u16,
     ) -> ProgramResult {
+        assert_not_paused(&ctx.accounts.config)?;
         let clock: Clock = Clock::get().unwrap();
         // Init escrow_account
         ctx.accounts.escrow_account.buyer_key = *ctx.accounts.buyer.key;
         ctx.accounts.escrow_account.buyer_deposit_token_account = *ctx.accounts.buyer_deposit_token_account.to_account_info().key;
         ctx.accounts.escrow_account.seller_key = *ctx.accounts.seller.key;
+        ctx.accounts.escrow_account.fulfiller_key = *ctx.accounts.seller.key;
         ctx.accounts.escrow_account.seller_receive_token_account = *ctx.accounts.seller_receive_token_account.to_account_info().key;
         ctx.accounts.escrow_account.judge_key = *ctx.accounts.judge.key;
         ctx.accounts.escrow_account.amount = amount;
@@ -31,6 +48,8 @@ pub mod lambda_escrow {
         ctx.accounts.escrow_account.status = 0;
         ctx.accounts.escrow_account.delivery_time = clock.unix_timestamp;
         ctx.accounts.escrow_account.trial_day = trial_day;
+        ctx.accounts.escrow_account.frozen = false;
+        ctx.accounts.escrow_account.rent_payer = *ctx.accounts.payer.key;
 
         // Init PDA
         let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
@@ -47,11 +66,81 @@ pub mod lambda_escrow {
             ctx.accounts.into_transfer_to_pda_context(),
             ctx.accounts.escrow_account.amount,
         )?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        // Tokenize the seller's claim when given the receivable accounts.
+        if !ctx.remaining_accounts.is_empty() {
+            let receivable_mint = mint_receivable(ctx.accounts, ctx.program_id, ctx.remaining_accounts, order_code)?;
+            ctx.accounts.escrow_account.receivable_mint = receivable_mint;
+            emit!(ReceivableMinted {
+                order_code,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                receivable_mint,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        emit!(EscrowCreated {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            judge_key: ctx.accounts.escrow_account.judge_key,
+            amount,
+            escrow_amount: amount,
+            trial_day,
+            old_status: 0,
+            new_status: 0,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn checkout<'info>(
+        ctx: Context<'_, '_, '_, 'info, Checkout<'info>>,
+        cart_id: u64,
+        entries: Vec<CartEntry>,
+    ) -> ProgramResult {
+        // One escrow per entry, like initialize, passed as (escrow, vault, seller,
+        // seller_receive_token_account) groups in remaining_accounts. Any failing entry fails the cart.
+        assert_not_paused(&ctx.accounts.config)?;
+        if cart_id == 0 {
+            return Err(ErrorCode::InvalidCartId.into())
+        }
+        if entries.is_empty() || ctx.remaining_accounts.len() != entries.len() * 4 {
+            return Err(ErrorCode::InvalidCartAccounts.into())
+        }
+
+        let clock: Clock = Clock::get().unwrap();
+        let mut escrow_keys = Vec::new();
+        let mut amount: u64 = 0;
+        for (entry, group) in entries.iter().zip(ctx.remaining_accounts.chunks(4)) {
+            checkout_entry(ctx.accounts, ctx.program_id, cart_id, entry, group, clock.unix_timestamp)?;
+            escrow_keys.push(*group[0].key);
+            amount = amount.checked_add(entry.amount).ok_or(ErrorCode::AmountOverflow)?;
+        }
+
+        emit!(CartCheckedOut {
+            cart_id,
+            buyer_key: *ctx.accounts.buyer.key,
+            escrow_keys,
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
 
         Ok(())
     }
 
     pub fn cancel(ctx: Context<Cancel>, order_code: u64,) -> ProgramResult {
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+
         // Make Seed
         let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
         let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
@@ -69,10 +158,76 @@ pub mod lambda_escrow {
             ctx.accounts.into_close_contest().with_signer(&[&authority_seeds[..]]),
         )?;
 
+        let clock: Clock = Clock::get().unwrap();
+        emit!(Cancelled {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout: ctx.accounts.escrow_account.amount,
+                seller_payout: 0,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 0,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn cancel_cart<'info>(ctx: Context<'_, '_, '_, 'info, CancelCart<'info>>, cart_id: u64,) -> ProgramResult {
+        // Cancel for escrows of one cart, passed as (escrow, vault, vault_authority) groups in
+        // remaining_accounts. All or nothing: one escrow already shipping fails the whole cancel.
+        assert_not_paused(&ctx.accounts.config)?;
+        if cart_id == 0 {
+            return Err(ErrorCode::InvalidCartId.into())
+        }
+        if ctx.remaining_accounts.is_empty() || ctx.remaining_accounts.len() % 3 != 0 {
+            return Err(ErrorCode::InvalidCartAccounts.into())
+        }
+
+        let clock: Clock = Clock::get().unwrap();
+        let mut escrow_keys = Vec::new();
+        let mut amount: u64 = 0;
+        for group in ctx.remaining_accounts.chunks(3) {
+            let cancelled = cancel_cart_entry(ctx.accounts, ctx.program_id, cart_id, group, clock.unix_timestamp)?;
+            escrow_keys.push(*group[0].key);
+            amount = amount.checked_add(cancelled).ok_or(ErrorCode::AmountOverflow)?;
+        }
+
+        emit!(CartCancelled {
+            cart_id,
+            buyer_key: *ctx.accounts.buyer.key,
+            escrow_keys,
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
+
         Ok(())
     }
     
     pub fn cancel_partial(ctx: Context<CancelPartial>, order_code: u64, amount: u64,) -> ProgramResult {
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
         // Make Seed
         let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
         let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
@@ -85,12 +240,32 @@ pub mod lambda_escrow {
             amount,
         )?;
         // Update escrow_account
-        ctx.accounts.escrow_account.amount -= amount;
+        ctx.accounts.escrow_account.amount = ctx.accounts.escrow_account.amount.checked_sub(amount)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(PartialRefund {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            initiator_key: *ctx.accounts.buyer.key,
+            amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
 
         Ok(())
     }
 
     pub fn charge_more(ctx: Context<ChargeMore>, order_code: u64, amount: u64,) -> ProgramResult {
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
+        assert_vesting_not_started(&ctx.accounts.escrow_account)?;
         if ctx.accounts.escrow_account.order_code == order_code {
             // Transfer token to PDA
             token::transfer(
@@ -98,27 +273,72 @@ pub mod lambda_escrow {
                 amount,
             )?;
             // Update escrow_account
-            ctx.accounts.escrow_account.amount += amount;
+            ctx.accounts.escrow_account.amount = ctx.accounts.escrow_account.amount.checked_add(amount)
+                .ok_or(ErrorCode::AmountOverflow)?;
+            check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+            let clock: Clock = Clock::get().unwrap();
+            emit!(Charged {
+                order_code,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                amount,
+                escrow_amount: ctx.accounts.escrow_account.amount,
+                old_status: ctx.accounts.escrow_account.status,
+                new_status: ctx.accounts.escrow_account.status,
+                timestamp: clock.unix_timestamp,
+            });
         }
 
         Ok(())
     }
 
     pub fn shipping(ctx: Context<Shipping>, order_code: u64,) -> ProgramResult {
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
         // Update escrow_account
         if ctx.accounts.escrow_account.order_code == order_code {
+            let old_status = ctx.accounts.escrow_account.status;
             ctx.accounts.escrow_account.status = 1;
+            // Not hash locked, even if it was before a refund.
+            ctx.accounts.escrow_account.secret_hash = [0; 32];
+            ctx.accounts.escrow_account.reveal_deadline = 0;
+
+            let clock: Clock = Clock::get().unwrap();
+            emit!(Shipped {
+                order_code,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                amount: 0,
+                escrow_amount: ctx.accounts.escrow_account.amount,
+                old_status,
+                new_status: 1,
+                timestamp: clock.unix_timestamp,
+            });
         }
 
         Ok(())
     }
 
     pub fn delivered(ctx: Context<Delivered>, order_code: u64,) -> ProgramResult {
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
         // Update escrow_account
         if ctx.accounts.escrow_account.order_code == order_code {
+            let old_status = ctx.accounts.escrow_account.status;
             ctx.accounts.escrow_account.status = 2;
             let clock: Clock = Clock::get().unwrap();
             ctx.accounts.escrow_account.delivery_time = clock.unix_timestamp;
+
+            emit!(DeliveredEvent {
+                order_code,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                amount: 0,
+                escrow_amount: ctx.accounts.escrow_account.amount,
+                old_status,
+                new_status: 2,
+                timestamp: clock.unix_timestamp,
+            });
         }
 
         Ok(())
@@ -126,15 +346,35 @@ pub mod lambda_escrow {
 
     pub fn refund(ctx: Context<Refund>, order_code: u64,) -> ProgramResult {
         // If status = Shipping or Delivered, Seller can refund to Buyer.
+        assert_not_tokenized(&ctx.accounts.escrow_account)?;
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
         if ctx.accounts.escrow_account.order_code == order_code && ctx.accounts.escrow_account.status > 0 {
             // Update escrow_account
+            let old_status = ctx.accounts.escrow_account.status;
             ctx.accounts.escrow_account.status = 0;
+
+            let clock: Clock = Clock::get().unwrap();
+            emit!(Refunded {
+                order_code,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                amount: 0,
+                escrow_amount: ctx.accounts.escrow_account.amount,
+                old_status,
+                new_status: 0,
+                timestamp: clock.unix_timestamp,
+            });
         }
 
         Ok(())
     }
 
     pub fn refund_partial(ctx: Context<RefundPartial>, order_code: u64, amount: u64,) -> ProgramResult {
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        assert_not_tokenized(&ctx.accounts.escrow_account)?;
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
         // Make Seed
         let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
         let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
@@ -147,19 +387,54 @@ pub mod lambda_escrow {
             amount,
         )?;
         // Update escrow_account
-        ctx.accounts.escrow_account.amount -= amount;
+        ctx.accounts.escrow_account.amount = ctx.accounts.escrow_account.amount.checked_sub(amount)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(PartialRefund {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            initiator_key: *ctx.accounts.seller.key,
+            amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
 
         Ok(())
     }
 
     pub fn exchange(ctx: Context<Exchange>) -> ProgramResult {
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
+        if ctx.accounts.escrow_account.dispute_time > 0 {
+            return Err(ErrorCode::EscrowDisputed.into())
+        }
+        // The signer has to be whoever is paid, the seller or the holder of the receivable token.
+        let (payee, receipt_accounts) = receivable_payee(
+            &ctx.accounts.escrow_account,
+            &ctx.accounts.seller_receive_token_account,
+            ctx.remaining_accounts,
+        )?;
+        if *ctx.accounts.seller.key != payee {
+            return Err(ErrorCode::ReceivableNotHeld.into())
+        }
         // Verify trial day.
         let clock: Clock = Clock::get().unwrap();
-        // seconds in day: 24 * 60 * 60 = 86400
-        if ctx.accounts.escrow_account.delivery_time + (i64::from(ctx.accounts.escrow_account.trial_day) * 86400) > clock.unix_timestamp {
+        if lambda_escrow_model::in_trial_day(ctx.accounts.escrow_account.delivery_time, ctx.accounts.escrow_account.trial_day, clock.unix_timestamp) {
             return Err(ErrorCode::InTrialDay.into())
         }
 
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+
         // Make Seed
         let escrow_seed: String = format!("{}{}", "escrow-".to_string(), ctx.accounts.escrow_account.order_code.to_string());
         let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
@@ -177,25 +452,225 @@ pub mod lambda_escrow {
             ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
         )?;
 
+        emit!(Settled {
+            order_code: ctx.accounts.escrow_account.order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: payee,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            receipt_accounts,
+            &SettlementReceipt {
+                order_code: ctx.accounts.escrow_account.order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: payee,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout: 0,
+                seller_payout: ctx.accounts.escrow_account.amount,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 1,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
         Ok(())
     }
 
-    pub fn adjudge(ctx: Context<Adjudge>, order_code: u64, status: u8,) -> ProgramResult {
-        // Judge can set status = (New or Shipping or Delivered).
-        if ctx.accounts.escrow_account.order_code == order_code && (status == 0 || status == 1 || status == 2) {
-            // Update escrow_account
-            ctx.accounts.escrow_account.status = status;
-            // If set status = Delivered, update delivery_time
-            if status == 2 {
-                let clock: Clock = Clock::get().unwrap();
-                ctx.accounts.escrow_account.delivery_time = clock.unix_timestamp;
+    pub fn settle_batch<'info>(ctx: Context<'_, '_, '_, 'info, SettleBatch<'info>>) -> ProgramResult {
+        // Exchange for many matured escrows of one seller, passed as
+        // (escrow, vault, vault_authority, rent_payer) groups in remaining_accounts.
+        assert_not_paused(&ctx.accounts.config)?;
+        if ctx.remaining_accounts.is_empty() || ctx.remaining_accounts.len() % 4 != 0 {
+            return Err(ErrorCode::InvalidBatchAccounts.into())
+        }
+
+        let clock: Clock = Clock::get().unwrap();
+        let mut escrow_keys = Vec::new();
+        let mut settled = Vec::new();
+        for group in ctx.remaining_accounts.chunks(4) {
+            escrow_keys.push(*group[0].key);
+            settled.push(settle_batch_group(ctx.accounts, ctx.program_id, group, clock.unix_timestamp)?);
+        }
+
+        emit!(BatchSettled {
+            seller_key: *ctx.accounts.seller.key,
+            escrow_keys,
+            settled,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_line_items(
+        ctx: Context<SetLineItems>,
+        _line_items_bump: u8,
+        order_code: u64,
+        items: Vec<NewLineItem>,
+    ) -> ProgramResult {
+        // Splits a New escrow into line items, together worth exactly the escrow amount.
+        assert_not_tokenized(&ctx.accounts.escrow_account)?;
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        if items.is_empty() || items.len() > LineItems::MAX_ITEMS {
+            return Err(ErrorCode::InvalidLineItems.into())
+        }
+        let mut line_items = Vec::new();
+        let mut amount: u64 = 0;
+        for item in items {
+            if item.quantity == 0 || item.price == 0 {
+                return Err(ErrorCode::InvalidLineItems.into())
             }
+            let line_item = LineItem {
+                sku_hash: item.sku_hash,
+                quantity: item.quantity,
+                price: item.price,
+                status: 0,
+                delivery_time: 0,
+            };
+            amount = amount.checked_add(line_item.total()?).ok_or(ErrorCode::AmountOverflow)?;
+            line_items.push(line_item);
         }
+        if amount != ctx.accounts.escrow_account.amount {
+            return Err(ErrorCode::InvalidLineItems.into())
+        }
+
+        ctx.accounts.line_items.escrow_key = *ctx.accounts.escrow_account.to_account_info().key;
+        ctx.accounts.line_items.items = line_items;
+        ctx.accounts.escrow_account.itemized = true;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(LineItemsSet {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            count: ctx.accounts.line_items.items.len() as u8,
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
 
         Ok(())
     }
 
-    pub fn adjudge_partial(ctx: Context<AdjudgePartial>, order_code: u64, amount: u64,) -> ProgramResult {
+    pub fn ship_items(ctx: Context<ShipItems>, order_code: u64, indexes: Vec<u8>,) -> ProgramResult {
+        // Seller marks New line items Shipping.
+        let clock: Clock = Clock::get().unwrap();
+        update_line_items(&mut ctx.accounts.line_items, &indexes, 1, clock.unix_timestamp, |item| {
+            expect_item_status(item, 0)
+        })?;
+
+        emit!(ItemsShipped {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            indexes,
+            amount: 0,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn deliver_items(ctx: Context<DeliverItems>, order_code: u64, indexes: Vec<u8>,) -> ProgramResult {
+        // Buyer marks Shipping line items Delivered, starting their own trial.
+        let clock: Clock = Clock::get().unwrap();
+        update_line_items(&mut ctx.accounts.line_items, &indexes, 2, clock.unix_timestamp, |item| {
+            expect_item_status(item, 1)
+        })?;
+
+        emit!(ItemsDelivered {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            indexes,
+            amount: 0,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn settle_items(ctx: Context<SettleItems>, order_code: u64, indexes: Vec<u8>,) -> ProgramResult {
+        // Pays the seller for Delivered line items whose trial is over, like exchange does for the order.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        check_line_items(&ctx.accounts.line_items, &ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        let trial_day = ctx.accounts.escrow_account.trial_day;
+        let amount = update_line_items(&mut ctx.accounts.line_items, &indexes, 3, clock.unix_timestamp, |item| {
+            expect_item_status(item, 2)?;
+            if lambda_escrow_model::in_trial_day(item.delivery_time, trial_day, clock.unix_timestamp) {
+                return Err(ErrorCode::InTrialDay.into())
+            }
+            Ok(())
+        })?;
+
+        // Make Seed
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+        // Transfer token to seller.
+        token::transfer(
+            ctx.accounts.into_transfer_to_seller_context().with_signer(&[&authority_seeds[..]]),
+            amount,
+        )?;
+        // Update escrow_account
+        ctx.accounts.escrow_account.amount = ctx.accounts.escrow_account.amount.checked_sub(amount)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        // The last open item closes the empty vault and escrow, rent goes back to whoever paid it.
+        let old_status = ctx.accounts.escrow_account.status;
+        let closed = ctx.accounts.line_items.all_closed();
+        if closed {
+            token::close_account(
+                ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
+            )?;
+            ctx.accounts.line_items.close(ctx.accounts.buyer.clone())?;
+            ctx.accounts.escrow_account.close(ctx.accounts.rent_payer.clone())?;
+        }
+
+        emit!(ItemsSettled {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            indexes,
+            amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status,
+            new_status: if closed { 3 } else { old_status },
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_items(ctx: Context<CancelItems>, order_code: u64, indexes: Vec<u8>,) -> ProgramResult {
+        // Refunds the buyer for line items not shipped yet, like cancel_partial does for an amount.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        check_line_items(&ctx.accounts.line_items, &ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        let amount = update_line_items(&mut ctx.accounts.line_items, &indexes, 3, clock.unix_timestamp, |item| {
+            expect_item_status(item, 0)
+        })?;
+
         // Make Seed
         let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
         let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
@@ -208,13 +683,213 @@ pub mod lambda_escrow {
             amount,
         )?;
         // Update escrow_account
-        ctx.accounts.escrow_account.amount -= amount;
+        ctx.accounts.escrow_account.amount = ctx.accounts.escrow_account.amount.checked_sub(amount)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        let old_status = ctx.accounts.escrow_account.status;
+        let closed = ctx.accounts.line_items.all_closed();
+        if closed {
+            token::close_account(
+                ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
+            )?;
+            ctx.accounts.line_items.close(ctx.accounts.buyer.clone())?;
+            ctx.accounts.escrow_account.close(ctx.accounts.rent_payer.clone())?;
+        }
+
+        emit!(ItemsCancelled {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            indexes,
+            amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status,
+            new_status: if closed { 3 } else { old_status },
+            timestamp: clock.unix_timestamp,
+        });
 
         Ok(())
     }
 
-    pub fn adjudge_for_buyer(ctx: Context<AdjudgeForBuyer>, order_code: u64,) -> ProgramResult {
+    pub fn set_vesting(ctx: Context<SetVesting>, order_code: u64,) -> ProgramResult {
+        // Buyer lets the escrow unlock to the seller over the trial instead of at its end.
+        assert_not_tokenized(&ctx.accounts.escrow_account)?;
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
+        ctx.accounts.escrow_account.vesting = true;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(VestingSet {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_vested(ctx: Context<WithdrawVested>, order_code: u64,) -> ProgramResult {
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        if !ctx.accounts.escrow_account.vesting {
+            return Err(ErrorCode::NotVesting.into())
+        }
+        if ctx.accounts.escrow_account.dispute_time > 0 {
+            return Err(ErrorCode::EscrowDisputed.into())
+        }
+        let clock: Clock = Clock::get().unwrap();
+        let amount = vested_amount(&ctx.accounts.escrow_account, clock.unix_timestamp)?;
+        if amount == 0 {
+            return Err(ErrorCode::NothingVested.into())
+        }
+
+        // Make Seed
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+        // Transfer token to seller.
+        token::transfer(
+            ctx.accounts.into_transfer_to_seller_context().with_signer(&[&authority_seeds[..]]),
+            amount,
+        )?;
+        // Update escrow_account
+        ctx.accounts.escrow_account.amount = ctx.accounts.escrow_account.amount.checked_sub(amount)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        ctx.accounts.escrow_account.withdrawn = ctx.accounts.escrow_account.withdrawn.checked_add(amount)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        emit!(VestedWithdrawn {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            withdrawn: ctx.accounts.escrow_account.withdrawn,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn dispute(ctx: Context<Dispute>, order_code: u64,) -> ProgramResult {
+        // Buyer stops the vesting during the trial. What already vested goes to the seller, the
+        // rest stays in the vault for adjudge_for_buyer or adjudge_for_seller.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        if !ctx.accounts.escrow_account.vesting {
+            return Err(ErrorCode::NotVesting.into())
+        }
+        if ctx.accounts.escrow_account.dispute_time > 0 {
+            return Err(ErrorCode::EscrowDisputed.into())
+        }
+        let clock: Clock = Clock::get().unwrap();
+        if !lambda_escrow_model::in_trial_day(ctx.accounts.escrow_account.delivery_time, ctx.accounts.escrow_account.trial_day, clock.unix_timestamp) {
+            return Err(ErrorCode::TrialEnded.into())
+        }
+        let amount = vested_amount(&ctx.accounts.escrow_account, clock.unix_timestamp)?;
+
+        if amount > 0 {
+            // Make Seed
+            let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+            let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+            let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+            let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+            // Transfer token to seller.
+            token::transfer(
+                ctx.accounts.into_transfer_to_seller_context().with_signer(&[&authority_seeds[..]]),
+                amount,
+            )?;
+            ctx.accounts.escrow_account.amount = ctx.accounts.escrow_account.amount.checked_sub(amount)
+                .ok_or(ErrorCode::AmountOverflow)?;
+            ctx.accounts.escrow_account.withdrawn = ctx.accounts.escrow_account.withdrawn.checked_add(amount)
+                .ok_or(ErrorCode::AmountOverflow)?;
+        }
+        ctx.accounts.escrow_account.dispute_time = clock.unix_timestamp;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        emit!(Disputed {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            judge_key: ctx.accounts.escrow_account.judge_key,
+            amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn relay_delivered(ctx: Context<RelayDelivered>, intent: Intent,) -> ProgramResult {
+        // `delivered` for a buyer without SOL, the relayer pays the fees.
+        let clock: Clock = Clock::get().unwrap();
+        verify_intent(
+            &ctx.accounts.instructions,
+            ctx.accounts.escrow_account.to_account_info().key,
+            &ctx.accounts.escrow_account,
+            &intent,
+            Intent::DELIVERED,
+            clock.unix_timestamp,
+        )?;
+        // Update escrow_account
+        let old_status = ctx.accounts.escrow_account.status;
+        ctx.accounts.escrow_account.intent_nonce = intent.nonce;
+        ctx.accounts.escrow_account.status = 2;
+        ctx.accounts.escrow_account.delivery_time = clock.unix_timestamp;
+
+        emit!(DeliveredEvent {
+            order_code: ctx.accounts.escrow_account.order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount: 0,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status,
+            new_status: 2,
+            timestamp: clock.unix_timestamp,
+        });
+        emit!(IntentRelayed {
+            order_code: ctx.accounts.escrow_account.order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            relayer_key: *ctx.accounts.relayer.key,
+            action: intent.action,
+            nonce: intent.nonce,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn relay_cancel(ctx: Context<RelayCancel>, intent: Intent,) -> ProgramResult {
+        // `cancel` for a buyer without SOL, the rent still goes back to the rent payer.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        verify_intent(
+            &ctx.accounts.instructions,
+            ctx.accounts.escrow_account.to_account_info().key,
+            &ctx.accounts.escrow_account,
+            &intent,
+            Intent::CANCEL,
+            clock.unix_timestamp,
+        )?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+
         // Make Seed
+        let order_code = ctx.accounts.escrow_account.order_code;
         let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
         let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
         let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
@@ -228,22 +903,246 @@ pub mod lambda_escrow {
 
         // Close vault account
         token::close_account(
-            ctx.accounts.into_close_contest().with_signer(&[&authority_seeds[..]]),
+            ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
+        )?;
+
+        emit!(Cancelled {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+        emit!(IntentRelayed {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            relayer_key: *ctx.accounts.relayer.key,
+            action: intent.action,
+            nonce: intent.nonce,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout: ctx.accounts.escrow_account.amount,
+                seller_payout: 0,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 0,
+                timestamp: clock.unix_timestamp,
+            },
         )?;
 
         Ok(())
     }
 
-    pub fn adjudge_for_seller(ctx: Context<AdjudgeForSeller>, order_code: u64,) -> ProgramResult {
+    pub fn relay_charge_more(ctx: Context<RelayChargeMore>, intent: Intent,) -> ProgramResult {
+        // `charge_more` for a buyer without SOL. The buyer can not sign the token transfer, it
+        // goes through the intent delegate the buyer approved on the deposit token account.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
+        assert_vesting_not_started(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        verify_intent(
+            &ctx.accounts.instructions,
+            ctx.accounts.escrow_account.to_account_info().key,
+            &ctx.accounts.escrow_account,
+            &intent,
+            Intent::CHARGE_MORE,
+            clock.unix_timestamp,
+        )?;
+        if intent.amount == 0 {
+            return Err(ErrorCode::InvalidIntent.into())
+        }
+        ctx.accounts.escrow_account.intent_nonce = intent.nonce;
+
+        // Make Seed
+        let vault_seed: String = format!("{}{}", "token-seed-".to_string(), ctx.accounts.escrow_account.order_code.to_string());
+        let (vault_key, _vault_bump) = Pubkey::find_program_address(&[vault_seed.as_bytes()], ctx.program_id);
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), ctx.accounts.escrow_account.order_code.to_string());
+        let (vault_authority, _vault_authority_bump) = Pubkey::find_program_address(&[escrow_seed.as_bytes()], ctx.program_id);
+        if *ctx.accounts.vault_account.to_account_info().key != vault_key
+            || ctx.accounts.vault_account.owner != vault_authority
+        {
+            return Err(ErrorCode::VaultMismatch.into())
+        }
+        let (intent_delegate, intent_delegate_bump) = Pubkey::find_program_address(&[b"intent-delegate"], ctx.program_id);
+        if intent_delegate != *ctx.accounts.intent_delegate.key {
+            return Err(ErrorCode::InvalidIntent.into())
+        }
+        let delegate_seeds = &[&b"intent-delegate"[..], &[intent_delegate_bump]];
+
+        // Transfer token to PDA
+        token::transfer(
+            ctx.accounts.into_transfer_to_pda_context().with_signer(&[&delegate_seeds[..]]),
+            intent.amount,
+        )?;
+        // Update escrow_account
+        ctx.accounts.escrow_account.amount = ctx.accounts.escrow_account.amount.checked_add(intent.amount)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        emit!(Charged {
+            order_code: ctx.accounts.escrow_account.order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount: intent.amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
+        emit!(IntentRelayed {
+            order_code: ctx.accounts.escrow_account.order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            relayer_key: *ctx.accounts.relayer.key,
+            action: intent.action,
+            nonce: intent.nonce,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn transfer_receivable(ctx: Context<TransferReceivable>, order_code: u64,) -> ProgramResult {
+        // The seller sells the payout of the escrow, e.g. to a financier. The new seller is paid
+        // by exchange and the judge like the seller was, the fulfiller still ships.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        // A tokenized escrow changes hands with its token instead.
+        assert_not_tokenized(&ctx.accounts.escrow_account)?;
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        let old_seller_key = ctx.accounts.escrow_account.seller_key;
+        let old_seller_receive_token_account = ctx.accounts.escrow_account.seller_receive_token_account;
+        ctx.accounts.escrow_account.seller_key = *ctx.accounts.new_seller.key;
+        ctx.accounts.escrow_account.seller_receive_token_account =
+            *ctx.accounts.new_seller_receive_token_account.to_account_info().key;
+
+        emit!(ReceivableTransferred {
+            order_code,
+            fulfiller_key: ctx.accounts.escrow_account.fulfiller_key,
+            old_seller_key,
+            new_seller_key: ctx.accounts.escrow_account.seller_key,
+            old_seller_receive_token_account,
+            new_seller_receive_token_account: ctx.accounts.escrow_account.seller_receive_token_account,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn ship_hash_locked(
+        ctx: Context<Shipping>,
+        order_code: u64,
+        secret_hash: [u8; 32],
+        reveal_deadline: i64,
+    ) -> ProgramResult {
+        // Ships a digital good, e.g. a license key, as the sha256 of a secret the seller reveals
+        // later. Unrevealed at `reveal_deadline`, the buyer takes the escrow back.
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        if secret_hash == [0; 32] || reveal_deadline <= clock.unix_timestamp {
+            return Err(ErrorCode::InvalidHashLock.into())
+        }
+        let old_status = ctx.accounts.escrow_account.status;
+        ctx.accounts.escrow_account.status = 1;
+        ctx.accounts.escrow_account.secret_hash = secret_hash;
+        ctx.accounts.escrow_account.reveal_deadline = reveal_deadline;
+
+        emit!(Shipped {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount: 0,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status,
+            new_status: 1,
+            timestamp: clock.unix_timestamp,
+        });
+        emit!(SecretCommitted {
+            order_code,
+            secret_hash,
+            reveal_deadline,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn reveal_and_claim(ctx: Context<RevealAndClaim>, order_code: u64, secret: Vec<u8>,) -> ProgramResult {
+        // The seller reveals the secret in the SecretRevealed event, which delivers the order
+        // and starts the trial. The secret can be encrypted to the buyer, the hash commits to
+        // the bytes revealed.
+        let clock: Clock = Clock::get().unwrap();
+        if clock.unix_timestamp > ctx.accounts.escrow_account.reveal_deadline {
+            return Err(ErrorCode::RevealDeadlinePassed.into())
+        }
+        if hash(&secret).to_bytes() != ctx.accounts.escrow_account.secret_hash {
+            return Err(ErrorCode::SecretMismatch.into())
+        }
+        let old_status = ctx.accounts.escrow_account.status;
+        ctx.accounts.escrow_account.status = 2;
+        ctx.accounts.escrow_account.delivery_time = clock.unix_timestamp;
+
+        emit!(DeliveredEvent {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount: 0,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status,
+            new_status: 2,
+            timestamp: clock.unix_timestamp,
+        });
+        emit!(SecretRevealed {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.fulfiller_key,
+            secret,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn reclaim_unrevealed(ctx: Context<ReclaimUnrevealed>, order_code: u64,) -> ProgramResult {
+        // Anyone, e.g. a crank, can return a hash-locked escrow whose secret was not revealed
+        // in time to the buyer, without the judge.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        if clock.unix_timestamp <= ctx.accounts.escrow_account.reveal_deadline {
+            return Err(ErrorCode::RevealDeadlineNotPassed.into())
+        }
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+
         // Make Seed
         let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
         let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
         let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
         let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
 
-        // Transfer token to seller.
+        // Transfer token to buyer.
         token::transfer(
-            ctx.accounts.into_transfer_to_seller_context().with_signer(&[&authority_seeds[..]]),
+            ctx.accounts.into_transfer_to_buyer_context().with_signer(&[&authority_seeds[..]]),
             ctx.accounts.escrow_account.amount,
         )?;
 
@@ -252,386 +1151,4823 @@ pub mod lambda_escrow {
             ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
         )?;
 
+        emit!(Cancelled {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout: ctx.accounts.escrow_account.amount,
+                seller_payout: 0,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 4,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
         Ok(())
     }
 
-    pub fn update_trial_day(ctx: Context<UpdateTrialDay>, order_code: u64, trial_day: u16,) -> ProgramResult {
-        if ctx.accounts.escrow_account.order_code == order_code {
-            // Update escrow_account trial_day
-            ctx.accounts.escrow_account.trial_day = trial_day;
+    pub fn create_campaign(
+        ctx: Context<CreateCampaign>,
+        _campaign_bump: u8,
+        _campaign_vault_bump: u8,
+        campaign_id: u64,
+        price: u64,
+        threshold: u32,
+        deadline: i64,
+        conversion_deadline: i64,
+        trial_day: u16,
+    ) -> ProgramResult {
+        // Group buy: buyers deposit `price` each until `deadline`, the seller only sells if at
+        // least `threshold` of them joined. Deposits still open at `conversion_deadline` can be
+        // refunded or converted by anyone, so the seller cannot keep them locked.
+        assert_not_paused(&ctx.accounts.config)?;
+        let clock: Clock = Clock::get().unwrap();
+        if price == 0 || threshold == 0 || deadline <= clock.unix_timestamp || conversion_deadline <= deadline {
+            return Err(ErrorCode::InvalidCampaign.into())
         }
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.campaign_id = campaign_id;
+        campaign.seller_key = *ctx.accounts.seller.key;
+        campaign.seller_receive_token_account = *ctx.accounts.seller_receive_token_account.to_account_info().key;
+        campaign.judge_key = *ctx.accounts.judge.key;
+        campaign.mint = *ctx.accounts.mint.to_account_info().key;
+        campaign.price = price;
+        campaign.threshold = threshold;
+        campaign.deadline = deadline;
+        campaign.conversion_deadline = conversion_deadline;
+        campaign.trial_day = trial_day;
+        campaign.participants = 0;
+        campaign.open_deposits = 0;
+
+        // The campaign PDA owns the vault.
+        token::set_authority(
+            ctx.accounts.into_set_authority_context(),
+            AuthorityType::AccountOwner,
+            Some(*ctx.accounts.campaign.to_account_info().key),
+        )?;
+
+        emit!(CampaignCreated {
+            campaign_id,
+            seller_key: *ctx.accounts.seller.key,
+            price,
+            threshold,
+            deadline,
+            conversion_deadline,
+            timestamp: clock.unix_timestamp,
+        });
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
+    pub fn join_campaign(ctx: Context<JoinCampaign>, _deposit_bump: u8, campaign_id: u64,) -> ProgramResult {
+        // One deposit of `price` per buyer, recorded in its own CampaignDeposit.
+        assert_not_paused(&ctx.accounts.config)?;
+        let clock: Clock = Clock::get().unwrap();
+        if clock.unix_timestamp >= ctx.accounts.campaign.deadline {
+            return Err(ErrorCode::CampaignEnded.into())
+        }
+        let price = ctx.accounts.campaign.price;
+        token::transfer(ctx.accounts.into_transfer_to_vault_context(), price)?;
+
+        let deposit = &mut ctx.accounts.deposit;
+        deposit.campaign_key = *ctx.accounts.campaign.to_account_info().key;
+        deposit.buyer_key = *ctx.accounts.buyer.key;
+        deposit.buyer_deposit_token_account = *ctx.accounts.buyer_deposit_token_account.to_account_info().key;
+        deposit.amount = price;
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.participants = campaign.participants.checked_add(1).ok_or(ErrorCode::AmountOverflow)?;
+        campaign.open_deposits = campaign.open_deposits.checked_add(1).ok_or(ErrorCode::AmountOverflow)?;
+
+        emit!(CampaignJoined {
+            campaign_id,
+            buyer_key: *ctx.accounts.buyer.key,
+            amount: price,
+            participants: ctx.accounts.campaign.participants,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn convert_campaign_deposit(
+        ctx: Context<ConvertCampaignDeposit>,
+        _vault_account_bump: u8,
+        campaign_id: u64,
+        order_code: u64,
+    ) -> ProgramResult {
+        // Once the threshold is met, the seller turns each deposit into a New escrow of its
+        // buyer, like initialize would have created it. Past the conversion deadline anyone can.
+        // The payer pays and gets back the rent.
+        assert_not_paused(&ctx.accounts.config)?;
+        if ctx.accounts.campaign.participants < ctx.accounts.campaign.threshold {
+            return Err(ErrorCode::CampaignThresholdNotMet.into())
+        }
+        let clock: Clock = Clock::get().unwrap();
+        if !ctx.accounts.seller.is_signer && clock.unix_timestamp < ctx.accounts.campaign.conversion_deadline {
+            return Err(ErrorCode::ConversionDeadlineNotPassed.into())
+        }
+        let amount = ctx.accounts.deposit.amount;
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        escrow_account.buyer_key = ctx.accounts.deposit.buyer_key;
+        escrow_account.buyer_deposit_token_account = ctx.accounts.deposit.buyer_deposit_token_account;
+        escrow_account.seller_key = ctx.accounts.campaign.seller_key;
+        escrow_account.fulfiller_key = ctx.accounts.campaign.seller_key;
+        escrow_account.seller_receive_token_account = ctx.accounts.campaign.seller_receive_token_account;
+        escrow_account.judge_key = ctx.accounts.campaign.judge_key;
+        escrow_account.amount = amount;
+        escrow_account.order_code = order_code;
+        escrow_account.status = 0;
+        escrow_account.delivery_time = clock.unix_timestamp;
+        escrow_account.trial_day = ctx.accounts.campaign.trial_day;
+        escrow_account.frozen = false;
+        escrow_account.rent_payer = *ctx.accounts.payer.key;
+
+        // Init PDA
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (vault_authority, _vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        token::set_authority(
+            ctx.accounts.into_set_authority_context(),
+            AuthorityType::AccountOwner,
+            Some(vault_authority),
+        )?;
+
+        // Make Seed
+        let campaign_seed: String = format!("{}{}", "campaign-".to_string(), campaign_id.to_string());
+        let campaign_pda_seed: &[u8] = campaign_seed.as_bytes();
+        let (_campaign, campaign_bump) = Pubkey::find_program_address(&[campaign_pda_seed], ctx.program_id);
+        let campaign_seeds = &[&campaign_pda_seed[..], &[campaign_bump]];
+
+        // Transfer the deposit to the escrow's vault
+        token::transfer(
+            ctx.accounts.into_transfer_to_vault_context().with_signer(&[&campaign_seeds[..]]),
+            amount,
+        )?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.open_deposits = campaign.open_deposits.checked_sub(1).ok_or(ErrorCode::AmountOverflow)?;
+
+        emit!(EscrowCreated {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            judge_key: ctx.accounts.escrow_account.judge_key,
+            amount,
+            escrow_amount: amount,
+            trial_day: ctx.accounts.escrow_account.trial_day,
+            old_status: 0,
+            new_status: 0,
+            timestamp: clock.unix_timestamp,
+        });
+        emit!(CampaignDepositConverted {
+            campaign_id,
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn refund_campaign_deposit(ctx: Context<RefundCampaignDeposit>, campaign_id: u64,) -> ProgramResult {
+        // Past the deadline without enough buyers, or past the conversion deadline with the deposit
+        // still not converted, each buyer takes its deposit back.
+        assert_not_paused(&ctx.accounts.config)?;
+        let clock: Clock = Clock::get().unwrap();
+        let failed = clock.unix_timestamp >= ctx.accounts.campaign.deadline
+            && ctx.accounts.campaign.participants < ctx.accounts.campaign.threshold;
+        if !failed && clock.unix_timestamp < ctx.accounts.campaign.conversion_deadline {
+            return Err(ErrorCode::CampaignNotFailed.into())
+        }
+
+        // Make Seed
+        let campaign_seed: String = format!("{}{}", "campaign-".to_string(), campaign_id.to_string());
+        let campaign_pda_seed: &[u8] = campaign_seed.as_bytes();
+        let (_campaign, campaign_bump) = Pubkey::find_program_address(&[campaign_pda_seed], ctx.program_id);
+        let campaign_seeds = &[&campaign_pda_seed[..], &[campaign_bump]];
+
+        // Transfer token to buyer.
+        let amount = ctx.accounts.deposit.amount;
+        token::transfer(
+            ctx.accounts.into_transfer_to_buyer_context().with_signer(&[&campaign_seeds[..]]),
+            amount,
+        )?;
+        let campaign = &mut ctx.accounts.campaign;
+        // A campaign that met its threshold keeps it, the deposits left can still be converted.
+        if failed {
+            campaign.participants = campaign.participants.checked_sub(1).ok_or(ErrorCode::AmountOverflow)?;
+        }
+        campaign.open_deposits = campaign.open_deposits.checked_sub(1).ok_or(ErrorCode::AmountOverflow)?;
+
+        emit!(CampaignDepositRefunded {
+            campaign_id,
+            buyer_key: *ctx.accounts.buyer.key,
+            amount,
+            participants: ctx.accounts.campaign.participants,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_campaign(ctx: Context<CloseCampaign>, campaign_id: u64,) -> ProgramResult {
+        // Every deposit was converted or refunded and no buyer can join anymore.
+        let clock: Clock = Clock::get().unwrap();
+        if clock.unix_timestamp < ctx.accounts.campaign.deadline || ctx.accounts.campaign.open_deposits > 0 {
+            return Err(ErrorCode::CampaignNotClosable.into())
+        }
+
+        // Make Seed
+        let campaign_seed: String = format!("{}{}", "campaign-".to_string(), campaign_id.to_string());
+        let campaign_pda_seed: &[u8] = campaign_seed.as_bytes();
+        let (_campaign, campaign_bump) = Pubkey::find_program_address(&[campaign_pda_seed], ctx.program_id);
+        let campaign_seeds = &[&campaign_pda_seed[..], &[campaign_bump]];
+
+        // Close vault account
+        token::close_account(
+            ctx.accounts.into_close_context().with_signer(&[&campaign_seeds[..]]),
+        )?;
+
+        emit!(CampaignClosed {
+            campaign_id,
+            seller_key: ctx.accounts.campaign.seller_key,
+            participants: ctx.accounts.campaign.participants,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn initialize_seller_funded(
+        ctx: Context<InitializeSellerFunded>,
+        _vault_account_bump: u8,
+        amount: u64,
+        order_code: u64,
+        payment_window: i64,
+    ) -> ProgramResult {
+        // P2P trade: the seller locks the tokens, the buyer pays fiat off-chain and `mark_paid`
+        // within `payment_window` seconds, the seller confirms the payment with `release`. The
+        // buyer's receive token account is stored as `buyer_deposit_token_account` and the
+        // seller's deposit one as `seller_receive_token_account`, so that adjudge_for_buyer pays
+        // the buyer and adjudge_for_seller returns the tokens to the seller.
+        assert_not_paused(&ctx.accounts.config)?;
+        if payment_window <= 0 {
+            return Err(ErrorCode::InvalidPaymentWindow.into())
+        }
+        let clock: Clock = Clock::get().unwrap();
+        // Init escrow_account
+        ctx.accounts.escrow_account.buyer_key = *ctx.accounts.buyer.key;
+        ctx.accounts.escrow_account.buyer_deposit_token_account = *ctx.accounts.buyer_receive_token_account.to_account_info().key;
+        ctx.accounts.escrow_account.seller_key = *ctx.accounts.seller.key;
+        ctx.accounts.escrow_account.fulfiller_key = *ctx.accounts.seller.key;
+        ctx.accounts.escrow_account.seller_receive_token_account = *ctx.accounts.seller_deposit_token_account.to_account_info().key;
+        ctx.accounts.escrow_account.judge_key = *ctx.accounts.judge.key;
+        ctx.accounts.escrow_account.amount = amount;
+        ctx.accounts.escrow_account.order_code = order_code;
+        ctx.accounts.escrow_account.status = 0;
+        ctx.accounts.escrow_account.delivery_time = clock.unix_timestamp;
+        ctx.accounts.escrow_account.trial_day = 0;
+        ctx.accounts.escrow_account.frozen = false;
+        ctx.accounts.escrow_account.rent_payer = *ctx.accounts.payer.key;
+        ctx.accounts.escrow_account.seller_funded = true;
+        ctx.accounts.escrow_account.payment_deadline = clock.unix_timestamp
+            .checked_add(payment_window)
+            .ok_or(ErrorCode::InvalidPaymentWindow)?;
+
+        // Init PDA
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (vault_authority, _vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        token::set_authority(
+            ctx.accounts.into_set_authority_context(),
+            AuthorityType::AccountOwner,
+            Some(vault_authority),
+        )?;
+
+        // Transfer token to PDA
+        token::transfer(
+            ctx.accounts.into_transfer_to_pda_context(),
+            ctx.accounts.escrow_account.amount,
+        )?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        emit!(EscrowCreated {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            judge_key: ctx.accounts.escrow_account.judge_key,
+            amount,
+            escrow_amount: amount,
+            trial_day: 0,
+            old_status: 0,
+            new_status: 0,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn mark_paid(ctx: Context<MarkPaid>, order_code: u64,) -> ProgramResult {
+        // The buyer reports the fiat payment. The seller can no longer reclaim_unpaid, only
+        // release or the judge moves the tokens.
+        let clock: Clock = Clock::get().unwrap();
+        if clock.unix_timestamp >= ctx.accounts.escrow_account.payment_deadline {
+            return Err(ErrorCode::PaymentDeadlinePassed.into())
+        }
+        let old_status = ctx.accounts.escrow_account.status;
+        ctx.accounts.escrow_account.status = 1;
+
+        emit!(MarkedPaid {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount: 0,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status,
+            new_status: 1,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn release(ctx: Context<Release>, order_code: u64,) -> ProgramResult {
+        // The seller confirms the fiat payment and the tokens go to the buyer.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+
+        // Make Seed
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+        // Transfer token to buyer.
+        token::transfer(
+            ctx.accounts.into_transfer_to_buyer_context().with_signer(&[&authority_seeds[..]]),
+            ctx.accounts.escrow_account.amount,
+        )?;
+
+        // Close vault account
+        token::close_account(
+            ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
+        )?;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(Released {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout: ctx.accounts.escrow_account.amount,
+                seller_payout: 0,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 5,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn reclaim_unpaid(ctx: Context<ReclaimUnpaid>, order_code: u64,) -> ProgramResult {
+        // Anyone, e.g. a crank, can return a seller-funded escrow the buyer did not mark paid in
+        // time to the seller.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        if clock.unix_timestamp < ctx.accounts.escrow_account.payment_deadline {
+            return Err(ErrorCode::PaymentDeadlineNotPassed.into())
+        }
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+
+        // Make Seed
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+        // Transfer token back to seller.
+        token::transfer(
+            ctx.accounts.into_transfer_to_seller_context().with_signer(&[&authority_seeds[..]]),
+            ctx.accounts.escrow_account.amount,
+        )?;
+
+        // Close vault account
+        token::close_account(
+            ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
+        )?;
+
+        emit!(Reclaimed {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout: 0,
+                seller_payout: ctx.accounts.escrow_account.amount,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 6,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn initialize_double_deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitializeDoubleDeposit<'info>>,
+        _vault_account_bump: u8,
+        price: u64,
+        order_code: u64,
+        buyer_collateral: u64,
+        seller_collateral: u64,
+        long_stop: i64,
+    ) -> ProgramResult {
+        // No judge: the buyer and the seller both lock collateral on top of the price and only
+        // get it back by agreeing on `settle` or `mutual_cancel`. With a `long_stop`, anyone can
+        // burn the vault after it, or donate it to the token account given as the first
+        // remaining account.
+        assert_not_paused(&ctx.accounts.config)?;
+        if buyer_collateral == 0 || seller_collateral == 0 {
+            return Err(ErrorCode::InvalidCollateral.into())
+        }
+        let clock: Clock = Clock::get().unwrap();
+        let long_stop_recipient = match ctx.remaining_accounts.first() {
+            Some(recipient_info) => {
+                let recipient: Account<'info, TokenAccount> = Account::try_from(recipient_info)
+                    .map_err(|_| ErrorCode::InvalidLongStop)?;
+                if recipient.mint != *ctx.accounts.mint.to_account_info().key {
+                    return Err(ErrorCode::InvalidLongStop.into())
+                }
+                *recipient_info.key
+            }
+            None => Pubkey::default(),
+        };
+        if long_stop < 0
+            || (long_stop > 0 && long_stop <= clock.unix_timestamp)
+            || (long_stop == 0 && long_stop_recipient != Pubkey::default())
+        {
+            return Err(ErrorCode::InvalidLongStop.into())
+        }
+        let buyer_amount = price.checked_add(buyer_collateral).ok_or(ErrorCode::AmountOverflow)?;
+        let amount = buyer_amount.checked_add(seller_collateral).ok_or(ErrorCode::AmountOverflow)?;
+
+        // Init escrow_account
+        ctx.accounts.escrow_account.buyer_key = *ctx.accounts.buyer.key;
+        ctx.accounts.escrow_account.buyer_deposit_token_account = *ctx.accounts.buyer_deposit_token_account.to_account_info().key;
+        ctx.accounts.escrow_account.seller_key = *ctx.accounts.seller.key;
+        ctx.accounts.escrow_account.fulfiller_key = *ctx.accounts.seller.key;
+        ctx.accounts.escrow_account.seller_receive_token_account = *ctx.accounts.seller_receive_token_account.to_account_info().key;
+        ctx.accounts.escrow_account.judge_key = Pubkey::default();
+        ctx.accounts.escrow_account.amount = amount;
+        ctx.accounts.escrow_account.order_code = order_code;
+        ctx.accounts.escrow_account.status = 0;
+        ctx.accounts.escrow_account.delivery_time = clock.unix_timestamp;
+        ctx.accounts.escrow_account.trial_day = 0;
+        ctx.accounts.escrow_account.frozen = false;
+        ctx.accounts.escrow_account.rent_payer = *ctx.accounts.payer.key;
+        ctx.accounts.escrow_account.double_deposit = true;
+        ctx.accounts.escrow_account.buyer_collateral = buyer_collateral;
+        ctx.accounts.escrow_account.seller_collateral = seller_collateral;
+        ctx.accounts.escrow_account.long_stop = long_stop;
+        ctx.accounts.escrow_account.long_stop_recipient = long_stop_recipient;
+
+        // Init PDA
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (vault_authority, _vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        token::set_authority(
+            ctx.accounts.into_set_authority_context(),
+            AuthorityType::AccountOwner,
+            Some(vault_authority),
+        )?;
+
+        // Transfer the price and both collaterals to PDA
+        token::transfer(ctx.accounts.into_transfer_from_buyer_context(), buyer_amount)?;
+        token::transfer(ctx.accounts.into_transfer_from_seller_context(), seller_collateral)?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        emit!(EscrowCreated {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            judge_key: Pubkey::default(),
+            amount,
+            escrow_amount: amount,
+            trial_day: 0,
+            old_status: 0,
+            new_status: 0,
+            timestamp: clock.unix_timestamp,
+        });
+        emit!(CollateralLocked {
+            order_code,
+            buyer_collateral,
+            seller_collateral,
+            long_stop,
+            long_stop_recipient,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn settle(ctx: Context<MutualSettlement>, order_code: u64,) -> ProgramResult {
+        // Both parties agree the order went through: the seller gets the price and its
+        // collateral, the buyer its collateral back. Anything donated to the vault goes with the price.
+        let surplus = vault_surplus(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        let buyer_payout = ctx.accounts.escrow_account.buyer_collateral;
+        let seller_payout = ctx.accounts.escrow_account.amount.checked_sub(buyer_payout)
+            .and_then(|payout| payout.checked_add(surplus))
+            .ok_or(ErrorCode::AmountOverflow)?;
+        let rent_refund = pay_out_double_deposit(ctx.accounts, ctx.program_id, buyer_payout, seller_payout)?;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(MutuallySettled {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            buyer_payout,
+            seller_payout,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout,
+                seller_payout,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 7,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn mutual_cancel(ctx: Context<MutualSettlement>, order_code: u64,) -> ProgramResult {
+        // Both parties call the order off: the buyer gets the price and its collateral, the
+        // seller its collateral back. Anything donated to the vault goes with the price.
+        let surplus = vault_surplus(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        let seller_payout = ctx.accounts.escrow_account.seller_collateral;
+        let buyer_payout = ctx.accounts.escrow_account.amount.checked_sub(seller_payout)
+            .and_then(|payout| payout.checked_add(surplus))
+            .ok_or(ErrorCode::AmountOverflow)?;
+        let rent_refund = pay_out_double_deposit(ctx.accounts, ctx.program_id, buyer_payout, seller_payout)?;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(MutuallyCancelled {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            buyer_payout,
+            seller_payout,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout,
+                seller_payout,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 8,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn expire_long_stop<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExpireLongStop<'info>>,
+        order_code: u64,
+    ) -> ProgramResult {
+        // Anyone, e.g. a crank, can burn a double-deposit escrow the parties did not settle by
+        // its long stop, or donate it to its `long_stop_recipient`, first in remaining_accounts.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        if clock.unix_timestamp < ctx.accounts.escrow_account.long_stop {
+            return Err(ErrorCode::LongStopNotReached.into())
+        }
+        let recipient_key = ctx.accounts.escrow_account.long_stop_recipient;
+        let (recipient, receipt_accounts) = if recipient_key == Pubkey::default() {
+            (None, ctx.remaining_accounts)
+        } else {
+            match ctx.remaining_accounts.split_first() {
+                Some((recipient, rest)) if *recipient.key == recipient_key => (Some(recipient), rest),
+                _ => return Err(ErrorCode::InvalidLongStop.into()),
+            }
+        };
+        // The whole vault goes, including anything donated to it.
+        let amount = ctx.accounts.escrow_account.amount
+            .checked_add(vault_surplus(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+
+        // Make Seed
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+        match recipient {
+            // Donate token to the recipient.
+            Some(recipient) => token::transfer(
+                ctx.accounts.into_transfer_to_recipient_context(recipient.clone()).with_signer(&[&authority_seeds[..]]),
+                amount,
+            )?,
+            // Burn token.
+            None => token::burn(
+                ctx.accounts.into_burn_context().with_signer(&[&authority_seeds[..]]),
+                amount,
+            )?,
+        }
+
+        // Close vault account
+        token::close_account(
+            ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
+        )?;
+
+        emit!(LongStopExpired {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            recipient_key,
+            amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            receipt_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout: 0,
+                seller_payout: 0,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 9,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn initialize_rental(
+        ctx: Context<InitializeRental>,
+        _vault_account_bump: u8,
+        amount: u64,
+        order_code: u64,
+        rental_end: i64,
+        claim_window_day: u16,
+    ) -> ProgramResult {
+        // Security deposit: the buyer locks `amount` until `rental_end`, stored as
+        // `delivery_time`, and the seller can `claim_damages` during the following
+        // `claim_window_day` days, stored as `trial_day`. Unclaimed, `return_deposit` gives the
+        // deposit back to the buyer once the window is over.
+        assert_not_paused(&ctx.accounts.config)?;
+        let clock: Clock = Clock::get().unwrap();
+        if rental_end <= clock.unix_timestamp || claim_window_day == 0 {
+            return Err(ErrorCode::InvalidRentalPeriod.into())
+        }
+        // Init escrow_account
+        ctx.accounts.escrow_account.buyer_key = *ctx.accounts.buyer.key;
+        ctx.accounts.escrow_account.buyer_deposit_token_account = *ctx.accounts.buyer_deposit_token_account.to_account_info().key;
+        ctx.accounts.escrow_account.seller_key = *ctx.accounts.seller.key;
+        ctx.accounts.escrow_account.fulfiller_key = *ctx.accounts.seller.key;
+        ctx.accounts.escrow_account.seller_receive_token_account = *ctx.accounts.seller_receive_token_account.to_account_info().key;
+        ctx.accounts.escrow_account.judge_key = *ctx.accounts.judge.key;
+        ctx.accounts.escrow_account.amount = amount;
+        ctx.accounts.escrow_account.order_code = order_code;
+        ctx.accounts.escrow_account.status = 2;
+        ctx.accounts.escrow_account.delivery_time = rental_end;
+        ctx.accounts.escrow_account.trial_day = claim_window_day;
+        ctx.accounts.escrow_account.frozen = false;
+        ctx.accounts.escrow_account.rent_payer = *ctx.accounts.payer.key;
+        ctx.accounts.escrow_account.rental = true;
+
+        // Init PDA
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (vault_authority, _vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        token::set_authority(
+            ctx.accounts.into_set_authority_context(),
+            AuthorityType::AccountOwner,
+            Some(vault_authority),
+        )?;
+
+        // Transfer token to PDA
+        token::transfer(
+            ctx.accounts.into_transfer_to_pda_context(),
+            ctx.accounts.escrow_account.amount,
+        )?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        emit!(EscrowCreated {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            judge_key: ctx.accounts.escrow_account.judge_key,
+            amount,
+            escrow_amount: amount,
+            trial_day: claim_window_day,
+            old_status: 0,
+            new_status: 2,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_damages(
+        ctx: Context<ClaimDamages>,
+        order_code: u64,
+        amount: u64,
+        evidence_hash: [u8; 32],
+    ) -> ProgramResult {
+        // The seller claims part or all of the deposit once the rental is over, with the hash of
+        // its evidence, e.g. photos kept off-chain. The buyer then accepts or disputes it within
+        // as many days as the claim window, after which anyone can settle it as accepted.
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        if clock.unix_timestamp < ctx.accounts.escrow_account.delivery_time {
+            return Err(ErrorCode::RentalNotEnded.into())
+        }
+        if !lambda_escrow_model::in_trial_day(ctx.accounts.escrow_account.delivery_time, ctx.accounts.escrow_account.trial_day, clock.unix_timestamp) {
+            return Err(ErrorCode::ClaimWindowClosed.into())
+        }
+        if ctx.accounts.escrow_account.damages_claim > 0 {
+            return Err(ErrorCode::DamagesAlreadyClaimed.into())
+        }
+        if amount == 0 || amount > ctx.accounts.escrow_account.amount {
+            return Err(ErrorCode::InvalidDamages.into())
+        }
+        ctx.accounts.escrow_account.damages_claim = amount;
+        ctx.accounts.escrow_account.damages_evidence_hash = evidence_hash;
+        ctx.accounts.escrow_account.damages_response_deadline =
+            lambda_escrow_model::trial_end(clock.unix_timestamp, ctx.accounts.escrow_account.trial_day);
+
+        emit!(DamagesClaimed {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            evidence_hash,
+            amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn accept_damages(ctx: Context<AcceptDamages>, order_code: u64,) -> ProgramResult {
+        // The buyer agrees with the claim: the seller gets the damages, the buyer the rest.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        if ctx.accounts.escrow_account.dispute_time > 0 {
+            return Err(ErrorCode::EscrowDisputed.into())
+        }
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+        let seller_payout = ctx.accounts.escrow_account.damages_claim;
+        let buyer_payout = ctx.accounts.escrow_account.amount.checked_sub(seller_payout)
+            .ok_or(ErrorCode::AmountOverflow)?;
+
+        // Make Seed
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+        // Transfer the damages to seller, the rest back to buyer.
+        token::transfer(
+            ctx.accounts.into_transfer_to_seller_context().with_signer(&[&authority_seeds[..]]),
+            seller_payout,
+        )?;
+        if buyer_payout > 0 {
+            token::transfer(
+                ctx.accounts.into_transfer_to_buyer_context().with_signer(&[&authority_seeds[..]]),
+                buyer_payout,
+            )?;
+        }
+
+        // Close vault account
+        token::close_account(
+            ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
+        )?;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(DamagesAccepted {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            buyer_payout,
+            seller_payout,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout,
+                seller_payout,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 10,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn dispute_damages(ctx: Context<DisputeDamages>, order_code: u64,) -> ProgramResult {
+        // The buyer escalates the claim. Only the judge settles it, with adjudge_partial to give
+        // the buyer part of the deposit back and adjudge_for_buyer or adjudge_for_seller for the rest.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        if ctx.accounts.escrow_account.dispute_time > 0 {
+            return Err(ErrorCode::EscrowDisputed.into())
+        }
+        let clock: Clock = Clock::get().unwrap();
+        if clock.unix_timestamp >= ctx.accounts.escrow_account.damages_response_deadline {
+            return Err(ErrorCode::ResponseWindowClosed.into())
+        }
+        ctx.accounts.escrow_account.dispute_time = clock.unix_timestamp;
+
+        emit!(Disputed {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            judge_key: ctx.accounts.escrow_account.judge_key,
+            amount: 0,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn settle_damages(ctx: Context<SettleDamages>, order_code: u64,) -> ProgramResult {
+        // Anyone, e.g. the seller or a crank, settles a claim the buyer left unanswered as
+        // accepted once the response window is over.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        if ctx.accounts.escrow_account.dispute_time > 0 {
+            return Err(ErrorCode::EscrowDisputed.into())
+        }
+        let clock: Clock = Clock::get().unwrap();
+        if clock.unix_timestamp < ctx.accounts.escrow_account.damages_response_deadline {
+            return Err(ErrorCode::ResponseWindowOpen.into())
+        }
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+        let seller_payout = ctx.accounts.escrow_account.damages_claim;
+        let buyer_payout = ctx.accounts.escrow_account.amount.checked_sub(seller_payout)
+            .ok_or(ErrorCode::AmountOverflow)?;
+
+        // Make Seed
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+        // Transfer the damages to seller, the rest back to buyer.
+        token::transfer(
+            ctx.accounts.into_transfer_to_seller_context().with_signer(&[&authority_seeds[..]]),
+            seller_payout,
+        )?;
+        if buyer_payout > 0 {
+            token::transfer(
+                ctx.accounts.into_transfer_to_buyer_context().with_signer(&[&authority_seeds[..]]),
+                buyer_payout,
+            )?;
+        }
+
+        // Close vault account
+        token::close_account(
+            ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
+        )?;
+
+        emit!(DamagesAccepted {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            buyer_payout,
+            seller_payout,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout,
+                seller_payout,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 12,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn return_deposit(ctx: Context<ReturnDeposit>, order_code: u64,) -> ProgramResult {
+        // Anyone, e.g. a crank, can give an unclaimed deposit back to the buyer once the claim
+        // window is over.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        if lambda_escrow_model::in_trial_day(ctx.accounts.escrow_account.delivery_time, ctx.accounts.escrow_account.trial_day, clock.unix_timestamp) {
+            return Err(ErrorCode::ClaimWindowOpen.into())
+        }
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+
+        // Make Seed
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+        // Transfer token back to buyer.
+        token::transfer(
+            ctx.accounts.into_transfer_to_buyer_context().with_signer(&[&authority_seeds[..]]),
+            ctx.accounts.escrow_account.amount,
+        )?;
+
+        // Close vault account
+        token::close_account(
+            ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
+        )?;
+
+        emit!(DepositReturned {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout: ctx.accounts.escrow_account.amount,
+                seller_payout: 0,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 11,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn request_surcharge(
+        ctx: Context<RequestSurcharge>,
+        order_code: u64,
+        amount: u64,
+        reason_hash: [u8; 32],
+        deadline: i64,
+    ) -> ProgramResult {
+        // The seller asks for extra shipping or customs costs, with the hash of its reason kept
+        // off-chain. The buyer funds it with approve_surcharge or drops it with reject_surcharge
+        // before `deadline`, after which it expires and the seller can ask again.
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        assert_vesting_not_started(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        if amount == 0 || deadline <= clock.unix_timestamp {
+            return Err(ErrorCode::InvalidSurcharge.into())
+        }
+        if ctx.accounts.escrow_account.surcharge_amount > 0
+            && clock.unix_timestamp < ctx.accounts.escrow_account.surcharge_deadline
+        {
+            return Err(ErrorCode::SurchargePending.into())
+        }
+        ctx.accounts.escrow_account.surcharge_amount = amount;
+        ctx.accounts.escrow_account.surcharge_reason_hash = reason_hash;
+        ctx.accounts.escrow_account.surcharge_deadline = deadline;
+
+        emit!(SurchargeRequested {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            reason_hash,
+            deadline,
+            amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_surcharge(ctx: Context<ChargeMore>, order_code: u64, amount: u64,) -> ProgramResult {
+        // Funds the pending surcharge like charge_more. The buyer repeats its `amount`, so the
+        // seller cannot swap it for another one between the buyer reading and signing.
+        assert_not_paused(&ctx.accounts.config)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        assert_buyer_funded(&ctx.accounts.escrow_account)?;
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
+        assert_vesting_not_started(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        if ctx.accounts.escrow_account.surcharge_amount == 0 {
+            return Err(ErrorCode::NoPendingSurcharge.into())
+        }
+        if clock.unix_timestamp >= ctx.accounts.escrow_account.surcharge_deadline {
+            return Err(ErrorCode::SurchargeExpired.into())
+        }
+        if amount != ctx.accounts.escrow_account.surcharge_amount {
+            return Err(ErrorCode::SurchargeMismatch.into())
+        }
+
+        // Transfer token to PDA
+        token::transfer(
+            ctx.accounts.into_transfer_to_pda_context(),
+            amount,
+        )?;
+        // Update escrow_account
+        ctx.accounts.escrow_account.amount = ctx.accounts.escrow_account.amount.checked_add(amount)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        ctx.accounts.escrow_account.surcharge_amount = 0;
+        ctx.accounts.escrow_account.surcharge_reason_hash = [0; 32];
+        ctx.accounts.escrow_account.surcharge_deadline = 0;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        emit!(Charged {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn reject_surcharge(ctx: Context<RejectSurcharge>, order_code: u64,) -> ProgramResult {
+        // The buyer drops the pending surcharge, expired or not.
+        if ctx.accounts.escrow_account.surcharge_amount == 0 {
+            return Err(ErrorCode::NoPendingSurcharge.into())
+        }
+        let amount = ctx.accounts.escrow_account.surcharge_amount;
+        ctx.accounts.escrow_account.surcharge_amount = 0;
+        ctx.accounts.escrow_account.surcharge_reason_hash = [0; 32];
+        ctx.accounts.escrow_account.surcharge_deadline = 0;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(SurchargeRejected {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn adjudge(ctx: Context<Adjudge>, order_code: u64, status: u8,) -> ProgramResult {
+        // Judge can set status = (New or Shipping or Delivered).
+        if ctx.accounts.escrow_account.order_code == order_code && (status == 0 || status == 1 || status == 2) {
+            // Update escrow_account
+            let old_status = ctx.accounts.escrow_account.status;
+            ctx.accounts.escrow_account.status = status;
+            // If set status = Delivered, update delivery_time
+            let clock: Clock = Clock::get().unwrap();
+            if status == 2 {
+                ctx.accounts.escrow_account.delivery_time = clock.unix_timestamp;
+            }
+
+            emit!(Adjudged {
+                order_code,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                recipient_key: Pubkey::default(),
+                amount: 0,
+                escrow_amount: ctx.accounts.escrow_account.amount,
+                old_status,
+                new_status: status,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn adjudge_partial(ctx: Context<AdjudgePartial>, order_code: u64, amount: u64,) -> ProgramResult {
+        // Judge-ordered resolution is still allowed on a frozen escrow.
+        assert_not_paused(&ctx.accounts.config)?;
+        // Make Seed
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+        // Transfer token to buyer.
+        token::transfer(
+            ctx.accounts.into_transfer_to_buyer_context().with_signer(&[&authority_seeds[..]]),
+            amount,
+        )?;
+        // Update escrow_account
+        ctx.accounts.escrow_account.amount = ctx.accounts.escrow_account.amount.checked_sub(amount)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(Adjudged {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            judge_key: ctx.accounts.escrow_account.judge_key,
+            recipient_key: ctx.accounts.escrow_account.buyer_key,
+            amount,
+            escrow_amount: ctx.accounts.escrow_account.amount,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: ctx.accounts.escrow_account.status,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn adjudge_for_buyer(ctx: Context<AdjudgeForBuyer>, order_code: u64,) -> ProgramResult {
+        // Judge-ordered resolution is still allowed on a frozen escrow.
+        assert_not_paused(&ctx.accounts.config)?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+
+        // Make Seed
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+        // Transfer token to buyer.
+        token::transfer(
+            ctx.accounts.into_transfer_to_buyer_context().with_signer(&[&authority_seeds[..]]),
+            ctx.accounts.escrow_account.amount,
+        )?;
+
+        // Close vault account
+        token::close_account(
+            ctx.accounts.into_close_contest().with_signer(&[&authority_seeds[..]]),
+        )?;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(Adjudged {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            judge_key: ctx.accounts.escrow_account.judge_key,
+            recipient_key: ctx.accounts.escrow_account.buyer_key,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout: ctx.accounts.escrow_account.amount,
+                seller_payout: 0,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 2,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn adjudge_for_seller(ctx: Context<AdjudgeForSeller>, order_code: u64,) -> ProgramResult {
+        // Judge-ordered resolution is still allowed on a frozen escrow.
+        assert_not_paused(&ctx.accounts.config)?;
+        let (payee, receipt_accounts) = receivable_payee(
+            &ctx.accounts.escrow_account,
+            &ctx.accounts.seller_receive_token_account,
+            ctx.remaining_accounts,
+        )?;
+        check_vault(&mut ctx.accounts.vault_account, &ctx.accounts.escrow_account)?;
+        // Lamports the rent payer gets back once the vault and escrow are closed.
+        let rent_refund = ctx.accounts.vault_account.to_account_info().lamports()
+            + ctx.accounts.escrow_account.to_account_info().lamports();
+
+        // Make Seed
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+        // Transfer token to seller.
+        token::transfer(
+            ctx.accounts.into_transfer_to_seller_context().with_signer(&[&authority_seeds[..]]),
+            ctx.accounts.escrow_account.amount,
+        )?;
+
+        // Close vault account
+        token::close_account(
+            ctx.accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
+        )?;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(Adjudged {
+            order_code,
+            buyer_key: ctx.accounts.escrow_account.buyer_key,
+            seller_key: ctx.accounts.escrow_account.seller_key,
+            judge_key: ctx.accounts.escrow_account.judge_key,
+            recipient_key: payee,
+            amount: ctx.accounts.escrow_account.amount,
+            escrow_amount: 0,
+            old_status: ctx.accounts.escrow_account.status,
+            new_status: 3,
+            timestamp: clock.unix_timestamp,
+        });
+
+        write_settlement_receipt(
+            ctx.program_id,
+            receipt_accounts,
+            &SettlementReceipt {
+                order_code,
+                escrow_key: *ctx.accounts.escrow_account.to_account_info().key,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: payee,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                buyer_payout: 0,
+                seller_payout: ctx.accounts.escrow_account.amount,
+                fee: 0,
+                rent_refund,
+                rent_payer: ctx.accounts.escrow_account.rent_payer,
+                settlement_path: 3,
+                timestamp: clock.unix_timestamp,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub fn update_trial_day(ctx: Context<UpdateTrialDay>, order_code: u64, trial_day: u16,) -> ProgramResult {
+        if ctx.accounts.escrow_account.order_code == order_code {
+            // Update escrow_account trial_day
+            let old_trial_day = ctx.accounts.escrow_account.trial_day;
+            ctx.accounts.escrow_account.trial_day = trial_day;
+
+            let clock: Clock = Clock::get().unwrap();
+            emit!(TrialUpdated {
+                order_code,
+                buyer_key: ctx.accounts.escrow_account.buyer_key,
+                seller_key: ctx.accounts.escrow_account.seller_key,
+                judge_key: ctx.accounts.escrow_account.judge_key,
+                old_trial_day,
+                new_trial_day: trial_day,
+                amount: 0,
+                escrow_amount: ctx.accounts.escrow_account.amount,
+                old_status: ctx.accounts.escrow_account.status,
+                new_status: ctx.accounts.escrow_account.status,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn sync_vault(ctx: Context<SyncVault>, order_code: u64,) -> ProgramResult {
+        // Judge aligns the escrow amount with the vault, e.g. after a direct transfer into it,
+        // on a frozen escrow as well.
+        assert_not_itemized(&ctx.accounts.escrow_account)?;
+        let escrow_seed: String = format!("{}{}", "escrow-".to_string(), order_code.to_string());
+        let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+        let (vault_authority, _vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], ctx.program_id);
+        if ctx.accounts.vault_account.owner != vault_authority {
+            return Err(ErrorCode::VaultMismatch.into())
+        }
+
+        let old_amount = ctx.accounts.escrow_account.amount;
+        ctx.accounts.escrow_account.amount = ctx.accounts.vault_account.amount;
+
+        let clock: Clock = Clock::get().unwrap();
+        emit!(VaultSynced {
+            order_code,
+            judge_key: ctx.accounts.escrow_account.judge_key,
+            old_amount,
+            new_amount: ctx.accounts.escrow_account.amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn initialize_config(ctx: Context<InitializeConfig>, _config_bump: u8, judge_change_delay: i64,) -> ProgramResult {
+        assert_upgrade_authority(ctx.program_id, &ctx.accounts.program_data, ctx.accounts.admin.key)?;
+        if judge_change_delay < 0 {
+            return Err(ErrorCode::InvalidTimelock.into())
+        }
+        ctx.accounts.config.admin = *ctx.accounts.admin.key;
+        ctx.accounts.config.compliance_authority = *ctx.accounts.compliance_authority.key;
+        ctx.accounts.config.judge_change_delay = judge_change_delay;
+        ctx.accounts.config.paused = false;
+
+        Ok(())
+    }
+
+    pub fn update_config(ctx: Context<UpdateConfig>, judge_change_delay: i64,) -> ProgramResult {
+        if judge_change_delay < 0 {
+            return Err(ErrorCode::InvalidTimelock.into())
+        }
+        ctx.accounts.config.admin = *ctx.accounts.new_admin.key;
+        ctx.accounts.config.compliance_authority = *ctx.accounts.compliance_authority.key;
+        ctx.accounts.config.judge_change_delay = judge_change_delay;
+
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool,) -> ProgramResult {
+        let clock: Clock = Clock::get().unwrap();
+        ctx.accounts.config.paused = paused;
+
+        emit!(PauseUpdated {
+            paused,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_frozen(ctx: Context<SetFrozen>, order_code: u64, frozen: bool,) -> ProgramResult {
+        let clock: Clock = Clock::get().unwrap();
+        ctx.accounts.escrow_account.frozen = frozen;
+
+        emit!(FreezeUpdated {
+            order_code,
+            frozen,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn change_judge(ctx: Context<ChangeJudge>, order_code: u64,) -> ProgramResult {
+        // Buyer and Seller agree on a new judge, applied immediately.
+        assert_not_double_deposit(&ctx.accounts.escrow_account)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        let old_judge_key = ctx.accounts.escrow_account.judge_key;
+        ctx.accounts.escrow_account.judge_key = *ctx.accounts.new_judge.key;
+        // Drop any change the admin has scheduled.
+        ctx.accounts.escrow_account.pending_judge_key = Pubkey::default();
+        ctx.accounts.escrow_account.judge_change_time = 0;
+
+        emit!(JudgeChanged {
+            order_code,
+            old_judge_key,
+            new_judge_key: *ctx.accounts.new_judge.key,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn request_judge_change(ctx: Context<RequestJudgeChange>, order_code: u64,) -> ProgramResult {
+        // Admin schedules a new judge, applied once the timelock has passed.
+        assert_not_double_deposit(&ctx.accounts.escrow_account)?;
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        let clock: Clock = Clock::get().unwrap();
+        let effective_time = clock.unix_timestamp
+            .checked_add(ctx.accounts.config.judge_change_delay)
+            .ok_or(ErrorCode::InvalidTimelock)?;
+        ctx.accounts.escrow_account.pending_judge_key = *ctx.accounts.new_judge.key;
+        ctx.accounts.escrow_account.judge_change_time = effective_time;
+
+        emit!(JudgeChangeRequested {
+            order_code,
+            current_judge_key: ctx.accounts.escrow_account.judge_key,
+            pending_judge_key: *ctx.accounts.new_judge.key,
+            effective_time,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_judge_change(ctx: Context<ExecuteJudgeChange>, order_code: u64,) -> ProgramResult {
+        assert_not_frozen(&ctx.accounts.escrow_account)?;
+        // Verify timelock.
+        let clock: Clock = Clock::get().unwrap();
+        if ctx.accounts.escrow_account.judge_change_time > clock.unix_timestamp {
+            return Err(ErrorCode::InJudgeChangeTimelock.into())
+        }
+
+        let old_judge_key = ctx.accounts.escrow_account.judge_key;
+        let new_judge_key = ctx.accounts.escrow_account.pending_judge_key;
+        ctx.accounts.escrow_account.judge_key = new_judge_key;
+        ctx.accounts.escrow_account.pending_judge_key = Pubkey::default();
+        ctx.accounts.escrow_account.judge_change_time = 0;
+
+        emit!(JudgeChanged {
+            order_code,
+            old_judge_key,
+            new_judge_key,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
 #[instruction(vault_account_bump: u8, amount: u64, order_code: u64, trial_day: u16)]
 pub struct Initialize<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    // Pays the vault and escrow rent, and gets it back when the escrow is closed.
+    #[account(mut, signer)]
+    pub payer: AccountInfo<'info>,
+    pub seller: AccountInfo<'info>,
+    pub judge: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        seeds = [format!("{}{}", "token-seed-".to_string(), order_code.to_string()).as_bytes().as_ref()],
+        bump = vault_account_bump,
+        payer = payer,
+        token::mint = mint,
+        token::authority = buyer,
+        constraint = amount > 0,
+    )]
+    pub vault_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = buyer_deposit_token_account.amount >= amount
+    )]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(zero)]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Checkout<'info> {
+    #[account(mut, signer)]
+    pub buyer: AccountInfo<'info>,
+    pub judge: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    pub mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(constraint = system_program.key == &system_program::ID)]
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    // Hands every new vault to its authority, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct Cancel<'info> {
+    #[account(mut, signer)]
+    pub buyer: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 0,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelCart<'info> {
+    #[account(mut, signer)]
+    pub buyer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    // Signs for every vault authority of the cart, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64, amount: u64)]
+pub struct CancelPartial<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 0,
+        constraint = amount > 0,
+        constraint = escrow_account.amount > amount,
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64, amount: u64)]
+pub struct ChargeMore<'info> {
+    #[account(mut, signer)]
+    pub buyer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut, constraint = buyer_deposit_token_account.amount >= amount)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    pub seller: AccountInfo<'info>,
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = amount > 0
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct Shipping<'info> {
+    pub buyer: AccountInfo<'info>,
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    // Ships, still the original seller after `transfer_receivable`.
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.fulfiller_key == *seller.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 0
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct Delivered<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    pub seller: AccountInfo<'info>,
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 1
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct Refund<'info> {
+    pub buyer: AccountInfo<'info>,
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status > 0
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64, amount: u64)]
+pub struct RefundPartial<'info> {
+    pub buyer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status > 0,
+        constraint = amount > 0,
+        constraint = escrow_account.amount > amount
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SettleBatch<'info> {
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    // Signs for every vault authority of the batch, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(line_items_bump: u8, order_code: u64)]
+pub struct SetLineItems<'info> {
+    #[account(mut, signer)]
+    pub buyer: AccountInfo<'info>,
+    #[account(
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 0
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(
+        init,
+        seeds = [format!("{}{}", "line-items-".to_string(), order_code.to_string()).as_bytes().as_ref()],
+        bump = line_items_bump,
+        payer = buyer,
+        space = 8 + LineItems::LEN,
+    )]
+    pub line_items: Box<Account<'info, LineItems>>,
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct ShipItems<'info> {
+    // Ships, still the original seller after `transfer_receivable`.
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    #[account(
+        constraint = escrow_account.fulfiller_key == *seller.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 0
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut, constraint = line_items.escrow_key == *escrow_account.to_account_info().key)]
+    pub line_items: Box<Account<'info, LineItems>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct DeliverItems<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    #[account(
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 0
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut, constraint = line_items.escrow_key == *escrow_account.to_account_info().key)]
+    pub line_items: Box<Account<'info, LineItems>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct SettleItems<'info> {
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    #[account(mut)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 0,
+        constraint = escrow_account.rent_payer == *rent_payer.key
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(mut, constraint = line_items.escrow_key == *escrow_account.to_account_info().key)]
+    pub line_items: Box<Account<'info, LineItems>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct CancelItems<'info> {
+    #[account(mut, signer)]
+    pub buyer: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 0,
+        constraint = escrow_account.rent_payer == *rent_payer.key
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(mut, constraint = line_items.escrow_key == *escrow_account.to_account_info().key)]
+    pub line_items: Box<Account<'info, LineItems>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct SetVesting<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status < 2
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct WithdrawVested<'info> {
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 2
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct Dispute<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 2
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RelayDelivered<'info> {
+    #[account(signer)]
+    pub relayer: AccountInfo<'info>,
+    #[account(mut, constraint = escrow_account.status == 1)]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(constraint = *instructions.key == sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RelayCancel<'info> {
+    #[account(signer)]
+    pub relayer: AccountInfo<'info>,
+    pub buyer: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.status == 0,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(constraint = *instructions.key == sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RelayChargeMore<'info> {
+    #[account(signer)]
+    pub relayer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    pub intent_delegate: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    // Checked against `token-seed-{order_code}` by relay_charge_more, nobody signs for it here.
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    #[account(constraint = *instructions.key == sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    // Signs for the intent delegate, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct TransferReceivable<'info> {
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    pub new_seller: AccountInfo<'info>,
+    // Has to hold the escrow's mint, or the escrow could never be settled.
+    #[account(constraint = new_seller_receive_token_account.mint == seller_receive_token_account.mint)]
+    pub new_seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct RevealAndClaim<'info> {
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.fulfiller_key == *seller.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 1,
+        constraint = escrow_account.secret_hash != [0; 32]
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct ReclaimUnrevealed<'info> {
+    pub buyer: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.status == 1,
+        constraint = escrow_account.secret_hash != [0; 32],
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    // Signs for the vault authority with no party signing, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    campaign_bump: u8,
+    campaign_vault_bump: u8,
+    campaign_id: u64,
+    price: u64,
+    threshold: u32,
+    deadline: i64,
+    conversion_deadline: i64,
+    trial_day: u16
+)]
+pub struct CreateCampaign<'info> {
+    // Pays the campaign and vault rent, and gets it back with close_campaign.
+    #[account(mut, signer)]
+    pub seller: AccountInfo<'info>,
+    pub judge: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    pub mint: Account<'info, Mint>,
+    #[account(constraint = seller_receive_token_account.mint == *mint.to_account_info().key)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        seeds = [format!("{}{}", "campaign-".to_string(), campaign_id.to_string()).as_bytes().as_ref()],
+        bump = campaign_bump,
+        payer = seller,
+        space = 8 + Campaign::LEN,
+    )]
+    pub campaign: Box<Account<'info, Campaign>>,
+    #[account(
+        init,
+        seeds = [format!("{}{}", "campaign-vault-".to_string(), campaign_id.to_string()).as_bytes().as_ref()],
+        bump = campaign_vault_bump,
+        payer = seller,
+        token::mint = mint,
+        token::authority = seller,
+    )]
+    pub campaign_vault: Account<'info, TokenAccount>,
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(deposit_bump: u8, campaign_id: u64)]
+pub struct JoinCampaign<'info> {
+    // Pays the deposit record rent, and gets it back once converted or refunded.
+    #[account(mut, signer)]
+    pub buyer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut, constraint = campaign.campaign_id == campaign_id)]
+    pub campaign: Box<Account<'info, Campaign>>,
+    // The campaign PDA owns only its vault.
+    #[account(mut, constraint = campaign_vault.owner == *campaign.to_account_info().key)]
+    pub campaign_vault: Account<'info, TokenAccount>,
+    #[account(mut, constraint = buyer_deposit_token_account.amount >= campaign.price)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        seeds = [
+            format!("{}{}", "campaign-deposit-".to_string(), campaign_id.to_string()).as_bytes().as_ref(),
+            buyer.key.as_ref(),
+        ],
+        bump = deposit_bump,
+        payer = buyer,
+        space = 8 + CampaignDeposit::LEN,
+    )]
+    pub deposit: Box<Account<'info, CampaignDeposit>>,
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_account_bump: u8, campaign_id: u64, order_code: u64)]
+pub struct ConvertCampaignDeposit<'info> {
+    // Pays the vault and escrow rent, and gets it back when the escrow is closed.
+    #[account(mut, signer)]
+    pub payer: AccountInfo<'info>,
+    // Signs before the conversion deadline, see convert_campaign_deposit.
+    pub seller: AccountInfo<'info>,
+    #[account(mut)]
+    pub buyer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(
+        mut,
+        constraint = campaign.campaign_id == campaign_id,
+        constraint = campaign.seller_key == *seller.key,
+        constraint = campaign.mint == *mint.to_account_info().key
+    )]
+    pub campaign: Box<Account<'info, Campaign>>,
+    #[account(mut, constraint = campaign_vault.owner == *campaign.to_account_info().key)]
+    pub campaign_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = deposit.campaign_key == *campaign.to_account_info().key,
+        constraint = deposit.buyer_key == *buyer.key,
+        close = buyer
+    )]
+    pub deposit: Box<Account<'info, CampaignDeposit>>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        seeds = [format!("{}{}", "token-seed-".to_string(), order_code.to_string()).as_bytes().as_ref()],
+        bump = vault_account_bump,
+        payer = payer,
+        token::mint = mint,
+        token::authority = payer,
+    )]
+    pub vault_account: Account<'info, TokenAccount>,
+    #[account(zero)]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    // Signs for the campaign PDA, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(campaign_id: u64)]
+pub struct RefundCampaignDeposit<'info> {
+    #[account(mut, signer)]
+    pub buyer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut, constraint = campaign.campaign_id == campaign_id)]
+    pub campaign: Box<Account<'info, Campaign>>,
+    #[account(mut, constraint = campaign_vault.owner == *campaign.to_account_info().key)]
+    pub campaign_vault: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = deposit.campaign_key == *campaign.to_account_info().key,
+        constraint = deposit.buyer_key == *buyer.key,
+        constraint = deposit.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        close = buyer
+    )]
+    pub deposit: Box<Account<'info, CampaignDeposit>>,
+    // Signs for the campaign PDA, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(campaign_id: u64)]
+pub struct CloseCampaign<'info> {
+    #[account(mut, signer)]
+    pub seller: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = campaign.campaign_id == campaign_id,
+        constraint = campaign.seller_key == *seller.key,
+        close = seller
+    )]
+    pub campaign: Box<Account<'info, Campaign>>,
+    #[account(mut, constraint = campaign_vault.owner == *campaign.to_account_info().key)]
+    pub campaign_vault: Account<'info, TokenAccount>,
+    // Signs for the campaign PDA, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_account_bump: u8, amount: u64, order_code: u64)]
+pub struct InitializeSellerFunded<'info> {
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    // Pays the vault and escrow rent, and gets it back when the escrow is closed.
+    #[account(mut, signer)]
+    pub payer: AccountInfo<'info>,
+    pub buyer: AccountInfo<'info>,
+    pub judge: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        seeds = [format!("{}{}", "token-seed-".to_string(), order_code.to_string()).as_bytes().as_ref()],
+        bump = vault_account_bump,
+        payer = payer,
+        token::mint = mint,
+        token::authority = seller,
+        constraint = amount > 0,
+    )]
+    pub vault_account: Account<'info, TokenAccount>,
+    #[account(constraint = buyer_receive_token_account.mint == *mint.to_account_info().key)]
+    pub buyer_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = seller_deposit_token_account.amount >= amount
+    )]
+    pub seller_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(zero)]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct MarkPaid<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.seller_funded,
+        constraint = escrow_account.status == 0
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct Release<'info> {
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_receive_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_receive_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.seller_funded,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct ReclaimUnpaid<'info> {
+    pub seller: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub seller_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.seller_funded,
+        constraint = escrow_account.status == 0,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    // Signs for the vault authority with no party signing, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_account_bump: u8, price: u64, order_code: u64)]
+pub struct InitializeDoubleDeposit<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    // Pays the vault and escrow rent, and gets it back when the escrow is closed.
+    #[account(mut, signer)]
+    pub payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        seeds = [format!("{}{}", "token-seed-".to_string(), order_code.to_string()).as_bytes().as_ref()],
+        bump = vault_account_bump,
+        payer = payer,
+        token::mint = mint,
+        token::authority = buyer,
+        constraint = price > 0,
+    )]
+    pub vault_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    // Locks the seller's collateral, and gets the payout back.
+    #[account(mut)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(zero)]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: AccountInfo<'info>,
+}
+
+// `settle` and `mutual_cancel`, both signed by the buyer and the seller.
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct MutualSettlement<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.double_deposit,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct ExpireLongStop<'info> {
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.double_deposit,
+        constraint = escrow_account.long_stop > 0,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    // Signs for the vault authority with no party signing, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_account_bump: u8, amount: u64, order_code: u64)]
+pub struct InitializeRental<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    // Pays the vault and escrow rent, and gets it back when the escrow is closed.
     #[account(mut, signer)]
+    pub payer: AccountInfo<'info>,
+    pub seller: AccountInfo<'info>,
+    pub judge: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        seeds = [format!("{}{}", "token-seed-".to_string(), order_code.to_string()).as_bytes().as_ref()],
+        bump = vault_account_bump,
+        payer = payer,
+        token::mint = mint,
+        token::authority = buyer,
+        constraint = amount > 0,
+    )]
+    pub vault_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = buyer_deposit_token_account.amount >= amount
+    )]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(constraint = seller_receive_token_account.mint == *mint.to_account_info().key)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(zero)]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub system_program: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct ClaimDamages<'info> {
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.rental
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct AcceptDamages<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.rental,
+        constraint = escrow_account.damages_claim > 0,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct DisputeDamages<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.rental,
+        constraint = escrow_account.damages_claim > 0
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct SettleDamages<'info> {
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.rental,
+        constraint = escrow_account.damages_claim > 0,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    // Signs for the vault authority with no party signing, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct ReturnDeposit<'info> {
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.rental,
+        constraint = escrow_account.damages_claim == 0,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    // Signs for the vault authority with no party signing, so it has to be the real token program.
+    #[account(constraint = token_program.key == &spl_token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct RequestSurcharge<'info> {
+    // Ships and finds the extra costs, still the original seller after `transfer_receivable`.
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.fulfiller_key == *seller.key,
+        constraint = escrow_account.order_code == order_code
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct RejectSurcharge<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.order_code == order_code
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct Exchange<'info> {
+    pub buyer: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    #[account(mut)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    // A tokenized escrow pays the holder of its receivable token instead, see receivable_payee.
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.receivable_mint != Pubkey::default()
+            || escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.receivable_mint != Pubkey::default() || escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.status == 2,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64, status: u8)]
+pub struct Adjudge<'info> {
+    #[account(signer)]
+    pub judge: AccountInfo<'info>,
+    pub buyer: AccountInfo<'info>,
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    pub seller: AccountInfo<'info>,
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.judge_key == *judge.key,
+        constraint = escrow_account.order_code == order_code
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64, amount: u64)]
+pub struct AdjudgePartial<'info> {
+    #[account(signer)]
+    pub judge: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    pub buyer: AccountInfo<'info>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    pub seller: AccountInfo<'info>,
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.judge_key == *judge.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = amount > 0,
+        constraint = escrow_account.amount > amount
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct AdjudgeForBuyer<'info> {
+    #[account(signer)]
+    pub judge: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
     pub buyer: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    #[account(mut)]
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
     pub seller: AccountInfo<'info>,
-    pub judge: AccountInfo<'info>,
-    pub mint: Account<'info, Mint>,
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
     #[account(
-        init,
-        seeds = [format!("{}{}", "token-seed-".to_string(), order_code.to_string()).as_bytes().as_ref()],
-        bump = vault_account_bump,
-        payer = buyer,
-        token::mint = mint,
-        token::authority = buyer,
-        constraint = amount > 0,
+        mut,
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.judge_key == *judge.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
     )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut)]
     pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct AdjudgeForSeller<'info> {
+    #[account(signer)]
+    pub judge: AccountInfo<'info>,
+    pub config: Account<'info, ProgramConfig>,
+    pub buyer: AccountInfo<'info>,
+    #[account(mut)]
+    pub rent_payer: AccountInfo<'info>,
+    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    pub seller: AccountInfo<'info>,
+    #[account(mut)]
+    pub seller_receive_token_account: Account<'info, TokenAccount>,
+    // A tokenized escrow pays the holder of its receivable token instead, see receivable_payee.
     #[account(
         mut,
-        constraint = buyer_deposit_token_account.amount >= amount
+        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.receivable_mint != Pubkey::default()
+            || escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+        constraint = escrow_account.receivable_mint != Pubkey::default() || escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.judge_key == *judge.key,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.rent_payer == *rent_payer.key,
+        close = rent_payer
     )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    #[account(mut)]
+    pub vault_account: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64, trial_day: u16)]
+pub struct UpdateTrialDay<'info> {
+    #[account(signer)]
+    pub judge: AccountInfo<'info>,
+    pub buyer: AccountInfo<'info>,
     pub buyer_deposit_token_account: Account<'info, TokenAccount>,
+    pub seller: AccountInfo<'info>,
     pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(zero)]
+    #[account(
+    mut,
+    constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
+    constraint = escrow_account.buyer_key == *buyer.key,
+    constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
+    constraint = escrow_account.seller_key == *seller.key,
+    constraint = escrow_account.judge_key == *judge.key,
+    constraint = escrow_account.order_code == order_code
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct SyncVault<'info> {
+    #[account(signer)]
+    pub judge: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.judge_key == *judge.key,
+        constraint = escrow_account.order_code == order_code
+    )]
     pub escrow_account: Box<Account<'info, EscrowAccount>>,
+    pub vault_account: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+#[instruction(config_bump: u8)]
+pub struct InitializeConfig<'info> {
+    #[account(mut, signer)]
+    pub admin: AccountInfo<'info>,
+    pub compliance_authority: AccountInfo<'info>,
+    #[account(
+        init,
+        seeds = [b"config".as_ref()],
+        bump = config_bump,
+        payer = admin,
+        space = 8 + ProgramConfig::LEN,
+    )]
+    pub config: Account<'info, ProgramConfig>,
+    // ProgramData of this program, only its upgrade authority can create the config.
+    pub program_data: AccountInfo<'info>,
     pub system_program: AccountInfo<'info>,
     pub rent: Sysvar<'info, Rent>,
-    pub token_program: AccountInfo<'info>,
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64)]
-pub struct Cancel<'info> {
-    #[account(mut, signer)]
-    pub buyer: AccountInfo<'info>,
-    #[account(mut)]
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    pub vault_authority: AccountInfo<'info>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.order_code == order_code,
-        constraint = escrow_account.status == 0,
-        close = buyer
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    pub token_program: AccountInfo<'info>,
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+    pub new_admin: AccountInfo<'info>,
+    pub compliance_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = config.admin == *admin.key
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(paused: bool)]
+pub struct SetPaused<'info> {
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = config.admin == *admin.key
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64, frozen: bool)]
+pub struct SetFrozen<'info> {
+    #[account(signer)]
+    pub compliance_authority: AccountInfo<'info>,
+    #[account(constraint = config.compliance_authority == *compliance_authority.key)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(
+        mut,
+        constraint = escrow_account.order_code == order_code
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct ChangeJudge<'info> {
+    #[account(signer)]
+    pub buyer: AccountInfo<'info>,
+    #[account(signer)]
+    pub seller: AccountInfo<'info>,
+    pub new_judge: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.buyer_key == *buyer.key,
+        constraint = escrow_account.seller_key == *seller.key,
+        constraint = escrow_account.order_code == order_code
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct RequestJudgeChange<'info> {
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+    #[account(constraint = config.admin == *admin.key)]
+    pub config: Account<'info, ProgramConfig>,
+    pub new_judge: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = escrow_account.order_code == order_code
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_code: u64)]
+pub struct ExecuteJudgeChange<'info> {
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+    #[account(constraint = config.admin == *admin.key)]
+    pub config: Account<'info, ProgramConfig>,
+    #[account(
+        mut,
+        constraint = escrow_account.order_code == order_code,
+        constraint = escrow_account.judge_change_time > 0
+    )]
+    pub escrow_account: Box<Account<'info, EscrowAccount>>,
+}
+
+/// Marketplace wide settings, stored at the `config` PDA.
+/// Only one can exist since `initialize_config` is the only way to create it.
+#[account]
+pub struct ProgramConfig {
+    pub admin: Pubkey,
+    /// Can freeze a single escrow, e.g. on a fraud report.
+    pub compliance_authority: Pubkey,
+    /// Seconds the admin must wait between `request_judge_change` and `execute_judge_change`.
+    pub judge_change_delay: i64,
+    /// Kill switch: blocks new escrows, charge more and every payout.
+    pub paused: bool,
+}
+
+impl ProgramConfig {
+    pub const LEN: usize = 32 + 32 + 8 + 1;
+}
+
+#[account]
+pub struct EscrowAccount {
+    pub buyer_key: Pubkey,
+    pub buyer_deposit_token_account: Pubkey,
+    pub seller_key: Pubkey,
+    pub seller_receive_token_account: Pubkey,
+    pub judge_key: Pubkey,
+    pub amount: u64,
+    pub order_code: u64,
+    /** status
+        0: New, for a seller-funded escrow waiting for the buyer's payment
+        1: Shipping, for a seller-funded escrow marked paid by the buyer
+        2: Delivered
+        3: Closed, only reported as `new_status` in events since the account is closed.
+    */
+    pub status: u8,
+    pub delivery_time: i64,
+    pub trial_day: u16,
+    /// Judge scheduled by the admin, `Pubkey::default()` if none.
+    pub pending_judge_key: Pubkey,
+    /// Time from which `pending_judge_key` can be applied, 0 if none.
+    pub judge_change_time: i64,
+    /// Set by the compliance authority, only the judge can move funds.
+    pub frozen: bool,
+    /// Cart shared with the other escrows of the same `checkout`, 0 if created by `initialize`.
+    pub cart_id: u64,
+    /// Set by `set_vesting`, the seller can `withdraw_vested` during the trial.
+    pub vesting: bool,
+    /// What the seller already took with `withdraw_vested` or `dispute`.
+    pub withdrawn: u64,
+    /// Time of the buyer's `dispute`, 0 if none. Only the judge settles a disputed escrow.
+    pub dispute_time: i64,
+    /// Nonce of the last buyer intent relayed for this escrow.
+    pub intent_nonce: u64,
+    /// Paid the vault and escrow rent at `initialize`, gets it back when the escrow is closed.
+    pub rent_payer: Pubkey,
+    /// Seller that ships the order. `seller_key` is paid, and differs once the receivable
+    /// was sold with `transfer_receivable`.
+    pub fulfiller_key: Pubkey,
+    /// 1-of-1 token minted to the seller by `initialize`, `Pubkey::default()` if none. Whoever
+    /// holds it is paid instead of `seller_key`.
+    pub receivable_mint: Pubkey,
+    /// sha256 of the secret committed by `ship_hash_locked`, zero if not hash locked.
+    pub secret_hash: [u8; 32],
+    /// Until when `reveal_and_claim` is accepted, after which `reclaim_unrevealed` refunds the buyer.
+    pub reveal_deadline: i64,
+    /// Set by `initialize_seller_funded`: the seller locked the tokens and `release`s them to
+    /// the buyer once paid off-chain. The buyer-funded instructions reject it.
+    pub seller_funded: bool,
+    /// Until when the buyer can `mark_paid` a seller-funded escrow, after which
+    /// `reclaim_unpaid` returns it to the seller.
+    pub payment_deadline: i64,
+    /// Set by `initialize_double_deposit`: no judge, `amount` includes both collaterals and only
+    /// `settle` or `mutual_cancel`, signed by both parties, pay it out.
+    pub double_deposit: bool,
+    /// Collateral the buyer locked on top of the price.
+    pub buyer_collateral: u64,
+    /// Collateral the seller locked.
+    pub seller_collateral: u64,
+    /// From when `expire_long_stop` burns or donates a double-deposit escrow, 0 if never.
+    pub long_stop: i64,
+    /// Token account `expire_long_stop` donates to, `Pubkey::default()` to burn.
+    pub long_stop_recipient: Pubkey,
+    /// Set by `initialize_rental`: `delivery_time` is the end of the rental and `trial_day` the
+    /// days after it the seller can `claim_damages`.
+    pub rental: bool,
+    /// Damages claimed by the seller, 0 if none.
+    pub damages_claim: u64,
+    /// Hash of the evidence given with `claim_damages`.
+    pub damages_evidence_hash: [u8; 32],
+    /// Surcharge requested by the seller and waiting for the buyer, 0 if none.
+    pub surcharge_amount: u64,
+    /// Hash of the reason given with `request_surcharge`.
+    pub surcharge_reason_hash: [u8; 32],
+    /// Until when the buyer can `approve_surcharge`, after which it expires.
+    pub surcharge_deadline: i64,
+    /// Until when the buyer can `dispute_damages`, after which anyone can `settle_damages`.
+    pub damages_response_deadline: i64,
+    /// Set by `set_line_items`: the escrow settles item by item and the whole order
+    /// instructions reject it.
+    pub itemized: bool,
+}
+
+impl EscrowAccount {
+    pub const LEN: usize = 32 * 5 + 8 + 8 + 1 + 8 + 2 + 32 + 8 + 1 + 8 + 1 + 8 + 8 + 8 + 32 + 32 + 32 + 32 + 8 + 1 + 8
+        + 1 + 8 + 8 + 8 + 32 + 1 + 8 + 32 + 8 + 32 + 8 + 8 + 1;
+}
+
+/// One seller of a `checkout`. The seller and its receive token account are the matching
+/// accounts group.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CartEntry {
+    pub order_code: u64,
+    pub amount: u64,
+    pub trial_day: u16,
+}
+
+/// Line items of an escrow, at `line-items-{order_code}`. Each item is shipped, delivered,
+/// settled or cancelled on its own while the escrow itself stays New.
+#[account]
+pub struct LineItems {
+    pub escrow_key: Pubkey,
+    pub items: Vec<LineItem>,
+}
+
+impl LineItems {
+    pub const MAX_ITEMS: usize = 10;
+    pub const LEN: usize = 32 + 4 + LineItems::MAX_ITEMS * LineItem::LEN;
+
+    fn all_closed(&self) -> bool {
+        self.items.iter().all(|item| item.status == 3)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LineItem {
+    pub sku_hash: [u8; 32],
+    pub quantity: u32,
+    /// Price of one unit.
+    pub price: u64,
+    /** status
+        0: New
+        1: Shipping
+        2: Delivered, its trial of `escrow.trial_day` days runs from `delivery_time`.
+        3: Closed, settled to the seller or cancelled to the buyer.
+    */
+    pub status: u8,
+    pub delivery_time: i64,
+}
+
+impl LineItem {
+    pub const LEN: usize = 32 + 4 + 8 + 1 + 8;
+
+    pub fn total(&self) -> Result<u64> {
+        u64::from(self.quantity).checked_mul(self.price).ok_or_else(|| ErrorCode::AmountOverflow.into())
+    }
+}
+
+/// A line item as given to `set_line_items`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct NewLineItem {
+    pub sku_hash: [u8; 32],
+    pub quantity: u32,
+    pub price: u64,
+}
+
+/// A buyer action signed off-chain, for a relayer to send when the buyer has no SOL for fees.
+/// The buyer signs `message(escrow_key)` and the relayer puts an Ed25519 program instruction
+/// with that signature right before the `relay_*` instruction.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct Intent {
+    pub action: u8,
+    /// Added to the escrow by `CHARGE_MORE`, 0 otherwise.
+    pub amount: u64,
+    /// Above the escrow's `intent_nonce`, each intent is used once.
+    pub nonce: u64,
+    /// Unix time after which the intent can no longer be relayed.
+    pub expiry: i64,
+}
+
+impl Intent {
+    pub const DELIVERED: u8 = 0;
+    pub const CANCEL: u8 = 1;
+    pub const CHARGE_MORE: u8 = 2;
+    pub const PREFIX: &'static [u8] = b"lambda-escrow intent";
+
+    /// What the buyer signs, bound to one escrow so an intent can not be replayed on another.
+    pub fn message(&self, escrow_key: &Pubkey) -> Vec<u8> {
+        let mut message = Intent::PREFIX.to_vec();
+        message.extend_from_slice(escrow_key.as_ref());
+        message.push(self.action);
+        message.extend_from_slice(&self.amount.to_le_bytes());
+        message.extend_from_slice(&self.nonce.to_le_bytes());
+        message.extend_from_slice(&self.expiry.to_le_bytes());
+        message
+    }
+}
+
+/// Group buy of `create_campaign`, at `campaign-{campaign_id}`. It owns the vault holding the
+/// deposits, `campaign-vault-{campaign_id}`.
+#[account]
+pub struct Campaign {
+    pub campaign_id: u64,
+    pub seller_key: Pubkey,
+    pub seller_receive_token_account: Pubkey,
+    pub judge_key: Pubkey,
+    pub mint: Pubkey,
+    /// Deposit of each buyer, the amount of its escrow once converted.
+    pub price: u64,
+    /// Buyers needed for the deposits to be converted into escrows.
+    pub threshold: u32,
+    /// End of `join_campaign`, from then on a campaign below its threshold is refunded.
+    pub deadline: i64,
+    /// After `deadline`. From then on open deposits are refunded, or converted by anyone.
+    pub conversion_deadline: i64,
+    /// `trial_day` of the converted escrows.
+    pub trial_day: u16,
+    /// Buyers who joined, less those refunded from a campaign that missed its threshold.
+    pub participants: u32,
+    /// Deposits neither converted nor refunded yet.
+    pub open_deposits: u32,
+}
+
+impl Campaign {
+    pub const LEN: usize = 8 + 32 * 4 + 8 + 4 + 8 + 8 + 2 + 4 + 4;
+}
+
+/// One buyer's deposit into a campaign, at the PDA of `campaign-deposit-{campaign_id}` and the buyer.
+#[account]
+pub struct CampaignDeposit {
+    pub campaign_key: Pubkey,
+    pub buyer_key: Pubkey,
+    pub buyer_deposit_token_account: Pubkey,
+    pub amount: u64,
+}
+
+impl CampaignDeposit {
+    pub const LEN: usize = 32 * 3 + 8;
+}
+
+/// Written at `receipt` and the escrow account key when a closing instruction is given the
+/// optional receipt accounts, so settlements stay provable after the escrow is closed.
+#[account]
+pub struct SettlementReceipt {
+    pub order_code: u64,
+    pub escrow_key: Pubkey,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub judge_key: Pubkey,
+    pub buyer_payout: u64,
+    pub seller_payout: u64,
+    /// Tokens the program kept. Always 0: the program charges no fee, the payouts add up to what the vault held.
+    pub fee: u64,
+    /// Lamports of the closed vault and escrow accounts, returned to `rent_payer`.
+    pub rent_refund: u64,
+    pub rent_payer: Pubkey,
+    /** settlement_path
+        0: Cancel
+        1: Exchange
+        2: AdjudgeForBuyer
+        3: AdjudgeForSeller
+        4: ReclaimUnrevealed
+        5: Release
+        6: ReclaimUnpaid
+        7: Settle
+        8: MutualCancel
+        9: ExpireLongStop
+        10: AcceptDamages
+        11: ReturnDeposit
+        12: SettleDamages
+    */
+    pub settlement_path: u8,
+    pub timestamp: i64,
+}
+
+impl SettlementReceipt {
+    pub const LEN: usize = 8 + 32 * 4 + 8 * 4 + 32 + 1 + 8;
+}
+
+// Events. `amount` is what this instruction moved, `escrow_amount` is what is left in the escrow.
+#[event]
+pub struct EscrowCreated {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub judge_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub trial_day: u16,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Charged {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+/// One per `checkout`, after an `EscrowCreated` per escrow. `amount` is the cart total.
+#[event]
+pub struct CartCheckedOut {
+    pub cart_id: u64,
+    pub buyer_key: Pubkey,
+    pub escrow_keys: Vec<Pubkey>,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// One per `cancel_cart`, after a `Cancelled` per escrow. `amount` is what went back to the buyer.
+#[event]
+pub struct CartCancelled {
+    pub cart_id: u64,
+    pub buyer_key: Pubkey,
+    pub escrow_keys: Vec<Pubkey>,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Shipped {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DeliveredEvent {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Refunded {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PartialRefund {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub initiator_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Cancelled {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Settled {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+/// One per `settle_batch`, `settled[i]` tells whether `escrow_keys[i]` was settled or skipped.
+#[event]
+pub struct BatchSettled {
+    pub seller_key: Pubkey,
+    pub escrow_keys: Vec<Pubkey>,
+    pub settled: Vec<bool>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LineItemsSet {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub count: u8,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+// Line item events. `indexes` are the items moved, `old_status` and `new_status` are the escrow's.
+#[event]
+pub struct ItemsShipped {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub indexes: Vec<u8>,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ItemsDelivered {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub indexes: Vec<u8>,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ItemsSettled {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub indexes: Vec<u8>,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ItemsCancelled {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub indexes: Vec<u8>,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VestingSet {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub timestamp: i64,
+}
+
+/// `withdrawn` is what the seller took from the escrow so far, this one included.
+#[event]
+pub struct VestedWithdrawn {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub withdrawn: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+/// `amount` is the vested part paid to the seller, `escrow_amount` what is left to the judge.
+#[event]
+pub struct Disputed {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub judge_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+/// Alongside the DeliveredEvent, Cancelled or Charged event of a `relay_*` instruction.
+#[event]
+pub struct IntentRelayed {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub relayer_key: Pubkey,
+    pub action: u8,
+    pub nonce: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReceivableMinted {
+    pub order_code: u64,
+    pub seller_key: Pubkey,
+    pub receivable_mint: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReceivableTransferred {
+    pub order_code: u64,
+    pub fulfiller_key: Pubkey,
+    pub old_seller_key: Pubkey,
+    pub new_seller_key: Pubkey,
+    pub old_seller_receive_token_account: Pubkey,
+    pub new_seller_receive_token_account: Pubkey,
+    pub escrow_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SecretCommitted {
+    pub order_code: u64,
+    pub secret_hash: [u8; 32],
+    pub reveal_deadline: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SecretRevealed {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub secret: Vec<u8>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CampaignCreated {
+    pub campaign_id: u64,
+    pub seller_key: Pubkey,
+    pub price: u64,
+    pub threshold: u32,
+    pub deadline: i64,
+    pub conversion_deadline: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CampaignJoined {
+    pub campaign_id: u64,
+    pub buyer_key: Pubkey,
+    pub amount: u64,
+    pub participants: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CampaignDepositConverted {
+    pub campaign_id: u64,
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub escrow_key: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CampaignDepositRefunded {
+    pub campaign_id: u64,
+    pub buyer_key: Pubkey,
+    pub amount: u64,
+    pub participants: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CampaignClosed {
+    pub campaign_id: u64,
+    pub seller_key: Pubkey,
+    pub participants: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MarkedPaid {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Released {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Reclaimed {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CollateralLocked {
+    pub order_code: u64,
+    pub buyer_collateral: u64,
+    pub seller_collateral: u64,
+    pub long_stop: i64,
+    pub long_stop_recipient: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MutuallySettled {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub buyer_payout: u64,
+    pub seller_payout: u64,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MutuallyCancelled {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub buyer_payout: u64,
+    pub seller_payout: u64,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LongStopExpired {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    /// Token account the escrow was donated to, `Pubkey::default()` if burned.
+    pub recipient_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DamagesClaimed {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    /// Hash of the seller's off-chain evidence.
+    pub evidence_hash: [u8; 32],
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DamagesAccepted {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub buyer_payout: u64,
+    pub seller_payout: u64,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepositReturned {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SurchargeRequested {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    /// Hash of the seller's off-chain reason.
+    pub reason_hash: [u8; 32],
+    /// Until when the buyer can approve it.
+    pub deadline: i64,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SurchargeRejected {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Adjudged {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub judge_key: Pubkey,
+    pub recipient_key: Pubkey,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TrialUpdated {
+    pub order_code: u64,
+    pub buyer_key: Pubkey,
+    pub seller_key: Pubkey,
+    pub judge_key: Pubkey,
+    pub old_trial_day: u16,
+    pub new_trial_day: u16,
+    pub amount: u64,
+    pub escrow_amount: u64,
+    pub old_status: u8,
+    pub new_status: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VaultSynced {
+    pub order_code: u64,
+    pub judge_key: Pubkey,
+    pub old_amount: u64,
+    pub new_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseUpdated {
+    pub paused: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FreezeUpdated {
+    pub order_code: u64,
+    pub frozen: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct JudgeChangeRequested {
+    pub order_code: u64,
+    pub current_judge_key: Pubkey,
+    pub pending_judge_key: Pubkey,
+    pub effective_time: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct JudgeChanged {
+    pub order_code: u64,
+    pub old_judge_key: Pubkey,
+    pub new_judge_key: Pubkey,
+    pub timestamp: i64,
+}
+
+#[error]
+pub enum ErrorCode {
+    #[msg("The order is still in the trial period.")]
+    InTrialDay,
+    #[msg("The timelock can not be negative.")]
+    InvalidTimelock,
+    #[msg("The judge change is still in its timelock.")]
+    InJudgeChangeTimelock,
+    #[msg("The program is paused.")]
+    ProgramPaused,
+    #[msg("The escrow is frozen.")]
+    EscrowFrozen,
+    #[msg("Expected receipt, payer and system program accounts.")]
+    InvalidReceiptAccounts,
+    #[msg("The amount overflows.")]
+    AmountOverflow,
+    #[msg("The vault balance does not match the escrow amount.")]
+    VaultMismatch,
+    #[msg("Expected (escrow, vault, vault authority, rent payer) account groups.")]
+    InvalidBatchAccounts,
+    #[msg("The cart id can not be 0.")]
+    InvalidCartId,
+    #[msg("A cart entry needs a positive amount.")]
+    InvalidCartEntry,
+    #[msg("The cart accounts do not match its entries or escrows.")]
+    InvalidCartAccounts,
+    #[msg("An escrow of the cart is already shipping.")]
+    CartShipped,
+    #[msg("Expected 1 to 10 line items with a positive quantity and price, worth the escrow amount.")]
+    InvalidLineItems,
+    #[msg("A line item is not in a status this instruction accepts.")]
+    InvalidItemStatus,
+    #[msg("The open line items are not worth the escrow amount.")]
+    LineItemsMismatch,
+    #[msg("The escrow is not vesting.")]
+    NotVesting,
+    #[msg("Nothing vested since the last withdrawal.")]
+    NothingVested,
+    #[msg("The escrow is disputed, only the judge can settle it.")]
+    EscrowDisputed,
+    #[msg("The trial is over, the escrow can no longer be disputed.")]
+    TrialEnded,
+    #[msg("The intent does not match this instruction.")]
+    InvalidIntent,
+    #[msg("The intent expired.")]
+    IntentExpired,
+    #[msg("The intent nonce was already used.")]
+    IntentNonceUsed,
+    #[msg("Expected the buyer's Ed25519 intent signature in the instruction before this one.")]
+    MissingIntentSignature,
+    #[msg("Expected the receivable mint and token account PDAs of the order code.")]
+    InvalidReceivableAccounts,
+    #[msg("The payee does not hold the escrow's receivable token.")]
+    ReceivableNotHeld,
+    #[msg("The escrow is tokenized, its payout belongs to the receivable token holder.")]
+    ReceivableTokenized,
+    #[msg("Expected a non-zero secret hash and a reveal deadline in the future.")]
+    InvalidHashLock,
+    #[msg("The secret does not match the committed hash.")]
+    SecretMismatch,
+    #[msg("The reveal deadline passed.")]
+    RevealDeadlinePassed,
+    #[msg("The reveal deadline has not passed yet.")]
+    RevealDeadlineNotPassed,
+    #[msg("Expected a price, a threshold, a deadline in the future and a conversion deadline after it.")]
+    InvalidCampaign,
+    #[msg("The campaign deadline passed.")]
+    CampaignEnded,
+    #[msg("The campaign has fewer buyers than its threshold.")]
+    CampaignThresholdNotMet,
+    #[msg("The campaign is still open or met its threshold, and its conversion deadline has not passed.")]
+    CampaignNotFailed,
+    #[msg("The campaign is still open or has deposits left.")]
+    CampaignNotClosable,
+    #[msg("The payment window has to be positive.")]
+    InvalidPaymentWindow,
+    #[msg("The payment deadline passed.")]
+    PaymentDeadlinePassed,
+    #[msg("The payment deadline has not passed yet.")]
+    PaymentDeadlineNotPassed,
+    #[msg("The escrow is seller-funded, it settles with mark_paid and release.")]
+    SellerFunded,
+    #[msg("Both parties have to lock a positive collateral.")]
+    InvalidCollateral,
+    #[msg("Expected no long stop, or one in the future donating to a token account of the mint.")]
+    InvalidLongStop,
+    #[msg("The long stop has not been reached yet.")]
+    LongStopNotReached,
+    #[msg("The escrow is double-deposit, it has no judge and settles with settle or mutual_cancel.")]
+    DoubleDeposit,
+    #[msg("The rental has to end in the future, with a claim window of at least a day.")]
+    InvalidRentalPeriod,
+    #[msg("Damages can only be claimed once the rental has ended.")]
+    RentalNotEnded,
+    #[msg("The claim window is over.")]
+    ClaimWindowClosed,
+    #[msg("The seller can still claim damages.")]
+    ClaimWindowOpen,
+    #[msg("Damages have to be positive and at most the deposit.")]
+    InvalidDamages,
+    #[msg("Damages were already claimed.")]
+    DamagesAlreadyClaimed,
+    #[msg("The escrow is a rental deposit, it settles with claim_damages or return_deposit.")]
+    RentalEscrow,
+    #[msg("A surcharge has to be positive, with a deadline in the future.")]
+    InvalidSurcharge,
+    #[msg("A surcharge is already waiting for the buyer.")]
+    SurchargePending,
+    #[msg("No surcharge is waiting for the buyer.")]
+    NoPendingSurcharge,
+    #[msg("The surcharge has expired.")]
+    SurchargeExpired,
+    #[msg("The amount differs from the pending surcharge.")]
+    SurchargeMismatch,
+    #[msg("Only the program upgrade authority can initialize the config.")]
+    NotUpgradeAuthority,
+    #[msg("A vesting escrow cannot be topped up once delivered.")]
+    VestingStarted,
+    #[msg("Only the seller can convert a deposit before the conversion deadline.")]
+    ConversionDeadlineNotPassed,
+    #[msg("The buyer can still accept or dispute the damages.")]
+    ResponseWindowOpen,
+    #[msg("The buyer can no longer dispute the damages.")]
+    ResponseWindowClosed,
+    #[msg("The escrow is split into line items, use the item instructions.")]
+    EscrowItemized,
+}
+
+fn assert_not_paused(config: &ProgramConfig) -> ProgramResult {
+    if config.paused {
+        return Err(ErrorCode::ProgramPaused.into())
+    }
+    Ok(())
+}
+
+// `program_data` must be the ProgramData account of this program, upgradeable by `authority`.
+fn assert_upgrade_authority(program_id: &Pubkey, program_data: &AccountInfo, authority: &Pubkey) -> ProgramResult {
+    let (program_data_address, _) = Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::ID);
+    if program_data.key != &program_data_address || program_data.owner != &bpf_loader_upgradeable::ID {
+        return Err(ErrorCode::NotUpgradeAuthority.into())
+    }
+    let data = program_data.try_borrow_data()?;
+    match ProgramData::try_deserialize(&mut &data[..]) {
+        Ok(ProgramData { upgrade_authority_address: Some(upgrade_authority), .. }) if upgrade_authority == *authority => {
+            Ok(())
+        }
+        _ => Err(ErrorCode::NotUpgradeAuthority.into()),
+    }
+}
+
+fn assert_not_frozen(escrow_account: &EscrowAccount) -> ProgramResult {
+    if escrow_account.frozen {
+        return Err(ErrorCode::EscrowFrozen.into())
+    }
+    Ok(())
+}
+
+// The buyer-funded lifecycle, from `cancel` to `exchange`, is for escrows only the buyer funded.
+fn assert_buyer_funded(escrow_account: &EscrowAccount) -> ProgramResult {
+    if escrow_account.seller_funded {
+        return Err(ErrorCode::SellerFunded.into())
+    }
+    // A rental deposit is buyer-funded too, but never paid to the seller by the purchase flow.
+    if escrow_account.rental {
+        return Err(ErrorCode::RentalEscrow.into())
+    }
+    assert_not_double_deposit(escrow_account)
+}
+
+fn assert_not_double_deposit(escrow_account: &EscrowAccount) -> ProgramResult {
+    if escrow_account.double_deposit {
+        return Err(ErrorCode::DoubleDeposit.into())
+    }
+    Ok(())
+}
+
+// Pays a double-deposit escrow out to both parties and closes its vault, the payouts have to
+// empty it. Returns the rent refund for the settlement receipt.
+fn pay_out_double_deposit<'info>(
+    accounts: &mut MutualSettlement<'info>,
+    program_id: &Pubkey,
+    buyer_payout: u64,
+    seller_payout: u64,
+) -> Result<u64> {
+    assert_not_paused(&accounts.config)?;
+    assert_not_frozen(&accounts.escrow_account)?;
+    accounts.vault_account.reload()?;
+    if buyer_payout.checked_add(seller_payout) != Some(accounts.vault_account.amount) {
+        return Err(ErrorCode::VaultMismatch.into())
+    }
+    // Lamports the rent payer gets back once the vault and escrow are closed.
+    let rent_refund = accounts.vault_account.to_account_info().lamports()
+        + accounts.escrow_account.to_account_info().lamports();
+
+    // Make Seed
+    let escrow_seed: String = format!("{}{}", "escrow-".to_string(), accounts.escrow_account.order_code.to_string());
+    let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+    let (_vault_authority, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], program_id);
+    let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+    token::transfer(
+        accounts.into_transfer_to_buyer_context().with_signer(&[&authority_seeds[..]]),
+        buyer_payout,
+    )?;
+    token::transfer(
+        accounts.into_transfer_to_seller_context().with_signer(&[&authority_seeds[..]]),
+        seller_payout,
+    )?;
+
+    // Close vault account
+    token::close_account(
+        accounts.into_close_context().with_signer(&[&authority_seeds[..]]),
+    )?;
+
+    Ok(rent_refund)
+}
+
+// An itemized escrow only moves through the item instructions, a whole order change would leave
+// the line items behind.
+fn assert_not_itemized(escrow_account: &EscrowAccount) -> ProgramResult {
+    if escrow_account.itemized {
+        return Err(ErrorCode::EscrowItemized.into())
+    }
+    Ok(())
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64, amount: u64)]
-pub struct CancelPartial<'info> {
-    #[account(signer)]
-    pub buyer: AccountInfo<'info>,
-    #[account(mut)]
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    pub vault_authority: AccountInfo<'info>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.order_code == order_code,
-        constraint = escrow_account.status == 0,
-        constraint = amount > 0,
-        constraint = escrow_account.amount > amount,
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    pub token_program: AccountInfo<'info>,
+// Top-ups during the trial of a vesting escrow would vest retroactively, see lambda_escrow_model::vesting_started.
+fn assert_vesting_not_started(escrow_account: &EscrowAccount) -> ProgramResult {
+    if lambda_escrow_model::vesting_started(escrow_account.status, escrow_account.vesting) {
+        return Err(ErrorCode::VestingStarted.into())
+    }
+    Ok(())
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64, amount: u64)]
-pub struct ChargeMore<'info> {
-    #[account(mut, signer)]
-    pub buyer: AccountInfo<'info>,
-    #[account(mut, constraint = buyer_deposit_token_account.amount >= amount)]
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    pub seller: AccountInfo<'info>,
-    pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
-        constraint = escrow_account.seller_key == *seller.key,
-        constraint = escrow_account.order_code == order_code,
-        constraint = amount > 0
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    pub vault_authority: AccountInfo<'info>,
-    pub token_program: AccountInfo<'info>,
+fn assert_not_tokenized(escrow_account: &EscrowAccount) -> ProgramResult {
+    if escrow_account.receivable_mint != Pubkey::default() {
+        return Err(ErrorCode::ReceivableTokenized.into())
+    }
+    Ok(())
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64)]
-pub struct Shipping<'info> {
-    pub buyer: AccountInfo<'info>,
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    #[account(signer)]
-    pub seller: AccountInfo<'info>,
-    pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
-        constraint = escrow_account.seller_key == *seller.key,
-        constraint = escrow_account.order_code == order_code,
-        constraint = escrow_account.status == 0
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    pub token_program: AccountInfo<'info>,
+// Mints the 1-of-1 receivable token of a new escrow to the seller, when initialize is given
+// [receivable_mint, receivable_token_account] as remaining accounts. Both are PDAs of the order
+// code paid by the rent payer, and the mint is its own authority until it drops it after minting.
+fn mint_receivable<'info>(
+    accounts: &Initialize<'info>,
+    program_id: &Pubkey,
+    remaining_accounts: &[AccountInfo<'info>],
+    order_code: u64,
+) -> Result<Pubkey> {
+    if remaining_accounts.len() != 2 || *accounts.token_program.key != spl_token::ID {
+        return Err(ErrorCode::InvalidReceivableAccounts.into())
+    }
+    let (mint_info, token_info) = (&remaining_accounts[0], &remaining_accounts[1]);
+
+    // Make Seed
+    let mint_seed: String = format!("{}{}", "receivable-mint-".to_string(), order_code.to_string());
+    let mint_pda_seed: &[u8] = mint_seed.as_bytes();
+    let (mint_key, mint_bump) = Pubkey::find_program_address(&[mint_pda_seed], program_id);
+    let token_seed: String = format!("{}{}", "receivable-".to_string(), order_code.to_string());
+    let token_pda_seed: &[u8] = token_seed.as_bytes();
+    let (token_key, token_bump) = Pubkey::find_program_address(&[token_pda_seed], program_id);
+    if *mint_info.key != mint_key || *token_info.key != token_key {
+        return Err(ErrorCode::InvalidReceivableAccounts.into())
+    }
+    let mint_seeds = &[&mint_pda_seed[..], &[mint_bump]];
+    let token_seeds = &[&token_pda_seed[..], &[token_bump]];
+
+    // Create receivable mint
+    let space = spl_token::state::Mint::LEN;
+    invoke_signed(
+        &system_instruction::create_account(
+            accounts.payer.key,
+            mint_info.key,
+            accounts.rent.minimum_balance(space),
+            space as u64,
+            &spl_token::ID,
+        ),
+        &[accounts.payer.clone(), mint_info.clone(), accounts.system_program.clone()],
+        &[&mint_seeds[..]],
+    )?;
+    token::initialize_mint(
+        CpiContext::new(
+            accounts.token_program.clone(),
+            InitializeMint { mint: mint_info.clone(), rent: accounts.rent.to_account_info() },
+        ),
+        0,
+        &mint_key,
+        None,
+    )?;
+
+    // Create the seller's receivable token account
+    let space = spl_token::state::Account::LEN;
+    invoke_signed(
+        &system_instruction::create_account(
+            accounts.payer.key,
+            token_info.key,
+            accounts.rent.minimum_balance(space),
+            space as u64,
+            &spl_token::ID,
+        ),
+        &[accounts.payer.clone(), token_info.clone(), accounts.system_program.clone()],
+        &[&token_seeds[..]],
+    )?;
+    token::initialize_account(CpiContext::new(
+        accounts.token_program.clone(),
+        InitializeAccount {
+            account: token_info.clone(),
+            mint: mint_info.clone(),
+            authority: accounts.seller.clone(),
+            rent: accounts.rent.to_account_info(),
+        },
+    ))?;
+
+    // Mint the only token and drop the mint authority.
+    token::mint_to(
+        CpiContext::new(
+            accounts.token_program.clone(),
+            MintTo { mint: mint_info.clone(), to: token_info.clone(), authority: mint_info.clone() },
+        )
+        .with_signer(&[&mint_seeds[..]]),
+        1,
+    )?;
+    token::set_authority(
+        CpiContext::new(
+            accounts.token_program.clone(),
+            SetAuthority { account_or_mint: mint_info.clone(), current_authority: mint_info.clone() },
+        )
+        .with_signer(&[&mint_seeds[..]]),
+        AuthorityType::MintTokens,
+        None,
+    )?;
+
+    Ok(mint_key)
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64)]
-pub struct Delivered<'info> {
-    #[account(signer)]
-    pub buyer: AccountInfo<'info>,
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    pub seller: AccountInfo<'info>,
-    pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
-        constraint = escrow_account.seller_key == *seller.key,
-        constraint = escrow_account.order_code == order_code,
-        constraint = escrow_account.status == 1
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    pub token_program: AccountInfo<'info>,
+// Who a seller payout goes to. A tokenized escrow pays whoever holds its receivable token: that
+// holder's token account of `receivable_mint` comes first in remaining_accounts, and the payout
+// has to go to a token account of the same owner. Also returns the remaining accounts left for a
+// settlement receipt.
+fn receivable_payee<'a, 'info>(
+    escrow_account: &EscrowAccount,
+    seller_receive_token_account: &TokenAccount,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<(Pubkey, &'a [AccountInfo<'info>])> {
+    if escrow_account.receivable_mint == Pubkey::default() {
+        return Ok((escrow_account.seller_key, remaining_accounts))
+    }
+    let holder_info = remaining_accounts.first().ok_or(ErrorCode::ReceivableNotHeld)?;
+    let holder: Account<'info, TokenAccount> = Account::try_from(holder_info)?;
+    if holder.mint != escrow_account.receivable_mint
+        || holder.amount != 1
+        || seller_receive_token_account.owner != holder.owner
+    {
+        return Err(ErrorCode::ReceivableNotHeld.into())
+    }
+    Ok((holder.owner, &remaining_accounts[1..]))
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64)]
-pub struct Refund<'info> {
-    pub buyer: AccountInfo<'info>,
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    #[account(signer)]
-    pub seller: AccountInfo<'info>,
-    pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
-        constraint = escrow_account.seller_key == *seller.key,
-        constraint = escrow_account.order_code == order_code,
-        constraint = escrow_account.status > 0
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    pub token_program: AccountInfo<'info>,
+// Transfers through the program keep the vault at exactly `amount`, anything else arrived
+// or left some other way. The vault can only be closed empty, so a donation holds the escrow up
+// until its judge takes it in with sync_vault, which still works while the escrow is frozen or a
+// judge change is pending. Double-deposit escrows have no judge and skip this, see vault_surplus.
+fn check_vault<'info>(vault_account: &mut Account<'info, TokenAccount>, escrow_account: &EscrowAccount) -> ProgramResult {
+    vault_account.reload()?;
+    if vault_account.amount != escrow_account.amount {
+        return Err(ErrorCode::VaultMismatch.into())
+    }
+    Ok(())
+}
+
+// Tokens in the vault on top of the escrow amount. A double-deposit escrow has no judge to
+// sync_vault, so its payouts take such a donation along instead.
+fn vault_surplus<'info>(
+    vault_account: &mut Account<'info, TokenAccount>,
+    escrow_account: &EscrowAccount,
+) -> Result<u64> {
+    vault_account.reload()?;
+    vault_account.amount.checked_sub(escrow_account.amount).ok_or_else(|| ErrorCode::VaultMismatch.into())
+}
+
+// Settles one settle_batch group like exchange does. A group that is not a matured escrow of
+// this seller, with its own vault, authority and rent payer, is skipped and left untouched, and
+// so is a tokenized escrow, its holder settles it with exchange.
+fn settle_batch_group<'info>(
+    accounts: &SettleBatch<'info>,
+    program_id: &Pubkey,
+    group: &[AccountInfo<'info>],
+    now: i64,
+) -> Result<bool> {
+    let (escrow_info, vault_info, vault_authority, rent_payer) = (&group[0], &group[1], &group[2], &group[3]);
+    if !escrow_info.is_writable || !vault_info.is_writable || !rent_payer.is_writable {
+        return Ok(false)
+    }
+    let escrow_account: Account<'info, EscrowAccount> = match Account::try_from(escrow_info) {
+        Ok(escrow_account) => escrow_account,
+        Err(_) => return Ok(false),
+    };
+    let vault_account: Account<'info, TokenAccount> = match Account::try_from(vault_info) {
+        Ok(vault_account) => vault_account,
+        Err(_) => return Ok(false),
+    };
+    if escrow_account.seller_key != *accounts.seller.key
+        || escrow_account.seller_receive_token_account != *accounts.seller_receive_token_account.to_account_info().key
+        || escrow_account.rent_payer != *rent_payer.key
+        || escrow_account.status != 2
+        || escrow_account.frozen
+        || escrow_account.dispute_time > 0
+        || escrow_account.receivable_mint != Pubkey::default()
+        || escrow_account.seller_funded
+        || escrow_account.double_deposit
+        || escrow_account.rental
+        || escrow_account.itemized
+        || lambda_escrow_model::in_trial_day(escrow_account.delivery_time, escrow_account.trial_day, now)
+    {
+        return Ok(false)
+    }
+
+    // Make Seed
+    let vault_seed: String = format!("{}{}", "token-seed-".to_string(), escrow_account.order_code.to_string());
+    let (vault_key, _vault_bump) = Pubkey::find_program_address(&[vault_seed.as_bytes()], program_id);
+    let escrow_seed: String = format!("{}{}", "escrow-".to_string(), escrow_account.order_code.to_string());
+    let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+    let (vault_authority_key, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], program_id);
+    if *vault_info.key != vault_key
+        || *vault_authority.key != vault_authority_key
+        || vault_account.amount != escrow_account.amount
+    {
+        return Ok(false)
+    }
+    let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+    // Transfer token to seller.
+    token::transfer(
+        CpiContext::new(
+            accounts.token_program.clone(),
+            Transfer {
+                from: vault_info.clone(),
+                to: accounts.seller_receive_token_account.to_account_info(),
+                authority: vault_authority.clone(),
+            },
+        )
+        .with_signer(&[&authority_seeds[..]]),
+        escrow_account.amount,
+    )?;
+
+    // Close vault account
+    token::close_account(
+        CpiContext::new(
+            accounts.token_program.clone(),
+            CloseAccount {
+                account: vault_info.clone(),
+                destination: rent_payer.clone(),
+                authority: vault_authority.clone(),
+            },
+        )
+        .with_signer(&[&authority_seeds[..]]),
+    )?;
+
+    emit!(Settled {
+        order_code: escrow_account.order_code,
+        buyer_key: escrow_account.buyer_key,
+        seller_key: escrow_account.seller_key,
+        amount: escrow_account.amount,
+        escrow_amount: 0,
+        old_status: escrow_account.status,
+        new_status: 3,
+        timestamp: now,
+    });
+
+    // Rent goes back to whoever paid it, like `close = rent_payer` on exchange.
+    escrow_account.close(rent_payer.clone())?;
+
+    Ok(true)
+}
+
+// What the seller of a vesting escrow can take at `now`, see lambda_escrow_model::withdrawable.
+fn vested_amount(escrow_account: &EscrowAccount, now: i64) -> Result<u64> {
+    lambda_escrow_model::withdrawable(
+        escrow_account.amount,
+        escrow_account.withdrawn,
+        escrow_account.delivery_time,
+        escrow_account.trial_day,
+        now,
+    )
+    .ok_or_else(|| ErrorCode::AmountOverflow.into())
+}
+
+// Checks a buyer intent for `action` on this escrow. The signature itself is verified by the
+// Ed25519 program, this only makes sure the instruction right before this one is that
+// verification, for the buyer's key and this intent's message.
+fn verify_intent(
+    instructions: &AccountInfo,
+    escrow_key: &Pubkey,
+    escrow_account: &EscrowAccount,
+    intent: &Intent,
+    action: u8,
+    now: i64,
+) -> ProgramResult {
+    assert_buyer_funded(escrow_account)?;
+    if intent.action != action {
+        return Err(ErrorCode::InvalidIntent.into())
+    }
+    if intent.expiry <= now {
+        return Err(ErrorCode::IntentExpired.into())
+    }
+    if intent.nonce <= escrow_account.intent_nonce {
+        return Err(ErrorCode::IntentNonceUsed.into())
+    }
+    let current = sysvar::instructions::load_current_index_checked(instructions)?;
+    if current == 0 {
+        return Err(ErrorCode::MissingIntentSignature.into())
+    }
+    let verification = sysvar::instructions::load_instruction_at_checked(usize::from(current - 1), instructions)?;
+    if verification.program_id != ed25519_program::ID
+        || !ed25519_signs(&verification.data, &escrow_account.buyer_key, &intent.message(escrow_key))
+    {
+        return Err(ErrorCode::MissingIntentSignature.into())
+    }
+    Ok(())
+}
+
+// Ed25519 program data: signature count, padding, then per signature 7 little endian u16s
+// (signature offset and instruction, public key offset and instruction, message offset, size
+// and instruction). Only a single signature with everything in the same instruction is taken.
+fn ed25519_signs(data: &[u8], signer: &Pubkey, message: &[u8]) -> bool {
+    if data.len() < 16 || data[0] != 1 {
+        return false
+    }
+    let field = |index: usize| usize::from(u16::from_le_bytes([data[2 + index * 2], data[3 + index * 2]]));
+    let this_instruction = usize::from(u16::MAX);
+    if field(1) != this_instruction || field(3) != this_instruction || field(6) != this_instruction {
+        return false
+    }
+    let public_key = data.get(field(2)..field(2) + 32);
+    let signed = data.get(field(4)..field(4) + field(5));
+    public_key == Some(signer.as_ref()) && signed == Some(message)
+}
+
+// Line items left open must add up to the escrow amount. The whole order instructions reject an
+// itemized escrow, but the judge can still adjudge_partial it, the items can not be settled after that.
+fn check_line_items(line_items: &LineItems, escrow_account: &EscrowAccount) -> ProgramResult {
+    let mut amount: u64 = 0;
+    for item in line_items.items.iter().filter(|item| item.status != 3) {
+        amount = amount.checked_add(item.total()?).ok_or(ErrorCode::AmountOverflow)?;
+    }
+    if amount != escrow_account.amount {
+        return Err(ErrorCode::LineItemsMismatch.into())
+    }
+    Ok(())
+}
+
+fn expect_item_status(item: &LineItem, status: u8) -> ProgramResult {
+    if item.status != status {
+        return Err(ErrorCode::InvalidItemStatus.into())
+    }
+    Ok(())
+}
+
+// Moves the line items at `indexes` to `status` once `check` accepts them, and returns what
+// they are worth. An index given twice fails `check` the second time.
+fn update_line_items(
+    line_items: &mut LineItems,
+    indexes: &[u8],
+    status: u8,
+    now: i64,
+    check: impl Fn(&LineItem) -> ProgramResult,
+) -> Result<u64> {
+    if indexes.is_empty() {
+        return Err(ErrorCode::InvalidLineItems.into())
+    }
+    let mut amount: u64 = 0;
+    for index in indexes {
+        let item = line_items.items.get_mut(usize::from(*index)).ok_or(ErrorCode::InvalidLineItems)?;
+        check(item)?;
+        item.status = status;
+        if status == 2 {
+            item.delivery_time = now;
+        }
+        amount = amount.checked_add(item.total()?).ok_or(ErrorCode::AmountOverflow)?;
+    }
+    Ok(amount)
+}
+
+// Creates one checkout escrow the way initialize does: the vault at `token-seed-{order_code}`
+// owned by the vault authority, funded with `entry.amount` from the buyer's deposit.
+fn checkout_entry<'info>(
+    accounts: &Checkout<'info>,
+    program_id: &Pubkey,
+    cart_id: u64,
+    entry: &CartEntry,
+    group: &[AccountInfo<'info>],
+    now: i64,
+) -> ProgramResult {
+    let (escrow_info, vault_info, seller, seller_receive_info) = (&group[0], &group[1], &group[2], &group[3]);
+    if entry.amount == 0 {
+        return Err(ErrorCode::InvalidCartEntry.into())
+    }
+    // Same checks as `#[account(zero)]` on initialize.
+    if escrow_info.owner != program_id
+        || !escrow_info.is_writable
+        || escrow_info.data_len() < 8 + EscrowAccount::LEN
+        || !accounts.rent.is_exempt(escrow_info.lamports(), escrow_info.data_len())
+        || escrow_info.try_borrow_data()?[..8] != [0u8; 8]
+    {
+        return Err(ErrorCode::InvalidCartAccounts.into())
+    }
+    let seller_receive_token_account: Account<'info, TokenAccount> = Account::try_from(seller_receive_info)?;
+
+    // Make Seed
+    let vault_seed: String = format!("{}{}", "token-seed-".to_string(), entry.order_code.to_string());
+    let vault_pda_seed: &[u8] = vault_seed.as_bytes();
+    let (vault_key, vault_bump) = Pubkey::find_program_address(&[vault_pda_seed], program_id);
+    if *vault_info.key != vault_key {
+        return Err(ErrorCode::InvalidCartAccounts.into())
+    }
+    let vault_seeds = &[&vault_pda_seed[..], &[vault_bump]];
+    let escrow_seed: String = format!("{}{}", "escrow-".to_string(), entry.order_code.to_string());
+    let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+    let (vault_authority, _vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], program_id);
+
+    // Create vault account, fails if the order code is taken.
+    let space = spl_token::state::Account::LEN;
+    invoke_signed(
+        &system_instruction::create_account(
+            accounts.buyer.key,
+            vault_info.key,
+            accounts.rent.minimum_balance(space),
+            space as u64,
+            &spl_token::ID,
+        ),
+        &[accounts.buyer.clone(), vault_info.clone(), accounts.system_program.clone()],
+        &[&vault_seeds[..]],
+    )?;
+    token::initialize_account(CpiContext::new(
+        accounts.token_program.clone(),
+        InitializeAccount {
+            account: vault_info.clone(),
+            mint: accounts.mint.to_account_info(),
+            authority: accounts.buyer.clone(),
+            rent: accounts.rent.to_account_info(),
+        },
+    ))?;
+    token::set_authority(
+        CpiContext::new(
+            accounts.token_program.clone(),
+            SetAuthority {
+                account_or_mint: vault_info.clone(),
+                current_authority: accounts.buyer.clone(),
+            },
+        ),
+        AuthorityType::AccountOwner,
+        Some(vault_authority),
+    )?;
+
+    // Transfer token to PDA
+    token::transfer(
+        CpiContext::new(
+            accounts.token_program.clone(),
+            Transfer {
+                from: accounts.buyer_deposit_token_account.to_account_info(),
+                to: vault_info.clone(),
+                authority: accounts.buyer.clone(),
+            },
+        ),
+        entry.amount,
+    )?;
+
+    // Init escrow_account
+    let escrow_account = EscrowAccount {
+        buyer_key: *accounts.buyer.key,
+        buyer_deposit_token_account: *accounts.buyer_deposit_token_account.to_account_info().key,
+        seller_key: *seller.key,
+        seller_receive_token_account: *seller_receive_token_account.to_account_info().key,
+        judge_key: *accounts.judge.key,
+        amount: entry.amount,
+        order_code: entry.order_code,
+        status: 0,
+        delivery_time: now,
+        trial_day: entry.trial_day,
+        pending_judge_key: Pubkey::default(),
+        judge_change_time: 0,
+        frozen: false,
+        cart_id,
+        vesting: false,
+        withdrawn: 0,
+        dispute_time: 0,
+        intent_nonce: 0,
+        rent_payer: *accounts.buyer.key,
+        fulfiller_key: *seller.key,
+        receivable_mint: Pubkey::default(),
+        secret_hash: [0; 32],
+        reveal_deadline: 0,
+        seller_funded: false,
+        payment_deadline: 0,
+        double_deposit: false,
+        buyer_collateral: 0,
+        seller_collateral: 0,
+        long_stop: 0,
+        long_stop_recipient: Pubkey::default(),
+        rental: false,
+        damages_claim: 0,
+        damages_evidence_hash: [0; 32],
+        surcharge_amount: 0,
+        surcharge_reason_hash: [0; 32],
+        surcharge_deadline: 0,
+        damages_response_deadline: 0,
+        itemized: false,
+    };
+    let mut vault_account: Account<'info, TokenAccount> = Account::try_from(vault_info)?;
+    check_vault(&mut vault_account, &escrow_account)?;
+    {
+        let mut data = escrow_info.try_borrow_mut_data()?;
+        let mut writer: &mut [u8] = &mut data[..];
+        escrow_account.try_serialize(&mut writer)?;
+    }
+
+    emit!(EscrowCreated {
+        order_code: entry.order_code,
+        buyer_key: escrow_account.buyer_key,
+        seller_key: escrow_account.seller_key,
+        judge_key: escrow_account.judge_key,
+        amount: entry.amount,
+        escrow_amount: entry.amount,
+        trial_day: entry.trial_day,
+        old_status: 0,
+        new_status: 0,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+// Cancels one escrow of the cart like cancel does, returns the amount given back.
+fn cancel_cart_entry<'info>(
+    accounts: &CancelCart<'info>,
+    program_id: &Pubkey,
+    cart_id: u64,
+    group: &[AccountInfo<'info>],
+    now: i64,
+) -> Result<u64> {
+    let (escrow_info, vault_info, vault_authority) = (&group[0], &group[1], &group[2]);
+    let escrow_account: Account<'info, EscrowAccount> = Account::try_from(escrow_info)?;
+    // Checkout has the buyer pay the rent, so it goes back to the buyer here.
+    if escrow_account.cart_id != cart_id
+        || escrow_account.buyer_key != *accounts.buyer.key
+        || escrow_account.rent_payer != *accounts.buyer.key
+        || escrow_account.buyer_deposit_token_account != *accounts.buyer_deposit_token_account.to_account_info().key
+    {
+        return Err(ErrorCode::InvalidCartAccounts.into())
+    }
+    if escrow_account.status != 0 {
+        return Err(ErrorCode::CartShipped.into())
+    }
+    assert_not_frozen(&escrow_account)?;
+    assert_not_itemized(&escrow_account)?;
+
+    // Make Seed
+    let vault_seed: String = format!("{}{}", "token-seed-".to_string(), escrow_account.order_code.to_string());
+    let (vault_key, _vault_bump) = Pubkey::find_program_address(&[vault_seed.as_bytes()], program_id);
+    let escrow_seed: String = format!("{}{}", "escrow-".to_string(), escrow_account.order_code.to_string());
+    let escrow_pda_seed: &[u8] = escrow_seed.as_bytes();
+    let (vault_authority_key, vault_authority_bump) = Pubkey::find_program_address(&[escrow_pda_seed], program_id);
+    if *vault_info.key != vault_key || *vault_authority.key != vault_authority_key {
+        return Err(ErrorCode::InvalidCartAccounts.into())
+    }
+    let mut vault_account: Account<'info, TokenAccount> = Account::try_from(vault_info)?;
+    check_vault(&mut vault_account, &escrow_account)?;
+    let authority_seeds = &[&escrow_pda_seed[..], &[vault_authority_bump]];
+
+    // Transfer token to buyer.
+    token::transfer(
+        CpiContext::new(
+            accounts.token_program.clone(),
+            Transfer {
+                from: vault_info.clone(),
+                to: accounts.buyer_deposit_token_account.to_account_info(),
+                authority: vault_authority.clone(),
+            },
+        )
+        .with_signer(&[&authority_seeds[..]]),
+        escrow_account.amount,
+    )?;
+
+    // Close vault account
+    token::close_account(
+        CpiContext::new(
+            accounts.token_program.clone(),
+            CloseAccount {
+                account: vault_info.clone(),
+                destination: accounts.buyer.clone(),
+                authority: vault_authority.clone(),
+            },
+        )
+        .with_signer(&[&authority_seeds[..]]),
+    )?;
+
+    emit!(Cancelled {
+        order_code: escrow_account.order_code,
+        buyer_key: escrow_account.buyer_key,
+        seller_key: escrow_account.seller_key,
+        amount: escrow_account.amount,
+        escrow_amount: 0,
+        old_status: escrow_account.status,
+        new_status: 3,
+        timestamp: now,
+    });
+
+    let amount = escrow_account.amount;
+    escrow_account.close(accounts.buyer.clone())?;
+
+    Ok(amount)
+}
+
+// Receipts are opt-in: the closing instructions take [receipt, payer, system_program]
+// as remaining accounts, and skip the receipt when none are passed.
+fn write_settlement_receipt<'info>(
+    program_id: &Pubkey,
+    remaining_accounts: &[AccountInfo<'info>],
+    receipt: &SettlementReceipt,
+) -> ProgramResult {
+    if remaining_accounts.is_empty() {
+        return Ok(())
+    }
+    if remaining_accounts.len() < 3 {
+        return Err(ErrorCode::InvalidReceiptAccounts.into())
+    }
+    let receipt_account = &remaining_accounts[0];
+    let payer = &remaining_accounts[1];
+    let system_program = &remaining_accounts[2];
+
+    // Make Seed, by escrow account since order codes can be reused once an escrow is closed.
+    let (receipt_key, receipt_bump) =
+        Pubkey::find_program_address(&[b"receipt".as_ref(), receipt.escrow_key.as_ref()], program_id);
+    if receipt_key != *receipt_account.key || !payer.is_signer {
+        return Err(ErrorCode::InvalidReceiptAccounts.into())
+    }
+    let receipt_seeds = &[b"receipt".as_ref(), receipt.escrow_key.as_ref(), &[receipt_bump]];
+
+    // Create receipt account. Anyone can send lamports to the address beforehand, which would make
+    // create_account fail, so top it up to rent exemption and allocate and assign it instead.
+    let space = 8 + SettlementReceipt::LEN;
+    let required_lamports = Rent::get()?.minimum_balance(space).saturating_sub(receipt_account.lamports());
+    if required_lamports > 0 {
+        invoke_signed(
+            &system_instruction::transfer(payer.key, receipt_account.key, required_lamports),
+            &[payer.clone(), receipt_account.clone(), system_program.clone()],
+            &[],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(receipt_account.key, space as u64),
+        &[receipt_account.clone(), system_program.clone()],
+        &[&receipt_seeds[..]],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(receipt_account.key, program_id),
+        &[receipt_account.clone(), system_program.clone()],
+        &[&receipt_seeds[..]],
+    )?;
+
+    let mut data = receipt_account.try_borrow_mut_data()?;
+    let mut writer: &mut [u8] = &mut data[..];
+    receipt.try_serialize(&mut writer)?;
+
+    Ok(())
+}
+
+impl<'info> Initialize<'info> {
+    fn into_transfer_to_pda_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.buyer_deposit_token_account.to_account_info().clone(),
+            to: self.vault_account.to_account_info().clone(),
+            authority: self.buyer.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_set_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
+        let cpi_accounts = SetAuthority {
+            account_or_mint: self.vault_account.to_account_info().clone(),
+            current_authority: self.buyer.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> InitializeSellerFunded<'info> {
+    fn into_transfer_to_pda_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.seller_deposit_token_account.to_account_info().clone(),
+            to: self.vault_account.to_account_info().clone(),
+            authority: self.seller.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_set_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
+        let cpi_accounts = SetAuthority {
+            account_or_mint: self.vault_account.to_account_info().clone(),
+            current_authority: self.seller.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64, amount: u64)]
-pub struct RefundPartial<'info> {
-    pub buyer: AccountInfo<'info>,
-    #[account(mut)]
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    #[account(signer)]
-    pub seller: AccountInfo<'info>,
-    pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
-        constraint = escrow_account.seller_key == *seller.key,
-        constraint = escrow_account.order_code == order_code,
-        constraint = escrow_account.status > 0,
-        constraint = amount > 0,
-        constraint = escrow_account.amount > amount
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    pub vault_authority: AccountInfo<'info>,
-    pub token_program: AccountInfo<'info>,
+impl<'info> InitializeDoubleDeposit<'info> {
+    fn into_transfer_from_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.buyer_deposit_token_account.to_account_info().clone(),
+            to: self.vault_account.to_account_info().clone(),
+            authority: self.buyer.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_transfer_from_seller_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.seller_receive_token_account.to_account_info().clone(),
+            to: self.vault_account.to_account_info().clone(),
+            authority: self.seller.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_set_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
+        let cpi_accounts = SetAuthority {
+            account_or_mint: self.vault_account.to_account_info().clone(),
+            current_authority: self.buyer.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
-#[derive(Accounts)]
-pub struct Exchange<'info> {
-    #[account(mut)]
-    pub buyer: AccountInfo<'info>,
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    #[account(signer)]
-    pub seller: AccountInfo<'info>,
-    #[account(mut)]
-    pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
-        constraint = escrow_account.seller_key == *seller.key,
-        constraint = escrow_account.status == 2,
-        close = buyer
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    pub vault_authority: AccountInfo<'info>,
-    pub token_program: AccountInfo<'info>,
+impl<'info> InitializeRental<'info> {
+    fn into_transfer_to_pda_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.buyer_deposit_token_account.to_account_info().clone(),
+            to: self.vault_account.to_account_info().clone(),
+            authority: self.buyer.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_set_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
+        let cpi_accounts = SetAuthority {
+            account_or_mint: self.vault_account.to_account_info().clone(),
+            current_authority: self.buyer.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64, status: u8)]
-pub struct Adjudge<'info> {
-    #[account(signer)]
-    pub judge: AccountInfo<'info>,
-    pub buyer: AccountInfo<'info>,
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    pub seller: AccountInfo<'info>,
-    pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
-        constraint = escrow_account.seller_key == *seller.key,
-        constraint = escrow_account.judge_key == *judge.key,
-        constraint = escrow_account.order_code == order_code
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    pub token_program: AccountInfo<'info>,
+impl<'info> CreateCampaign<'info> {
+    fn into_set_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
+        let cpi_accounts = SetAuthority {
+            account_or_mint: self.campaign_vault.to_account_info().clone(),
+            current_authority: self.seller.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64, amount: u64)]
-pub struct AdjudgePartial<'info> {
-    #[account(signer)]
-    pub judge: AccountInfo<'info>,
-    pub buyer: AccountInfo<'info>,
-    #[account(mut)]
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    pub seller: AccountInfo<'info>,
-    pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
-        constraint = escrow_account.seller_key == *seller.key,
-        constraint = escrow_account.order_code == order_code,
-        constraint = amount > 0,
-        constraint = escrow_account.amount > amount
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    pub vault_authority: AccountInfo<'info>,
-    pub token_program: AccountInfo<'info>,
+impl<'info> JoinCampaign<'info> {
+    fn into_transfer_to_vault_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.buyer_deposit_token_account.to_account_info().clone(),
+            to: self.campaign_vault.to_account_info().clone(),
+            authority: self.buyer.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64)]
-pub struct AdjudgeForBuyer<'info> {
-    #[account(signer)]
-    pub judge: AccountInfo<'info>,
-    #[account(mut)]
-    pub buyer: AccountInfo<'info>,
-    #[account(mut)]
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    pub seller: AccountInfo<'info>,
-    pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
-        constraint = escrow_account.seller_key == *seller.key,
-        constraint = escrow_account.order_code == order_code,
-        close = buyer
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    pub vault_authority: AccountInfo<'info>,
-    pub token_program: AccountInfo<'info>,
+impl<'info> ConvertCampaignDeposit<'info> {
+    fn into_set_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
+        let cpi_accounts = SetAuthority {
+            account_or_mint: self.vault_account.to_account_info().clone(),
+            current_authority: self.payer.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_transfer_to_vault_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.campaign_vault.to_account_info().clone(),
+            to: self.vault_account.to_account_info().clone(),
+            authority: self.campaign.to_account_info().clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64)]
-pub struct AdjudgeForSeller<'info> {
-    #[account(signer)]
-    pub judge: AccountInfo<'info>,
-    #[account(mut)]
-    pub buyer: AccountInfo<'info>,
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    pub seller: AccountInfo<'info>,
-    #[account(mut)]
-    pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-        constraint = escrow_account.buyer_key == *buyer.key,
-        constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
-        constraint = escrow_account.seller_key == *seller.key,
-        constraint = escrow_account.order_code == order_code,
-        close = buyer
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    #[account(mut)]
-    pub vault_account: Account<'info, TokenAccount>,
-    pub vault_authority: AccountInfo<'info>,
-    pub token_program: AccountInfo<'info>,
+impl<'info> RefundCampaignDeposit<'info> {
+    fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.campaign_vault.to_account_info().clone(),
+            to: self.buyer_deposit_token_account.to_account_info().clone(),
+            authority: self.campaign.to_account_info().clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
-#[derive(Accounts)]
-#[instruction(order_code: u64, trial_day: u16)]
-pub struct UpdateTrialDay<'info> {
-    #[account(signer)]
-    pub judge: AccountInfo<'info>,
-    pub buyer: AccountInfo<'info>,
-    pub buyer_deposit_token_account: Account<'info, TokenAccount>,
-    pub seller: AccountInfo<'info>,
-    pub seller_receive_token_account: Account<'info, TokenAccount>,
-    #[account(
-    mut,
-    constraint = escrow_account.buyer_deposit_token_account == *buyer_deposit_token_account.to_account_info().key,
-    constraint = escrow_account.buyer_key == *buyer.key,
-    constraint = escrow_account.seller_receive_token_account == *seller_receive_token_account.to_account_info().key,
-    constraint = escrow_account.seller_key == *seller.key,
-    constraint = escrow_account.judge_key == *judge.key,
-    constraint = escrow_account.order_code == order_code
-    )]
-    pub escrow_account: Box<Account<'info, EscrowAccount>>,
-    pub token_program: AccountInfo<'info>,
+impl<'info> CloseCampaign<'info> {
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.campaign_vault.to_account_info().clone(),
+            destination: self.seller.clone(),
+            authority: self.campaign.to_account_info().clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
-#[account]
-pub struct EscrowAccount {
-    pub buyer_key: Pubkey,
-    pub buyer_deposit_token_account: Pubkey,
-    pub seller_key: Pubkey,
-    pub seller_receive_token_account: Pubkey,
-    pub judge_key: Pubkey,
-    pub amount: u64,
-    pub order_code: u64,
-    /** status
-        0: New
-        1: Shipping
-        2: Delivered
-    */
-    pub status: u8,
-    pub delivery_time: i64,
-    pub trial_day: u16,
+impl<'info> Cancel<'info> {
+    fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.buyer_deposit_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_close_contest(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.to_account_info().clone(),
+            destination: self.rent_payer.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
-#[error]
-pub enum ErrorCode {
-    #[msg("The order is still in the trial period.")]
-    InTrialDay,
+impl<'info> CancelPartial<'info> {
+    fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.buyer_deposit_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
-impl<'info> Initialize<'info> {
+impl<'info> ChargeMore<'info> {
     fn into_transfer_to_pda_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.buyer_deposit_token_account.to_account_info().clone(),
@@ -640,17 +5976,20 @@ impl<'info> Initialize<'info> {
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
+}
 
-    fn into_set_authority_context(&self) -> CpiContext<'_, '_, '_, 'info, SetAuthority<'info>> {
-        let cpi_accounts = SetAuthority {
-            account_or_mint: self.vault_account.to_account_info().clone(),
-            current_authority: self.buyer.clone(),
+impl<'info> AdjudgePartial<'info> {
+    fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.buyer_deposit_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
-impl<'info> Cancel<'info> {
+impl<'info> AdjudgeForBuyer<'info> {
     fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.vault_account.to_account_info().clone(),
@@ -663,14 +6002,34 @@ impl<'info> Cancel<'info> {
     fn into_close_contest(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
         let cpi_accounts = CloseAccount {
             account: self.vault_account.to_account_info().clone(),
-            destination: self.buyer.clone(),
+            destination: self.rent_payer.clone(),
             authority: self.vault_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
-impl<'info> CancelPartial<'info> {
+impl<'info> AdjudgeForSeller<'info> {
+    fn into_transfer_to_seller_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.seller_receive_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.to_account_info().clone(),
+            destination: self.rent_payer.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> RefundPartial<'info> {
     fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.vault_account.to_account_info().clone(),
@@ -681,18 +6040,27 @@ impl<'info> CancelPartial<'info> {
     }
 }
 
-impl<'info> ChargeMore<'info> {
-    fn into_transfer_to_pda_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+impl<'info> SettleItems<'info> {
+    fn into_transfer_to_seller_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
-            from: self.buyer_deposit_token_account.to_account_info().clone(),
-            to: self.vault_account.to_account_info().clone(),
-            authority: self.buyer.clone(),
+            from: self.vault_account.to_account_info().clone(),
+            to: self.seller_receive_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.to_account_info().clone(),
+            destination: self.rent_payer.clone(),
+            authority: self.vault_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
-impl<'info> AdjudgePartial<'info> {
+impl<'info> CancelItems<'info> {
     fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.vault_account.to_account_info().clone(),
@@ -701,9 +6069,40 @@ impl<'info> AdjudgePartial<'info> {
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
+
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.to_account_info().clone(),
+            destination: self.rent_payer.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
-impl<'info> AdjudgeForBuyer<'info> {
+impl<'info> WithdrawVested<'info> {
+    fn into_transfer_to_seller_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.seller_receive_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> Dispute<'info> {
+    fn into_transfer_to_seller_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.seller_receive_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> RelayCancel<'info> {
     fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.vault_account.to_account_info().clone(),
@@ -713,17 +6112,86 @@ impl<'info> AdjudgeForBuyer<'info> {
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 
-    fn into_close_contest(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
         let cpi_accounts = CloseAccount {
             account: self.vault_account.to_account_info().clone(),
-            destination: self.buyer.clone(),
+            destination: self.rent_payer.clone(),
             authority: self.vault_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
-impl<'info> AdjudgeForSeller<'info> {
+impl<'info> ReclaimUnrevealed<'info> {
+    fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.buyer_deposit_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.to_account_info().clone(),
+            destination: self.rent_payer.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> Release<'info> {
+    fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.buyer_receive_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.to_account_info().clone(),
+            destination: self.rent_payer.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> ReclaimUnpaid<'info> {
+    fn into_transfer_to_seller_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.seller_deposit_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.to_account_info().clone(),
+            destination: self.rent_payer.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> MutualSettlement<'info> {
+    fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.buyer_deposit_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
     fn into_transfer_to_seller_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.vault_account.to_account_info().clone(),
@@ -736,14 +6204,101 @@ impl<'info> AdjudgeForSeller<'info> {
     fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
         let cpi_accounts = CloseAccount {
             account: self.vault_account.to_account_info().clone(),
-            destination: self.buyer.clone(),
+            destination: self.rent_payer.clone(),
             authority: self.vault_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
 }
 
-impl<'info> RefundPartial<'info> {
+impl<'info> ExpireLongStop<'info> {
+    fn into_transfer_to_recipient_context(&self, recipient: AccountInfo<'info>) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: recipient,
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_burn_context(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        let cpi_accounts = Burn {
+            mint: self.mint.to_account_info().clone(),
+            to: self.vault_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.to_account_info().clone(),
+            destination: self.rent_payer.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> AcceptDamages<'info> {
+    fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.buyer_deposit_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_transfer_to_seller_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.seller_receive_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.to_account_info().clone(),
+            destination: self.rent_payer.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> SettleDamages<'info> {
+    fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.buyer_deposit_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_transfer_to_seller_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault_account.to_account_info().clone(),
+            to: self.seller_receive_token_account.to_account_info().clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.to_account_info().clone(),
+            destination: self.rent_payer.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> ReturnDeposit<'info> {
     fn into_transfer_to_buyer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.vault_account.to_account_info().clone(),
@@ -752,6 +6307,26 @@ impl<'info> RefundPartial<'info> {
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
     }
+
+    fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account: self.vault_account.to_account_info().clone(),
+            destination: self.rent_payer.clone(),
+            authority: self.vault_authority.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info> RelayChargeMore<'info> {
+    fn into_transfer_to_pda_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.buyer_deposit_token_account.to_account_info().clone(),
+            to: self.vault_account.to_account_info().clone(),
+            authority: self.intent_delegate.clone(),
+        };
+        CpiContext::new(self.token_program.clone(), cpi_accounts)
+    }
 }
 
 impl<'info> Exchange<'info> {
@@ -767,7 +6342,7 @@ impl<'info> Exchange<'info> {
     fn into_close_context(&self) -> CpiContext<'_, '_, '_, 'info, CloseAccount<'info>> {
         let cpi_accounts = CloseAccount {
             account: self.vault_account.to_account_info().clone(),
-            destination: self.buyer.clone(),
+            destination: self.rent_payer.clone(),
             authority: self.vault_authority.clone(),
         };
         CpiContext::new(self.token_program.clone(), cpi_accounts)
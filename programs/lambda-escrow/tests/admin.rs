@@ -0,0 +1,168 @@
+mod common;
+
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::signature::{Keypair, Signer};
+
+const ORDER_CODE: u64 = 99;
+const AMOUNT: u64 = 1000;
+
+#[tokio::test]
+async fn initialize_config_by_upgrade_authority() {
+    let mut env = Env::without_config().await;
+    let admin = env.admin.pubkey();
+    let compliance_authority = env.compliance_authority.pubkey();
+
+    // Whoever gets there first after a deploy does not become the admin, even paying the rent.
+    let attacker = clone_keypair(&env.buyer);
+    let result = env.process(&[initialize_config(&attacker.pubkey(), &compliance_authority, 0)], &[&attacker]).await;
+    assert_program_error(result, ErrorCode::NotUpgradeAuthority);
+    assert!(!env.account_exists(&config_address().0).await);
+
+    env.process(&[initialize_config(&admin, &compliance_authority, SECONDS_PER_DAY)], &[]).await.unwrap();
+    let config = env.config().await;
+    assert_eq!((config.admin, config.judge_change_delay), (admin, SECONDS_PER_DAY));
+}
+
+#[tokio::test]
+async fn update_config_by_admin() {
+    let mut env = Env::new(0).await;
+    let admin = clone_keypair(&env.admin);
+    let new_admin = Keypair::new();
+    let compliance_authority = env.compliance_authority.pubkey();
+
+    let result = env
+        .process(&[update_config(&admin.pubkey(), &admin.pubkey(), &compliance_authority, -1)], &[&admin])
+        .await;
+    assert_program_error(result, ErrorCode::InvalidTimelock);
+
+    let attacker = Keypair::new();
+    let result = env
+        .process(&[update_config(&attacker.pubkey(), &attacker.pubkey(), &compliance_authority, 0)], &[&attacker])
+        .await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    env.process(&[update_config(&admin.pubkey(), &new_admin.pubkey(), &compliance_authority, SECONDS_PER_DAY)], &[&admin])
+        .await
+        .unwrap();
+    let config = env.config().await;
+    assert_eq!(config.admin, new_admin.pubkey());
+    assert_eq!(config.judge_change_delay, SECONDS_PER_DAY);
+
+    // The old admin is out.
+    let result = env.process(&[set_paused(&admin.pubkey(), true)], &[&admin]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+}
+
+#[tokio::test]
+async fn pause_blocks_buyer_and_seller() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let admin = clone_keypair(&env.admin);
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+    env.process(&[set_paused(&admin.pubkey(), true)], &[&admin]).await.unwrap();
+    assert!(env.config().await.paused);
+
+    let escrow_keypair = Keypair::new();
+    let other = env.escrow(&escrow_keypair, ORDER_CODE + 1);
+    let result = env.try_initialize(&other, &escrow_keypair, AMOUNT, 0).await;
+    assert_program_error(result, ErrorCode::ProgramPaused);
+    let result = env.process(&[charge_more(&escrow, AMOUNT)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::ProgramPaused);
+    let result = env.process(&[exchange(&escrow)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::ProgramPaused);
+
+    env.process(&[set_paused(&admin.pubkey(), false)], &[&admin]).await.unwrap();
+    env.process(&[exchange(&escrow)], &[&seller]).await.unwrap();
+    assert_eq!(env.seller_balance().await, AMOUNT);
+}
+
+#[tokio::test]
+async fn only_admin_pauses() {
+    let mut env = Env::new(0).await;
+    let attacker = Keypair::new();
+
+    let result = env.process(&[set_paused(&attacker.pubkey(), true)], &[&attacker]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    assert!(!env.config().await.paused);
+}
+
+#[tokio::test]
+async fn freeze_blocks_parties_but_not_judge() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let compliance_authority = clone_keypair(&env.compliance_authority);
+    let buyer = clone_keypair(&env.buyer);
+    let judge = clone_keypair(&env.judge);
+
+    // Only the compliance authority can freeze.
+    let result = env.process(&[set_frozen(&escrow, &buyer.pubkey(), true)], &[&buyer]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    env.process(&[set_frozen(&escrow, &compliance_authority.pubkey(), true)], &[&compliance_authority])
+        .await
+        .unwrap();
+    assert!(env.escrow_account(&escrow.escrow_account).await.frozen);
+
+    let result = env.process(&[cancel(&escrow)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::EscrowFrozen);
+    let result = env.process(&[cancel_partial(&escrow, 1)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::EscrowFrozen);
+    let result = env.process(&[charge_more(&escrow, 1)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::EscrowFrozen);
+
+    env.process(&[adjudge_for_buyer(&escrow)], &[&judge]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+}
+
+#[tokio::test]
+async fn freeze_blocks_judge_changes() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let admin = clone_keypair(&env.admin);
+    let compliance_authority = clone_keypair(&env.compliance_authority);
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let new_judge = Keypair::new().pubkey();
+
+    // Scheduled before the freeze, the change cannot land after it.
+    env.process(&[request_judge_change(&escrow, &admin.pubkey(), &new_judge)], &[&admin]).await.unwrap();
+    env.process(&[set_frozen(&escrow, &compliance_authority.pubkey(), true)], &[&compliance_authority])
+        .await
+        .unwrap();
+
+    let result = env.process(&[execute_judge_change(&escrow, &admin.pubkey())], &[&admin]).await;
+    assert_program_error(result, ErrorCode::EscrowFrozen);
+    let result = env.process(&[request_judge_change(&escrow, &admin.pubkey(), &new_judge)], &[&admin]).await;
+    assert_program_error(result, ErrorCode::EscrowFrozen);
+    let result = env.process(&[change_judge(&escrow, &new_judge)], &[&buyer, &seller]).await;
+    assert_program_error(result, ErrorCode::EscrowFrozen);
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.judge_key, env.judge.pubkey());
+}
+
+#[tokio::test]
+async fn thaw_restores_cancel() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let compliance_authority = clone_keypair(&env.compliance_authority);
+    let buyer = clone_keypair(&env.buyer);
+
+    env.process(&[set_frozen(&escrow, &compliance_authority.pubkey(), true)], &[&compliance_authority])
+        .await
+        .unwrap();
+    env.process(&[set_frozen(&escrow, &compliance_authority.pubkey(), false)], &[&compliance_authority])
+        .await
+        .unwrap();
+
+    let instruction = with_receipt(cancel(&escrow), &escrow.escrow_account, &buyer.pubkey());
+    env.process(&[instruction], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    let receipt = env.receipt(&escrow.escrow_account).await;
+    assert_eq!(receipt.buyer_payout, AMOUNT);
+    assert_eq!(receipt.settlement_path, 0);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::signature::{Keypair, Signer};
+
+const AMOUNT: u64 = 1000;
+
+#[tokio::test]
+async fn settle_batch_pays_matured_and_skips_the_rest() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+
+    let mut escrows = Vec::new();
+    for order_code in 1..=4 {
+        // Order 4 is still in its trial.
+        let trial_day = if order_code == 4 { 3 } else { 0 };
+        let escrow = env.initialize(order_code, AMOUNT, trial_day).await;
+        env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+        env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+        escrows.push(escrow);
+    }
+    // Order 5 is not delivered yet.
+    let shipped = env.initialize(5, AMOUNT, 0).await;
+    env.process(&[shipping(&shipped)], &[&seller]).await.unwrap();
+    escrows.push(shipped);
+
+    env.process(&[settle_batch(&escrows)], &[&seller]).await.unwrap();
+
+    assert_eq!(env.seller_balance().await, 3 * AMOUNT);
+    for escrow in &escrows[..3] {
+        assert!(!env.account_exists(&escrow.escrow_account).await);
+        assert!(!env.account_exists(&vault_account(escrow.order_code).0).await);
+    }
+    for escrow in &escrows[3..] {
+        assert!(env.account_exists(&escrow.escrow_account).await);
+        assert_eq!(env.token_balance(&vault_account(escrow.order_code).0).await, AMOUNT);
+    }
+
+    // Once matured, the same batch settles the remaining one.
+    env.warp_forward(3 * SECONDS_PER_DAY).await;
+    env.process(&[settle_batch(&escrows[3..4])], &[&seller]).await.unwrap();
+    assert_eq!(env.seller_balance().await, 4 * AMOUNT);
+}
+
+#[tokio::test]
+async fn settle_batch_skips_groups_that_do_not_match() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+
+    let escrow = env.initialize(1, AMOUNT, 0).await;
+    let other = env.initialize(2, AMOUNT, 0).await;
+    for escrow in &[escrow, other] {
+        env.process(&[shipping(escrow)], &[&seller]).await.unwrap();
+        env.process(&[delivered(escrow)], &[&buyer]).await.unwrap();
+    }
+
+    // Vault of another order, and a rent payer that is not the escrow's.
+    let mut instruction = settle_batch(&[escrow, escrow]);
+    instruction.accounts[5].pubkey = vault_account(other.order_code).0;
+    instruction.accounts[11].pubkey = Keypair::new().pubkey();
+    env.process(&[instruction], &[&seller]).await.unwrap();
+
+    assert_eq!(env.seller_balance().await, 0);
+    assert_eq!(env.token_balance(&vault_account(1).0).await, AMOUNT);
+    assert_eq!(env.token_balance(&vault_account(2).0).await, AMOUNT);
+}
+
+#[tokio::test]
+async fn settle_batch_needs_the_seller() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let escrow = env.initialize(1, AMOUNT, 0).await;
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+
+    // Another signer is not the escrow's seller, the group is skipped.
+    let attacker = Keypair::new();
+    let mut as_seller = escrow;
+    as_seller.seller = attacker.pubkey();
+    env.process(&[settle_batch(&[as_seller])], &[&attacker]).await.unwrap();
+    assert!(env.account_exists(&escrow.escrow_account).await);
+
+    // Groups come in fours.
+    let mut instruction = settle_batch(&[escrow]);
+    instruction.accounts.pop();
+    let result = env.process(&[instruction], &[&seller]).await;
+    assert_program_error(result, ErrorCode::InvalidBatchAccounts);
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::signature::Signer;
+use solana_sdk::transaction::TransactionError;
+
+const CAMPAIGN_ID: u64 = 12;
+const PRICE: u64 = 400;
+const DURATION: i64 = 3600;
+const CONVERT_WITHIN: i64 = 7200;
+
+async fn create(env: &mut Env, threshold: u32, deadline: i64) -> Result<(), TransactionError> {
+    create_with_conversion_deadline(env, threshold, deadline, deadline + CONVERT_WITHIN).await
+}
+
+async fn create_with_conversion_deadline(
+    env: &mut Env,
+    threshold: u32,
+    deadline: i64,
+    conversion_deadline: i64,
+) -> Result<(), TransactionError> {
+    let seller = clone_keypair(&env.seller);
+    let instruction = create_campaign(
+        &seller.pubkey(),
+        &env.judge.pubkey(),
+        &env.mint.pubkey(),
+        &env.seller_token,
+        CAMPAIGN_ID,
+        PRICE,
+        threshold,
+        deadline,
+        conversion_deadline,
+    );
+    env.process(&[instruction], &[&seller]).await
+}
+
+#[tokio::test]
+async fn threshold_met_converts_deposits_into_escrows() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let buyer_token = env.buyer_token;
+    let (other_buyer, other_token) = env.new_buyer().await;
+    let now = env.now().await;
+    create(&mut env, 2, now + DURATION).await.unwrap();
+
+    env.process(&[join_campaign(&buyer.pubkey(), &buyer_token, CAMPAIGN_ID)], &[&buyer]).await.unwrap();
+    let result = env.convert_campaign_deposit(CAMPAIGN_ID, &buyer.pubkey(), &buyer_token, 1).await;
+    assert_program_error(result.map(|_| ()), ErrorCode::CampaignThresholdNotMet);
+
+    env.process(&[join_campaign(&other_buyer.pubkey(), &other_token, CAMPAIGN_ID)], &[&other_buyer]).await.unwrap();
+    let campaign = env.campaign(CAMPAIGN_ID).await;
+    assert_eq!((campaign.participants, campaign.open_deposits), (2, 2));
+    assert_eq!(env.token_balance(&campaign_vault_address(CAMPAIGN_ID).0).await, 2 * PRICE);
+    let result = env.process(&[refund_campaign_deposit(&buyer.pubkey(), &buyer_token, CAMPAIGN_ID)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::CampaignNotFailed);
+
+    // Each deposit becomes a New escrow of its buyer, with the usual lifecycle.
+    let escrow = env.convert_campaign_deposit(CAMPAIGN_ID, &buyer.pubkey(), &buyer_token, 1).await.unwrap();
+    let other = env.convert_campaign_deposit(CAMPAIGN_ID, &other_buyer.pubkey(), &other_token, 2).await.unwrap();
+    assert_eq!(env.token_balance(&campaign_vault_address(CAMPAIGN_ID).0).await, 0);
+    assert!(!env.account_exists(&campaign_deposit_address(CAMPAIGN_ID, &buyer.pubkey()).0).await);
+    let escrow_account = env.escrow_account(&other.escrow_account).await;
+    assert_eq!(escrow_account.buyer_key, other_buyer.pubkey());
+    assert_eq!(escrow_account.amount, PRICE);
+    assert_eq!(escrow_account.rent_payer, seller.pubkey());
+    assert_eq!(env.token_balance(&vault_account(2).0).await, PRICE);
+
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+    env.process(&[exchange(&escrow)], &[&seller]).await.unwrap();
+    assert_eq!(env.seller_balance().await, PRICE);
+
+    // Closed once no buyer can join anymore.
+    let result = env.process(&[close_campaign(&seller.pubkey(), CAMPAIGN_ID)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::CampaignNotClosable);
+    env.warp_forward(DURATION).await;
+    env.next_slot().await;
+    env.process(&[close_campaign(&seller.pubkey(), CAMPAIGN_ID)], &[&seller]).await.unwrap();
+    assert!(!env.account_exists(&campaign_address(CAMPAIGN_ID).0).await);
+    assert!(!env.account_exists(&campaign_vault_address(CAMPAIGN_ID).0).await);
+}
+
+#[tokio::test]
+async fn missed_threshold_refunds_every_buyer() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let buyer_token = env.buyer_token;
+    let (other_buyer, other_token) = env.new_buyer().await;
+    let (late_buyer, late_token) = env.new_buyer().await;
+    let now = env.now().await;
+    let result = create(&mut env, 3, now).await;
+    assert_program_error(result, ErrorCode::InvalidCampaign);
+    create(&mut env, 3, now + DURATION).await.unwrap();
+
+    env.process(&[join_campaign(&buyer.pubkey(), &buyer_token, CAMPAIGN_ID)], &[&buyer]).await.unwrap();
+    env.process(&[join_campaign(&other_buyer.pubkey(), &other_token, CAMPAIGN_ID)], &[&other_buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - PRICE);
+    let result = env.process(&[refund_campaign_deposit(&buyer.pubkey(), &buyer_token, CAMPAIGN_ID)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::CampaignNotFailed);
+
+    env.warp_forward(DURATION).await;
+    let result = env.process(&[join_campaign(&late_buyer.pubkey(), &late_token, CAMPAIGN_ID)], &[&late_buyer]).await;
+    assert_program_error(result, ErrorCode::CampaignEnded);
+    let result = env.convert_campaign_deposit(CAMPAIGN_ID, &buyer.pubkey(), &buyer_token, 1).await;
+    assert_program_error(result.map(|_| ()), ErrorCode::CampaignThresholdNotMet);
+
+    // Each buyer takes its own deposit back, not someone else's.
+    let result = env.process(&[refund_campaign_deposit(&buyer.pubkey(), &other_token, CAMPAIGN_ID)], &[&buyer]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    env.next_slot().await;
+    env.process(&[refund_campaign_deposit(&buyer.pubkey(), &buyer_token, CAMPAIGN_ID)], &[&buyer]).await.unwrap();
+    let result = env.process(&[close_campaign(&seller.pubkey(), CAMPAIGN_ID)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::CampaignNotClosable);
+    env.process(&[refund_campaign_deposit(&other_buyer.pubkey(), &other_token, CAMPAIGN_ID)], &[&other_buyer])
+        .await
+        .unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    assert_eq!(env.token_balance(&other_token).await, BUYER_TOKENS);
+    assert_eq!(env.campaign(CAMPAIGN_ID).await.participants, 0);
+
+    env.next_slot().await;
+    env.process(&[close_campaign(&seller.pubkey(), CAMPAIGN_ID)], &[&seller]).await.unwrap();
+    assert!(!env.account_exists(&campaign_address(CAMPAIGN_ID).0).await);
+}
+
+#[tokio::test]
+async fn deposits_not_converted_in_time_are_freed() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let keeper = clone_keypair(&env.admin);
+    let buyer_token = env.buyer_token;
+    let (other_buyer, other_token) = env.new_buyer().await;
+    let now = env.now().await;
+    let result = create_with_conversion_deadline(&mut env, 2, now + DURATION, now + DURATION).await;
+    assert_program_error(result, ErrorCode::InvalidCampaign);
+    create(&mut env, 2, now + DURATION).await.unwrap();
+    env.process(&[join_campaign(&buyer.pubkey(), &buyer_token, CAMPAIGN_ID)], &[&buyer]).await.unwrap();
+    env.process(&[join_campaign(&other_buyer.pubkey(), &other_token, CAMPAIGN_ID)], &[&other_buyer]).await.unwrap();
+    assert_eq!(env.campaign(CAMPAIGN_ID).await.conversion_deadline, now + DURATION + CONVERT_WITHIN);
+
+    // The threshold is met but the seller never converts. Until the conversion deadline only
+    // the seller can, and the deposits stay locked.
+    env.warp_forward(DURATION).await;
+    let result = env.convert_campaign_deposit_paid_by(CAMPAIGN_ID, &buyer.pubkey(), &buyer_token, 1, &keeper).await;
+    assert_program_error(result.map(|_| ()), ErrorCode::ConversionDeadlineNotPassed);
+    let result = env.process(&[refund_campaign_deposit(&buyer.pubkey(), &buyer_token, CAMPAIGN_ID)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::CampaignNotFailed);
+
+    // Past it, a buyer takes its deposit back...
+    env.warp_forward(CONVERT_WITHIN).await;
+    env.process(&[refund_campaign_deposit(&buyer.pubkey(), &buyer_token, CAMPAIGN_ID)], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+
+    // ...or anyone converts it for the seller, paying the escrow rent.
+    let escrow = env
+        .convert_campaign_deposit_paid_by(CAMPAIGN_ID, &other_buyer.pubkey(), &other_token, 2, &keeper)
+        .await
+        .unwrap();
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!((escrow_account.buyer_key, escrow_account.seller_key), (other_buyer.pubkey(), seller.pubkey()));
+    assert_eq!((escrow_account.amount, escrow_account.rent_payer), (PRICE, keeper.pubkey()));
+    assert_eq!(env.token_balance(&vault_account(2).0).await, PRICE);
+    let campaign = env.campaign(CAMPAIGN_ID).await;
+    assert_eq!((campaign.participants, campaign.open_deposits), (2, 0));
+
+    env.process(&[close_campaign(&seller.pubkey(), CAMPAIGN_ID)], &[&seller]).await.unwrap();
+    assert!(!env.account_exists(&campaign_address(CAMPAIGN_ID).0).await);
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::*;
+use lambda_escrow::{CartEntry, ErrorCode};
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+const CART_ID: u64 = 7;
+
+fn entry(order_code: u64, amount: u64, trial_day: u16) -> CartEntry {
+    CartEntry { order_code, amount, trial_day }
+}
+
+/// A cart of 400 from the env seller and 600 from a second seller.
+async fn two_seller_cart(env: &mut Env) -> Vec<(Pubkey, Pubkey, CartEntry)> {
+    let other_seller = Keypair::new().pubkey();
+    let other_seller_token = env.create_token_account(&other_seller).await;
+    vec![
+        (env.seller.pubkey(), env.seller_token, entry(1, 400, 0)),
+        (other_seller, other_seller_token, entry(2, 600, 2)),
+    ]
+}
+
+#[tokio::test]
+async fn checkout_and_cancel_cart() {
+    let mut env = Env::new(0).await;
+    let items = two_seller_cart(&mut env).await;
+    let (escrows, result) = env.try_checkout(CART_ID, &items).await;
+    result.unwrap();
+
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - 1000);
+    for (escrow, (seller, seller_token, entry)) in escrows.iter().zip(&items) {
+        let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+        assert_eq!(escrow_account.buyer_key, env.buyer.pubkey());
+        assert_eq!(escrow_account.seller_key, *seller);
+        assert_eq!(escrow_account.seller_receive_token_account, *seller_token);
+        assert_eq!(escrow_account.judge_key, env.judge.pubkey());
+        assert_eq!(escrow_account.order_code, entry.order_code);
+        assert_eq!(escrow_account.amount, entry.amount);
+        assert_eq!(escrow_account.trial_day, entry.trial_day);
+        assert_eq!(escrow_account.status, 0);
+        assert_eq!(escrow_account.cart_id, CART_ID);
+        assert_eq!(env.token_balance(&vault_account(entry.order_code).0).await, entry.amount);
+    }
+
+    // Each escrow of the cart is a regular escrow.
+    let seller = clone_keypair(&env.seller);
+    env.process(&[shipping(&escrows[0])], &[&seller]).await.unwrap();
+    env.process(&[refund(&escrows[0])], &[&seller]).await.unwrap();
+
+    let buyer = clone_keypair(&env.buyer);
+    env.process(&[cancel_cart(&escrows, CART_ID)], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    for escrow in &escrows {
+        assert!(!env.account_exists(&escrow.escrow_account).await);
+        assert!(!env.account_exists(&vault_account(escrow.order_code).0).await);
+    }
+}
+
+#[tokio::test]
+async fn checkout_is_all_or_nothing() {
+    let mut env = Env::new(0).await;
+    let mut items = two_seller_cart(&mut env).await;
+
+    let (_, result) = env.try_checkout(0, &items).await;
+    assert_program_error(result, ErrorCode::InvalidCartId);
+
+    items[1].2.amount = 0;
+    let (_, result) = env.try_checkout(CART_ID, &items).await;
+    assert_program_error(result, ErrorCode::InvalidCartEntry);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    assert!(!env.account_exists(&vault_account(1).0).await);
+
+    // An order code already taken fails the cart after the first escrow was made.
+    env.initialize(2, 100, 0).await;
+    items[1].2.amount = 600;
+    let (escrows, result) = env.try_checkout(CART_ID, &items).await;
+    assert!(result.is_err());
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - 100);
+    assert!(!env.account_exists(&vault_account(1).0).await);
+    assert!(!env.account_exists(&escrows[0].escrow_account).await);
+}
+
+#[tokio::test]
+async fn cancel_cart_fails_once_shipped() {
+    let mut env = Env::new(0).await;
+    let items = two_seller_cart(&mut env).await;
+    let (escrows, result) = env.try_checkout(CART_ID, &items).await;
+    result.unwrap();
+    let buyer = clone_keypair(&env.buyer);
+
+    let result = env.process(&[cancel_cart(&escrows, CART_ID + 1)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::InvalidCartAccounts);
+
+    // An escrow made by initialize is not in the cart.
+    let single = env.initialize(3, 100, 0).await;
+    let result = env.process(&[cancel_cart(&[escrows[0], single], CART_ID)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::InvalidCartAccounts);
+
+    let seller = clone_keypair(&env.seller);
+    env.process(&[shipping(&escrows[0])], &[&seller]).await.unwrap();
+    let result = env.process(&[cancel_cart(&escrows, CART_ID)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::CartShipped);
+    assert_eq!(env.token_balance(&vault_account(2).0).await, 600);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - 1100);
+
+    // The rest of the cart can still be cancelled.
+    env.process(&[cancel_cart(&escrows[1..], CART_ID)], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - 500);
+}
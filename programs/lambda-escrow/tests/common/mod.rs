@@ -0,0 +1,1702 @@
+// Shared setup for the program-test suites: a bank with one mint, funded
+// buyer/seller token accounts, the program config and instruction builders.
+#![allow(dead_code)]
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction, InstructionError};
+use anchor_lang::solana_program::program_error::ProgramError;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::{system_instruction, system_program, sysvar};
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anchor_lang::solana_program::ed25519_program;
+use lambda_escrow::{
+    accounts, instruction as ix, Campaign, CartEntry, EscrowAccount, Intent, LineItems, NewLineItem, ProgramConfig,
+    SettlementReceipt,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::account::{Account, AccountSharedData};
+use solana_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use solana_sdk::clock::Clock;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_sdk::transport::TransportError;
+
+pub const BUYER_TOKENS: u64 = 10_000;
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+// anchor_lang::error::ErrorCode
+pub const CONSTRAINT_SIGNER: u32 = 142;
+pub const CONSTRAINT_RAW: u32 = 143;
+
+pub struct Env {
+    pub context: ProgramTestContext,
+    pub admin: Keypair,
+    pub compliance_authority: Keypair,
+    pub mint: Keypair,
+    pub mint_authority: Keypair,
+    pub buyer: Keypair,
+    pub seller: Keypair,
+    pub judge: Keypair,
+    pub buyer_token: Pubkey,
+    pub seller_token: Pubkey,
+}
+
+/// Keys of one escrow, like `EscrowKeys` in the client crate.
+#[derive(Clone, Copy, Debug)]
+pub struct Escrow {
+    pub escrow_account: Pubkey,
+    pub order_code: u64,
+    pub buyer: Pubkey,
+    pub buyer_deposit_token_account: Pubkey,
+    pub seller: Pubkey,
+    pub seller_receive_token_account: Pubkey,
+    pub judge: Pubkey,
+    pub rent_payer: Pubkey,
+    pub fulfiller: Pubkey,
+}
+
+pub fn vault_account(order_code: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[format!("token-seed-{}", order_code).as_bytes()], &lambda_escrow::ID)
+}
+
+pub fn vault_authority(order_code: u64) -> Pubkey {
+    Pubkey::find_program_address(&[format!("escrow-{}", order_code).as_bytes()], &lambda_escrow::ID).0
+}
+
+pub fn config_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config".as_ref()], &lambda_escrow::ID)
+}
+
+pub fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[lambda_escrow::ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+pub fn line_items_address(order_code: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[format!("line-items-{}", order_code).as_bytes()], &lambda_escrow::ID)
+}
+
+pub fn receipt_address(escrow_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"receipt".as_ref(), escrow_account.as_ref()], &lambda_escrow::ID).0
+}
+
+pub fn receivable_mint_address(order_code: u64) -> Pubkey {
+    Pubkey::find_program_address(&[format!("receivable-mint-{}", order_code).as_bytes()], &lambda_escrow::ID).0
+}
+
+pub fn receivable_token_address(order_code: u64) -> Pubkey {
+    Pubkey::find_program_address(&[format!("receivable-{}", order_code).as_bytes()], &lambda_escrow::ID).0
+}
+
+pub fn campaign_address(campaign_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[format!("campaign-{}", campaign_id).as_bytes()], &lambda_escrow::ID)
+}
+
+pub fn campaign_vault_address(campaign_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[format!("campaign-vault-{}", campaign_id).as_bytes()], &lambda_escrow::ID)
+}
+
+pub fn campaign_deposit_address(campaign_id: u64, buyer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[format!("campaign-deposit-{}", campaign_id).as_bytes(), buyer.as_ref()],
+        &lambda_escrow::ID,
+    )
+}
+
+pub fn clone_keypair(keypair: &Keypair) -> Keypair {
+    Keypair::from_bytes(&keypair.to_bytes()).unwrap()
+}
+
+pub fn program_test() -> ProgramTest {
+    ProgramTest::new("lambda_escrow", lambda_escrow::ID, processor!(lambda_escrow::entry))
+}
+
+/// Asserts the transaction failed in its first instruction with `code`.
+pub fn assert_custom_error(result: Result<(), TransactionError>, code: u32) {
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(code))
+    );
+}
+
+/// The `InstructionError::Custom` code of a program error.
+pub fn error_code(code: lambda_escrow::ErrorCode) -> u32 {
+    match ProgramError::from(code) {
+        ProgramError::Custom(code) => code,
+        error => panic!("not a custom error: {:?}", error),
+    }
+}
+
+pub fn assert_program_error(result: Result<(), TransactionError>, code: lambda_escrow::ErrorCode) {
+    assert_custom_error(result, error_code(code));
+}
+
+/// Like `assert_custom_error`, for the instruction at `index`.
+pub fn assert_custom_error_at(result: Result<(), TransactionError>, index: u8, code: u32) {
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(index, InstructionError::Custom(code))
+    );
+}
+
+/// Like `assert_program_error`, for the instruction at `index`, e.g. after an Ed25519 one.
+pub fn assert_program_error_at(result: Result<(), TransactionError>, index: u8, code: lambda_escrow::ErrorCode) {
+    assert_custom_error_at(result, index, error_code(code));
+}
+
+impl Env {
+    /// Starts a bank with the config created, `judge_change_delay` seconds of timelock.
+    pub async fn new(judge_change_delay: i64) -> Env {
+        let mut env = Env::without_config().await;
+
+        // Program config, admin is the payer.
+        let admin = env.admin.pubkey();
+        let compliance_authority = env.compliance_authority.pubkey();
+        env.process(&[initialize_config(&admin, &compliance_authority, judge_change_delay)], &[]).await.unwrap();
+
+        env
+    }
+
+    /// Starts a bank with the mint and token accounts but no config, the payer is the upgrade authority.
+    pub async fn without_config() -> Env {
+        let mut context = program_test().start_with_context().await;
+        // processor! loads the program as a builtin, give it the ProgramData a deploy would create.
+        let program_data = UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: Some(context.payer.pubkey()),
+        };
+        let program_data = Account::new_data(1_000_000_000, &program_data, &bpf_loader_upgradeable::ID).unwrap();
+        context.set_account(&program_data_address(), &AccountSharedData::from(program_data));
+        let admin = clone_keypair(&context.payer);
+        let mut env = Env {
+            context,
+            admin,
+            compliance_authority: Keypair::new(),
+            mint: Keypair::new(),
+            mint_authority: Keypair::new(),
+            buyer: Keypair::new(),
+            seller: Keypair::new(),
+            judge: Keypair::new(),
+            buyer_token: Pubkey::default(),
+            seller_token: Pubkey::default(),
+        };
+
+        // Fund Sol to accounts test.
+        let payer = env.admin.pubkey();
+        let fund = [
+            system_instruction::transfer(&payer, &env.buyer.pubkey(), 1_000_000_000),
+            system_instruction::transfer(&payer, &env.seller.pubkey(), 1_000_000_000),
+        ];
+        env.process(&fund, &[]).await.unwrap();
+
+        // Create Token A
+        let rent = env.context.banks_client.get_rent().await.unwrap();
+        let mint = env.mint.pubkey();
+        let create_mint = [
+            system_instruction::create_account(
+                &payer,
+                &mint,
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint(&spl_token::ID, &mint, &env.mint_authority.pubkey(), None, 0)
+                .unwrap(),
+        ];
+        let mint_signer = clone_keypair(&env.mint);
+        env.process(&create_mint, &[&mint_signer]).await.unwrap();
+
+        // Create account-token belong to Token A
+        let buyer = env.buyer.pubkey();
+        let seller = env.seller.pubkey();
+        env.buyer_token = env.create_token_account(&buyer).await;
+        env.seller_token = env.create_token_account(&seller).await;
+        let buyer_token = env.buyer_token;
+        env.mint_to(&buyer_token, BUYER_TOKENS).await;
+
+        env
+    }
+
+    /// Signs with the payer and `signers`, and returns the transaction error if any.
+    pub async fn process(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), TransactionError> {
+        let mut all_signers: Vec<&Keypair> = vec![&self.admin];
+        all_signers.extend(signers.iter().copied().filter(|signer| signer.pubkey() != self.admin.pubkey()));
+        let recent_blockhash = self.context.banks_client.get_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.admin.pubkey()),
+            &all_signers,
+            recent_blockhash,
+        );
+        match self.context.banks_client.process_transaction(transaction).await {
+            Ok(()) => Ok(()),
+            Err(TransportError::TransactionError(error)) => Err(error),
+            Err(error) => panic!("transport error: {:?}", error),
+        }
+    }
+
+    pub async fn create_token_account(&mut self, owner: &Pubkey) -> Pubkey {
+        let mint = self.mint.pubkey();
+        self.create_token_account_of(&mint, owner).await
+    }
+
+    /// Like `create_token_account`, for another mint such as a receivable mint.
+    pub async fn create_token_account_of(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        let account = Keypair::new();
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let instructions = [
+            system_instruction::create_account(
+                &self.admin.pubkey(),
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_account(&spl_token::ID, &account.pubkey(), mint, owner).unwrap(),
+        ];
+        self.process(&instructions, &[&account]).await.unwrap();
+        account.pubkey()
+    }
+
+    pub async fn mint_to(&mut self, account: &Pubkey, amount: u64) {
+        let mint_to = spl_token::instruction::mint_to(
+            &spl_token::ID,
+            &self.mint.pubkey(),
+            account,
+            &self.mint_authority.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap();
+        let mint_authority = clone_keypair(&self.mint_authority);
+        self.process(&[mint_to], &[&mint_authority]).await.unwrap();
+    }
+
+    pub async fn token_balance(&mut self, address: &Pubkey) -> u64 {
+        let account = self.context.banks_client.get_account(*address).await.unwrap().unwrap();
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+
+    pub async fn buyer_balance(&mut self) -> u64 {
+        let buyer_token = self.buyer_token;
+        self.token_balance(&buyer_token).await
+    }
+
+    pub async fn seller_balance(&mut self) -> u64 {
+        let seller_token = self.seller_token;
+        self.token_balance(&seller_token).await
+    }
+
+    pub async fn account_exists(&mut self, address: &Pubkey) -> bool {
+        self.context.banks_client.get_account(*address).await.unwrap().is_some()
+    }
+
+    pub async fn lamports(&mut self, address: &Pubkey) -> u64 {
+        self.context.banks_client.get_balance(*address).await.unwrap()
+    }
+
+    pub async fn escrow_account(&mut self, address: &Pubkey) -> EscrowAccount {
+        let account = self.context.banks_client.get_account(*address).await.unwrap().unwrap();
+        EscrowAccount::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub async fn config(&mut self) -> ProgramConfig {
+        let account = self.context.banks_client.get_account(config_address().0).await.unwrap().unwrap();
+        ProgramConfig::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub async fn line_items(&mut self, order_code: u64) -> LineItems {
+        let account = self.context.banks_client.get_account(line_items_address(order_code).0).await.unwrap().unwrap();
+        LineItems::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub async fn campaign(&mut self, campaign_id: u64) -> Campaign {
+        let account = self.context.banks_client.get_account(campaign_address(campaign_id).0).await.unwrap().unwrap();
+        Campaign::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    /// Another buyer with SOL for rent and `BUYER_TOKENS` in its token account.
+    pub async fn new_buyer(&mut self) -> (Keypair, Pubkey) {
+        let buyer = Keypair::new();
+        let fund = system_instruction::transfer(&self.admin.pubkey(), &buyer.pubkey(), 1_000_000_000);
+        self.process(&[fund], &[]).await.unwrap();
+        let buyer_token = self.create_token_account(&buyer.pubkey()).await;
+        self.mint_to(&buyer_token, BUYER_TOKENS).await;
+        (buyer, buyer_token)
+    }
+
+    pub async fn receipt(&mut self, escrow_account: &Pubkey) -> SettlementReceipt {
+        let account = self.context.banks_client.get_account(receipt_address(escrow_account)).await.unwrap().unwrap();
+        SettlementReceipt::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    /// Moves the cluster clock forward, e.g. past `trial_day`.
+    pub async fn warp_forward(&mut self, seconds: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += seconds;
+        self.context.set_sysvar(&clock);
+    }
+
+    pub async fn now(&mut self) -> i64 {
+        let clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp
+    }
+
+    /// Moves to the next slot for a fresh blockhash, so the same instruction can be sent twice.
+    pub async fn next_slot(&mut self) {
+        let clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        self.context.warp_to_slot(clock.slot + 1).unwrap();
+    }
+
+    pub fn escrow(&self, escrow_account: &Keypair, order_code: u64) -> Escrow {
+        Escrow {
+            escrow_account: escrow_account.pubkey(),
+            order_code,
+            buyer: self.buyer.pubkey(),
+            buyer_deposit_token_account: self.buyer_token,
+            seller: self.seller.pubkey(),
+            seller_receive_token_account: self.seller_token,
+            judge: self.judge.pubkey(),
+            rent_payer: self.buyer.pubkey(),
+            fulfiller: self.seller.pubkey(),
+        }
+    }
+
+    /// Creates and funds an escrow of `amount` for `order_code`.
+    pub async fn initialize(&mut self, order_code: u64, amount: u64, trial_day: u16) -> Escrow {
+        let escrow_keypair = Keypair::new();
+        let escrow = self.escrow(&escrow_keypair, order_code);
+        let result = self.try_initialize(&escrow, &escrow_keypair, amount, trial_day).await;
+        result.unwrap();
+        escrow
+    }
+
+    /// Like `initialize`, with `rent_payer` paying the escrow and vault rent instead of the buyer.
+    pub async fn initialize_paid_by(&mut self, order_code: u64, amount: u64, trial_day: u16, rent_payer: &Keypair) -> Escrow {
+        let escrow_keypair = Keypair::new();
+        let escrow = Escrow { rent_payer: rent_payer.pubkey(), ..self.escrow(&escrow_keypair, order_code) };
+        let result = self.try_initialize_paid_by(&escrow, &escrow_keypair, amount, trial_day, rent_payer).await;
+        result.unwrap();
+        escrow
+    }
+
+    /// Like `initialize`, minting the escrow's receivable token to the seller.
+    pub async fn initialize_tokenized(&mut self, order_code: u64, amount: u64, trial_day: u16) -> Escrow {
+        let escrow_keypair = Keypair::new();
+        let escrow = self.escrow(&escrow_keypair, order_code);
+        let instruction = with_receivable(initialize(&escrow, &self.mint.pubkey(), amount, trial_day), order_code);
+        let buyer = clone_keypair(&self.buyer);
+        let result = self.try_initialize_with(&escrow, &escrow_keypair, instruction, &buyer).await;
+        result.unwrap();
+        escrow
+    }
+
+    pub async fn try_initialize(
+        &mut self,
+        escrow: &Escrow,
+        escrow_keypair: &Keypair,
+        amount: u64,
+        trial_day: u16,
+    ) -> Result<(), TransactionError> {
+        let buyer = clone_keypair(&self.buyer);
+        self.try_initialize_paid_by(escrow, escrow_keypair, amount, trial_day, &buyer).await
+    }
+
+    pub async fn try_initialize_paid_by(
+        &mut self,
+        escrow: &Escrow,
+        escrow_keypair: &Keypair,
+        amount: u64,
+        trial_day: u16,
+        rent_payer: &Keypair,
+    ) -> Result<(), TransactionError> {
+        let instruction = initialize(escrow, &self.mint.pubkey(), amount, trial_day);
+        self.try_initialize_with(escrow, escrow_keypair, instruction, rent_payer).await
+    }
+
+    /// Sends `initialize`, built by the caller, after creating the escrow account paid by `rent_payer`.
+    pub async fn try_initialize_with(
+        &mut self,
+        escrow: &Escrow,
+        escrow_keypair: &Keypair,
+        initialize: Instruction,
+        rent_payer: &Keypair,
+    ) -> Result<(), TransactionError> {
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = 8 + EscrowAccount::LEN;
+        let instructions = [
+            system_instruction::create_account(
+                &rent_payer.pubkey(),
+                &escrow.escrow_account,
+                rent.minimum_balance(space),
+                space as u64,
+                &lambda_escrow::ID,
+            ),
+            initialize,
+        ];
+        let buyer = clone_keypair(&self.buyer);
+        let mut signers = vec![escrow_keypair, &buyer];
+        if rent_payer.pubkey() != buyer.pubkey() {
+            signers.push(rent_payer);
+        }
+        let result = self.process(&instructions, &signers).await;
+        // The create_account instruction comes first, report program errors against index 0.
+        result.map_err(|error| match error {
+            TransactionError::InstructionError(1, error) => TransactionError::InstructionError(0, error),
+            error => error,
+        })
+    }
+
+    /// Converts the campaign deposit of `buyer` into the escrow of `order_code`, created and
+    /// paid by the seller.
+    pub async fn convert_campaign_deposit(
+        &mut self,
+        campaign_id: u64,
+        buyer: &Pubkey,
+        buyer_deposit_token_account: &Pubkey,
+        order_code: u64,
+    ) -> Result<Escrow, TransactionError> {
+        let seller = clone_keypair(&self.seller);
+        self.convert_campaign_deposit_paid_by(campaign_id, buyer, buyer_deposit_token_account, order_code, &seller)
+            .await
+    }
+
+    /// Like `convert_campaign_deposit`, created and paid by `payer`, who alone signs.
+    pub async fn convert_campaign_deposit_paid_by(
+        &mut self,
+        campaign_id: u64,
+        buyer: &Pubkey,
+        buyer_deposit_token_account: &Pubkey,
+        order_code: u64,
+        payer: &Keypair,
+    ) -> Result<Escrow, TransactionError> {
+        let escrow_keypair = Keypair::new();
+        let escrow = Escrow {
+            buyer: *buyer,
+            buyer_deposit_token_account: *buyer_deposit_token_account,
+            rent_payer: payer.pubkey(),
+            ..self.escrow(&escrow_keypair, order_code)
+        };
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = 8 + EscrowAccount::LEN;
+        let instructions = [
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &escrow.escrow_account,
+                rent.minimum_balance(space),
+                space as u64,
+                &lambda_escrow::ID,
+            ),
+            convert_campaign_deposit(&escrow, &self.mint.pubkey(), campaign_id),
+        ];
+        let result = self.process(&instructions, &[&escrow_keypair, payer]).await;
+        // The create_account instruction comes first, report program errors against index 0.
+        result.map(|()| escrow).map_err(|error| match error {
+            TransactionError::InstructionError(1, error) => TransactionError::InstructionError(0, error),
+            error => error,
+        })
+    }
+
+    /// Creates a seller-funded escrow of `amount` for `order_code`, locked from the seller's
+    /// token account and paid for by the seller. The buyer receives on its token account.
+    pub async fn try_initialize_seller_funded(
+        &mut self,
+        order_code: u64,
+        amount: u64,
+        payment_window: i64,
+    ) -> Result<Escrow, TransactionError> {
+        let escrow_keypair = Keypair::new();
+        let seller = clone_keypair(&self.seller);
+        let escrow = Escrow { rent_payer: seller.pubkey(), ..self.escrow(&escrow_keypair, order_code) };
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = 8 + EscrowAccount::LEN;
+        let instructions = [
+            system_instruction::create_account(
+                &seller.pubkey(),
+                &escrow.escrow_account,
+                rent.minimum_balance(space),
+                space as u64,
+                &lambda_escrow::ID,
+            ),
+            initialize_seller_funded(&escrow, &self.mint.pubkey(), amount, payment_window),
+        ];
+        let result = self.process(&instructions, &[&escrow_keypair, &seller]).await;
+        // The create_account instruction comes first, report program errors against index 0.
+        result.map(|()| escrow).map_err(|error| match error {
+            TransactionError::InstructionError(1, error) => TransactionError::InstructionError(0, error),
+            error => error,
+        })
+    }
+
+    /// Creates a double-deposit escrow with no judge for `order_code`, locking `price` and the
+    /// buyer's collateral from the buyer and the seller's collateral from the seller.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn try_initialize_double_deposit(
+        &mut self,
+        order_code: u64,
+        price: u64,
+        buyer_collateral: u64,
+        seller_collateral: u64,
+        long_stop: i64,
+        long_stop_recipient: Option<&Pubkey>,
+    ) -> Result<Escrow, TransactionError> {
+        let escrow_keypair = Keypair::new();
+        let buyer = clone_keypair(&self.buyer);
+        let seller = clone_keypair(&self.seller);
+        let escrow = Escrow { judge: Pubkey::default(), ..self.escrow(&escrow_keypair, order_code) };
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = 8 + EscrowAccount::LEN;
+        let instructions = [
+            system_instruction::create_account(
+                &buyer.pubkey(),
+                &escrow.escrow_account,
+                rent.minimum_balance(space),
+                space as u64,
+                &lambda_escrow::ID,
+            ),
+            initialize_double_deposit(
+                &escrow,
+                &self.mint.pubkey(),
+                price,
+                buyer_collateral,
+                seller_collateral,
+                long_stop,
+                long_stop_recipient,
+            ),
+        ];
+        let result = self.process(&instructions, &[&escrow_keypair, &buyer, &seller]).await;
+        // The create_account instruction comes first, report program errors against index 0.
+        result.map(|()| escrow).map_err(|error| match error {
+            TransactionError::InstructionError(1, error) => TransactionError::InstructionError(0, error),
+            error => error,
+        })
+    }
+
+    /// Creates a rental deposit of `amount` for `order_code`, locked from the buyer until
+    /// `rental_end`, with `claim_window_day` days to claim damages after it.
+    pub async fn try_initialize_rental(
+        &mut self,
+        order_code: u64,
+        amount: u64,
+        rental_end: i64,
+        claim_window_day: u16,
+    ) -> Result<Escrow, TransactionError> {
+        let escrow_keypair = Keypair::new();
+        let buyer = clone_keypair(&self.buyer);
+        let escrow = self.escrow(&escrow_keypair, order_code);
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = 8 + EscrowAccount::LEN;
+        let instructions = [
+            system_instruction::create_account(
+                &buyer.pubkey(),
+                &escrow.escrow_account,
+                rent.minimum_balance(space),
+                space as u64,
+                &lambda_escrow::ID,
+            ),
+            initialize_rental(&escrow, &self.mint.pubkey(), amount, rental_end, claim_window_day),
+        ];
+        let result = self.process(&instructions, &[&escrow_keypair, &buyer]).await;
+        // The create_account instruction comes first, report program errors against index 0.
+        result.map(|()| escrow).map_err(|error| match error {
+            TransactionError::InstructionError(1, error) => TransactionError::InstructionError(0, error),
+            error => error,
+        })
+    }
+
+    /// Creates one escrow per `(seller, seller_receive_token_account, entry)` with a single `checkout`.
+    pub async fn try_checkout(
+        &mut self,
+        cart_id: u64,
+        items: &[(Pubkey, Pubkey, CartEntry)],
+    ) -> (Vec<Escrow>, Result<(), TransactionError>) {
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = 8 + EscrowAccount::LEN;
+        let escrow_keypairs: Vec<Keypair> = items.iter().map(|_| Keypair::new()).collect();
+        let escrows: Vec<Escrow> = items
+            .iter()
+            .zip(&escrow_keypairs)
+            .map(|((seller, seller_receive_token_account, entry), escrow_keypair)| Escrow {
+                seller: *seller,
+                seller_receive_token_account: *seller_receive_token_account,
+                fulfiller: *seller,
+                ..self.escrow(escrow_keypair, entry.order_code)
+            })
+            .collect();
+        let mut instructions: Vec<Instruction> = escrows
+            .iter()
+            .map(|escrow| {
+                system_instruction::create_account(
+                    &self.admin.pubkey(),
+                    &escrow.escrow_account,
+                    rent.minimum_balance(space),
+                    space as u64,
+                    &lambda_escrow::ID,
+                )
+            })
+            .collect();
+        let entries = items.iter().map(|(_, _, entry)| entry.clone()).collect();
+        instructions.push(checkout(&escrows, &self.mint.pubkey(), cart_id, entries));
+
+        let buyer = clone_keypair(&self.buyer);
+        let mut signers: Vec<&Keypair> = escrow_keypairs.iter().collect();
+        signers.push(&buyer);
+        let result = self.process(&instructions, &signers).await;
+        // Report program errors against index 0, after the create_account instructions.
+        let checkout_index = escrows.len() as u8;
+        let result = result.map_err(|error| match error {
+            TransactionError::InstructionError(index, error) if index == checkout_index => {
+                TransactionError::InstructionError(0, error)
+            }
+            error => error,
+        });
+        (escrows, result)
+    }
+}
+
+pub fn instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: lambda_escrow::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+pub fn with_receipt(mut instruction: Instruction, escrow_account: &Pubkey, payer: &Pubkey) -> Instruction {
+    instruction.accounts.push(AccountMeta::new(receipt_address(escrow_account), false));
+    instruction.accounts.push(AccountMeta::new(*payer, true));
+    instruction.accounts.push(AccountMeta::new_readonly(system_program::ID, false));
+    instruction
+}
+
+/// Asks `initialize` to mint the receivable token to the seller.
+pub fn with_receivable(mut instruction: Instruction, order_code: u64) -> Instruction {
+    instruction.accounts.push(AccountMeta::new(receivable_mint_address(order_code), false));
+    instruction.accounts.push(AccountMeta::new(receivable_token_address(order_code), false));
+    instruction
+}
+
+/// Points `exchange` or `adjudge_for_seller` of a tokenized escrow at the holder's receivable
+/// token account. Build them with the holder as seller, paid to its receive token account.
+pub fn with_holder(mut instruction: Instruction, holder_receivable_account: &Pubkey) -> Instruction {
+    instruction.accounts.push(AccountMeta::new_readonly(*holder_receivable_account, false));
+    instruction
+}
+
+pub fn initialize(escrow: &Escrow, mint: &Pubkey, amount: u64, trial_day: u16) -> Instruction {
+    let (vault_account, vault_account_bump) = vault_account(escrow.order_code);
+    instruction(
+        accounts::Initialize {
+            buyer: escrow.buyer,
+            payer: escrow.rent_payer,
+            seller: escrow.seller,
+            judge: escrow.judge,
+            config: config_address().0,
+            mint: *mint,
+            vault_account,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::Initialize {
+            _vault_account_bump: vault_account_bump,
+            amount,
+            order_code: escrow.order_code,
+            trial_day,
+        },
+    )
+}
+
+pub fn checkout(escrows: &[Escrow], mint: &Pubkey, cart_id: u64, entries: Vec<CartEntry>) -> Instruction {
+    let mut instruction = instruction(
+        accounts::Checkout {
+            buyer: escrows[0].buyer,
+            judge: escrows[0].judge,
+            config: config_address().0,
+            mint: *mint,
+            buyer_deposit_token_account: escrows[0].buyer_deposit_token_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::Checkout { cart_id, entries },
+    );
+    for escrow in escrows {
+        instruction.accounts.push(AccountMeta::new(escrow.escrow_account, false));
+        instruction.accounts.push(AccountMeta::new(vault_account(escrow.order_code).0, false));
+        instruction.accounts.push(AccountMeta::new_readonly(escrow.seller, false));
+        instruction.accounts.push(AccountMeta::new_readonly(escrow.seller_receive_token_account, false));
+    }
+    instruction
+}
+
+pub fn cancel_cart(escrows: &[Escrow], cart_id: u64) -> Instruction {
+    let mut instruction = instruction(
+        accounts::CancelCart {
+            buyer: escrows[0].buyer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrows[0].buyer_deposit_token_account,
+            token_program: spl_token::ID,
+        },
+        ix::CancelCart { cart_id },
+    );
+    for escrow in escrows {
+        instruction.accounts.push(AccountMeta::new(escrow.escrow_account, false));
+        instruction.accounts.push(AccountMeta::new(vault_account(escrow.order_code).0, false));
+        instruction.accounts.push(AccountMeta::new_readonly(vault_authority(escrow.order_code), false));
+    }
+    instruction
+}
+
+pub fn cancel(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::Cancel {
+            buyer: escrow.buyer,
+            rent_payer: escrow.rent_payer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Cancel { order_code: escrow.order_code },
+    )
+}
+
+pub fn cancel_partial(escrow: &Escrow, amount: u64) -> Instruction {
+    instruction(
+        accounts::CancelPartial {
+            buyer: escrow.buyer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::CancelPartial { order_code: escrow.order_code, amount },
+    )
+}
+
+pub fn charge_more(escrow: &Escrow, amount: u64) -> Instruction {
+    instruction(
+        accounts::ChargeMore {
+            buyer: escrow.buyer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            token_program: spl_token::ID,
+        },
+        ix::ChargeMore { order_code: escrow.order_code, amount },
+    )
+}
+
+pub fn request_surcharge(escrow: &Escrow, amount: u64, reason_hash: [u8; 32], deadline: i64) -> Instruction {
+    instruction(
+        accounts::RequestSurcharge { seller: escrow.fulfiller, escrow_account: escrow.escrow_account },
+        ix::RequestSurcharge { order_code: escrow.order_code, amount, reason_hash, deadline },
+    )
+}
+
+pub fn approve_surcharge(escrow: &Escrow, amount: u64) -> Instruction {
+    instruction(
+        accounts::ChargeMore {
+            buyer: escrow.buyer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            token_program: spl_token::ID,
+        },
+        ix::ApproveSurcharge { order_code: escrow.order_code, amount },
+    )
+}
+
+pub fn reject_surcharge(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::RejectSurcharge { buyer: escrow.buyer, escrow_account: escrow.escrow_account },
+        ix::RejectSurcharge { order_code: escrow.order_code },
+    )
+}
+
+pub fn shipping(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::Shipping {
+            buyer: escrow.buyer,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.fulfiller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Shipping { order_code: escrow.order_code },
+    )
+}
+
+pub fn delivered(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::Delivered {
+            buyer: escrow.buyer,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Delivered { order_code: escrow.order_code },
+    )
+}
+
+pub fn refund(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::Refund {
+            buyer: escrow.buyer,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Refund { order_code: escrow.order_code },
+    )
+}
+
+pub fn refund_partial(escrow: &Escrow, amount: u64) -> Instruction {
+    instruction(
+        accounts::RefundPartial {
+            buyer: escrow.buyer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            token_program: spl_token::ID,
+        },
+        ix::RefundPartial { order_code: escrow.order_code, amount },
+    )
+}
+
+pub fn exchange(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::Exchange {
+            buyer: escrow.buyer,
+            rent_payer: escrow.rent_payer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            token_program: spl_token::ID,
+        },
+        ix::Exchange,
+    )
+}
+
+pub fn settle_batch(escrows: &[Escrow]) -> Instruction {
+    let mut instruction = instruction(
+        accounts::SettleBatch {
+            seller: escrows[0].seller,
+            config: config_address().0,
+            seller_receive_token_account: escrows[0].seller_receive_token_account,
+            token_program: spl_token::ID,
+        },
+        ix::SettleBatch,
+    );
+    for escrow in escrows {
+        instruction.accounts.push(AccountMeta::new(escrow.escrow_account, false));
+        instruction.accounts.push(AccountMeta::new(vault_account(escrow.order_code).0, false));
+        instruction.accounts.push(AccountMeta::new_readonly(vault_authority(escrow.order_code), false));
+        instruction.accounts.push(AccountMeta::new(escrow.rent_payer, false));
+    }
+    instruction
+}
+
+pub fn set_line_items(escrow: &Escrow, items: Vec<NewLineItem>) -> Instruction {
+    let (line_items, line_items_bump) = line_items_address(escrow.order_code);
+    instruction(
+        accounts::SetLineItems {
+            buyer: escrow.buyer,
+            escrow_account: escrow.escrow_account,
+            line_items,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        ix::SetLineItems {
+            _line_items_bump: line_items_bump,
+            order_code: escrow.order_code,
+            items,
+        },
+    )
+}
+
+pub fn ship_items(escrow: &Escrow, indexes: Vec<u8>) -> Instruction {
+    instruction(
+        accounts::ShipItems {
+            seller: escrow.fulfiller,
+            escrow_account: escrow.escrow_account,
+            line_items: line_items_address(escrow.order_code).0,
+        },
+        ix::ShipItems { order_code: escrow.order_code, indexes },
+    )
+}
+
+pub fn deliver_items(escrow: &Escrow, indexes: Vec<u8>) -> Instruction {
+    instruction(
+        accounts::DeliverItems {
+            buyer: escrow.buyer,
+            escrow_account: escrow.escrow_account,
+            line_items: line_items_address(escrow.order_code).0,
+        },
+        ix::DeliverItems { order_code: escrow.order_code, indexes },
+    )
+}
+
+pub fn settle_items(escrow: &Escrow, indexes: Vec<u8>) -> Instruction {
+    instruction(
+        accounts::SettleItems {
+            seller: escrow.seller,
+            config: config_address().0,
+            buyer: escrow.buyer,
+            rent_payer: escrow.rent_payer,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            line_items: line_items_address(escrow.order_code).0,
+            token_program: spl_token::ID,
+        },
+        ix::SettleItems { order_code: escrow.order_code, indexes },
+    )
+}
+
+pub fn cancel_items(escrow: &Escrow, indexes: Vec<u8>) -> Instruction {
+    instruction(
+        accounts::CancelItems {
+            buyer: escrow.buyer,
+            rent_payer: escrow.rent_payer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            line_items: line_items_address(escrow.order_code).0,
+            token_program: spl_token::ID,
+        },
+        ix::CancelItems { order_code: escrow.order_code, indexes },
+    )
+}
+
+pub fn set_vesting(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::SetVesting {
+            buyer: escrow.buyer,
+            escrow_account: escrow.escrow_account,
+        },
+        ix::SetVesting { order_code: escrow.order_code },
+    )
+}
+
+pub fn withdraw_vested(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::WithdrawVested {
+            seller: escrow.seller,
+            config: config_address().0,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            token_program: spl_token::ID,
+        },
+        ix::WithdrawVested { order_code: escrow.order_code },
+    )
+}
+
+pub fn dispute(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::Dispute {
+            buyer: escrow.buyer,
+            config: config_address().0,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            token_program: spl_token::ID,
+        },
+        ix::Dispute { order_code: escrow.order_code },
+    )
+}
+
+/// Ed25519 program instruction with `signer`'s signature of `intent` for `escrow`, key,
+/// signature and message all in its own data.
+pub fn sign_intent(signer: &Keypair, escrow: &Escrow, intent: &Intent) -> Instruction {
+    let message = intent.message(&escrow.escrow_account);
+    let signature = signer.sign_message(&message);
+    let this_instruction = u16::MAX;
+    let mut data = vec![1u8, 0];
+    for field in &[48u16, this_instruction, 16, this_instruction, 112, message.len() as u16, this_instruction] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    data.extend_from_slice(signer.pubkey().as_ref());
+    data.extend_from_slice(signature.as_ref());
+    data.extend_from_slice(&message);
+    Instruction {
+        program_id: ed25519_program::ID,
+        accounts: Vec::new(),
+        data,
+    }
+}
+
+pub fn intent_delegate() -> Pubkey {
+    Pubkey::find_program_address(&[b"intent-delegate".as_ref()], &lambda_escrow::ID).0
+}
+
+pub fn relay_delivered(escrow: &Escrow, relayer: &Pubkey, intent: Intent) -> Instruction {
+    instruction(
+        accounts::RelayDelivered {
+            relayer: *relayer,
+            escrow_account: escrow.escrow_account,
+            instructions: sysvar::instructions::ID,
+        },
+        ix::RelayDelivered { intent },
+    )
+}
+
+pub fn relay_cancel(escrow: &Escrow, relayer: &Pubkey, intent: Intent) -> Instruction {
+    instruction(
+        accounts::RelayCancel {
+            relayer: *relayer,
+            buyer: escrow.buyer,
+            rent_payer: escrow.rent_payer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            escrow_account: escrow.escrow_account,
+            instructions: sysvar::instructions::ID,
+            token_program: spl_token::ID,
+        },
+        ix::RelayCancel { intent },
+    )
+}
+
+pub fn relay_charge_more(escrow: &Escrow, relayer: &Pubkey, intent: Intent) -> Instruction {
+    instruction(
+        accounts::RelayChargeMore {
+            relayer: *relayer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            intent_delegate: intent_delegate(),
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            instructions: sysvar::instructions::ID,
+            token_program: spl_token::ID,
+        },
+        ix::RelayChargeMore { intent },
+    )
+}
+
+pub fn transfer_receivable(escrow: &Escrow, new_seller: &Pubkey, new_seller_receive_token_account: &Pubkey) -> Instruction {
+    instruction(
+        accounts::TransferReceivable {
+            seller: escrow.seller,
+            config: config_address().0,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            new_seller: *new_seller,
+            new_seller_receive_token_account: *new_seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+        },
+        ix::TransferReceivable { order_code: escrow.order_code },
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_campaign(
+    seller: &Pubkey,
+    judge: &Pubkey,
+    mint: &Pubkey,
+    seller_receive_token_account: &Pubkey,
+    campaign_id: u64,
+    price: u64,
+    threshold: u32,
+    deadline: i64,
+    conversion_deadline: i64,
+) -> Instruction {
+    let (campaign, campaign_bump) = campaign_address(campaign_id);
+    let (campaign_vault, campaign_vault_bump) = campaign_vault_address(campaign_id);
+    instruction(
+        accounts::CreateCampaign {
+            seller: *seller,
+            judge: *judge,
+            config: config_address().0,
+            mint: *mint,
+            seller_receive_token_account: *seller_receive_token_account,
+            campaign,
+            campaign_vault,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::CreateCampaign {
+            _campaign_bump: campaign_bump,
+            _campaign_vault_bump: campaign_vault_bump,
+            campaign_id,
+            price,
+            threshold,
+            deadline,
+            conversion_deadline,
+            trial_day: 0,
+        },
+    )
+}
+
+pub fn join_campaign(buyer: &Pubkey, buyer_deposit_token_account: &Pubkey, campaign_id: u64) -> Instruction {
+    let (deposit, deposit_bump) = campaign_deposit_address(campaign_id, buyer);
+    instruction(
+        accounts::JoinCampaign {
+            buyer: *buyer,
+            config: config_address().0,
+            campaign: campaign_address(campaign_id).0,
+            campaign_vault: campaign_vault_address(campaign_id).0,
+            buyer_deposit_token_account: *buyer_deposit_token_account,
+            deposit,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::JoinCampaign { _deposit_bump: deposit_bump, campaign_id },
+    )
+}
+
+/// `escrow` is the one the deposit becomes, its buyer the depositor, its seller the campaign's
+/// and its rent payer who converts it.
+pub fn convert_campaign_deposit(escrow: &Escrow, mint: &Pubkey, campaign_id: u64) -> Instruction {
+    let (vault_account, vault_account_bump) = vault_account(escrow.order_code);
+    instruction(
+        accounts::ConvertCampaignDeposit {
+            payer: escrow.rent_payer,
+            seller: escrow.seller,
+            buyer: escrow.buyer,
+            config: config_address().0,
+            campaign: campaign_address(campaign_id).0,
+            campaign_vault: campaign_vault_address(campaign_id).0,
+            deposit: campaign_deposit_address(campaign_id, &escrow.buyer).0,
+            mint: *mint,
+            vault_account,
+            escrow_account: escrow.escrow_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::ConvertCampaignDeposit {
+            _vault_account_bump: vault_account_bump,
+            campaign_id,
+            order_code: escrow.order_code,
+        },
+    )
+}
+
+pub fn refund_campaign_deposit(buyer: &Pubkey, buyer_deposit_token_account: &Pubkey, campaign_id: u64) -> Instruction {
+    instruction(
+        accounts::RefundCampaignDeposit {
+            buyer: *buyer,
+            config: config_address().0,
+            campaign: campaign_address(campaign_id).0,
+            campaign_vault: campaign_vault_address(campaign_id).0,
+            buyer_deposit_token_account: *buyer_deposit_token_account,
+            deposit: campaign_deposit_address(campaign_id, buyer).0,
+            token_program: spl_token::ID,
+        },
+        ix::RefundCampaignDeposit { campaign_id },
+    )
+}
+
+pub fn close_campaign(seller: &Pubkey, campaign_id: u64) -> Instruction {
+    instruction(
+        accounts::CloseCampaign {
+            seller: *seller,
+            campaign: campaign_address(campaign_id).0,
+            campaign_vault: campaign_vault_address(campaign_id).0,
+            token_program: spl_token::ID,
+        },
+        ix::CloseCampaign { campaign_id },
+    )
+}
+
+pub fn ship_hash_locked(escrow: &Escrow, secret_hash: [u8; 32], reveal_deadline: i64) -> Instruction {
+    instruction(
+        accounts::Shipping {
+            buyer: escrow.buyer,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.fulfiller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::ShipHashLocked { order_code: escrow.order_code, secret_hash, reveal_deadline },
+    )
+}
+
+pub fn reveal_and_claim(escrow: &Escrow, secret: &[u8]) -> Instruction {
+    instruction(
+        accounts::RevealAndClaim { seller: escrow.fulfiller, escrow_account: escrow.escrow_account },
+        ix::RevealAndClaim { order_code: escrow.order_code, secret: secret.to_vec() },
+    )
+}
+
+pub fn reclaim_unrevealed(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::ReclaimUnrevealed {
+            buyer: escrow.buyer,
+            rent_payer: escrow.rent_payer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::ReclaimUnrevealed { order_code: escrow.order_code },
+    )
+}
+
+/// The seller locks `amount` from `seller_receive_token_account`, released to the buyer's
+/// `buyer_deposit_token_account`.
+pub fn initialize_seller_funded(escrow: &Escrow, mint: &Pubkey, amount: u64, payment_window: i64) -> Instruction {
+    let (vault_account, vault_account_bump) = vault_account(escrow.order_code);
+    instruction(
+        accounts::InitializeSellerFunded {
+            seller: escrow.seller,
+            payer: escrow.rent_payer,
+            buyer: escrow.buyer,
+            judge: escrow.judge,
+            config: config_address().0,
+            mint: *mint,
+            vault_account,
+            buyer_receive_token_account: escrow.buyer_deposit_token_account,
+            seller_deposit_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::InitializeSellerFunded {
+            _vault_account_bump: vault_account_bump,
+            amount,
+            order_code: escrow.order_code,
+            payment_window,
+        },
+    )
+}
+
+pub fn mark_paid(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::MarkPaid { buyer: escrow.buyer, escrow_account: escrow.escrow_account },
+        ix::MarkPaid { order_code: escrow.order_code },
+    )
+}
+
+pub fn release(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::Release {
+            seller: escrow.seller,
+            rent_payer: escrow.rent_payer,
+            config: config_address().0,
+            buyer_receive_token_account: escrow.buyer_deposit_token_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Release { order_code: escrow.order_code },
+    )
+}
+
+pub fn reclaim_unpaid(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::ReclaimUnpaid {
+            seller: escrow.seller,
+            rent_payer: escrow.rent_payer,
+            config: config_address().0,
+            seller_deposit_token_account: escrow.seller_receive_token_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::ReclaimUnpaid { order_code: escrow.order_code },
+    )
+}
+
+pub fn initialize_double_deposit(
+    escrow: &Escrow,
+    mint: &Pubkey,
+    price: u64,
+    buyer_collateral: u64,
+    seller_collateral: u64,
+    long_stop: i64,
+    long_stop_recipient: Option<&Pubkey>,
+) -> Instruction {
+    let (vault_account, vault_account_bump) = vault_account(escrow.order_code);
+    let mut instruction = instruction(
+        accounts::InitializeDoubleDeposit {
+            buyer: escrow.buyer,
+            seller: escrow.seller,
+            payer: escrow.rent_payer,
+            config: config_address().0,
+            mint: *mint,
+            vault_account,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::InitializeDoubleDeposit {
+            _vault_account_bump: vault_account_bump,
+            price,
+            order_code: escrow.order_code,
+            buyer_collateral,
+            seller_collateral,
+            long_stop,
+        },
+    );
+    if let Some(recipient) = long_stop_recipient {
+        instruction.accounts.push(AccountMeta::new_readonly(*recipient, false));
+    }
+    instruction
+}
+
+fn mutual_settlement(escrow: &Escrow) -> accounts::MutualSettlement {
+    accounts::MutualSettlement {
+        buyer: escrow.buyer,
+        seller: escrow.seller,
+        rent_payer: escrow.rent_payer,
+        config: config_address().0,
+        buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+        seller_receive_token_account: escrow.seller_receive_token_account,
+        vault_account: vault_account(escrow.order_code).0,
+        vault_authority: vault_authority(escrow.order_code),
+        escrow_account: escrow.escrow_account,
+        token_program: spl_token::ID,
+    }
+}
+
+pub fn settle(escrow: &Escrow) -> Instruction {
+    instruction(mutual_settlement(escrow), ix::Settle { order_code: escrow.order_code })
+}
+
+pub fn mutual_cancel(escrow: &Escrow) -> Instruction {
+    instruction(mutual_settlement(escrow), ix::MutualCancel { order_code: escrow.order_code })
+}
+
+/// Sends the vault to `recipient` when given, burns it otherwise.
+pub fn expire_long_stop(escrow: &Escrow, mint: &Pubkey, recipient: Option<&Pubkey>) -> Instruction {
+    let mut instruction = instruction(
+        accounts::ExpireLongStop {
+            rent_payer: escrow.rent_payer,
+            config: config_address().0,
+            mint: *mint,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::ExpireLongStop { order_code: escrow.order_code },
+    );
+    if let Some(recipient) = recipient {
+        instruction.accounts.push(AccountMeta::new(*recipient, false));
+    }
+    instruction
+}
+
+pub fn initialize_rental(
+    escrow: &Escrow,
+    mint: &Pubkey,
+    amount: u64,
+    rental_end: i64,
+    claim_window_day: u16,
+) -> Instruction {
+    let (vault_account, vault_account_bump) = vault_account(escrow.order_code);
+    instruction(
+        accounts::InitializeRental {
+            buyer: escrow.buyer,
+            payer: escrow.rent_payer,
+            seller: escrow.seller,
+            judge: escrow.judge,
+            config: config_address().0,
+            mint: *mint,
+            vault_account,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            token_program: spl_token::ID,
+        },
+        ix::InitializeRental {
+            _vault_account_bump: vault_account_bump,
+            amount,
+            order_code: escrow.order_code,
+            rental_end,
+            claim_window_day,
+        },
+    )
+}
+
+pub fn claim_damages(escrow: &Escrow, amount: u64, evidence_hash: [u8; 32]) -> Instruction {
+    instruction(
+        accounts::ClaimDamages { seller: escrow.seller, escrow_account: escrow.escrow_account },
+        ix::ClaimDamages { order_code: escrow.order_code, amount, evidence_hash },
+    )
+}
+
+pub fn accept_damages(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::AcceptDamages {
+            buyer: escrow.buyer,
+            rent_payer: escrow.rent_payer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::AcceptDamages { order_code: escrow.order_code },
+    )
+}
+
+pub fn dispute_damages(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::DisputeDamages {
+            buyer: escrow.buyer,
+            config: config_address().0,
+            escrow_account: escrow.escrow_account,
+        },
+        ix::DisputeDamages { order_code: escrow.order_code },
+    )
+}
+
+pub fn settle_damages(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::SettleDamages {
+            rent_payer: escrow.rent_payer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::SettleDamages { order_code: escrow.order_code },
+    )
+}
+
+pub fn return_deposit(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::ReturnDeposit {
+            rent_payer: escrow.rent_payer,
+            config: config_address().0,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::ReturnDeposit { order_code: escrow.order_code },
+    )
+}
+
+pub fn adjudge(escrow: &Escrow, status: u8) -> Instruction {
+    instruction(
+        accounts::Adjudge {
+            judge: escrow.judge,
+            buyer: escrow.buyer,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::Adjudge { order_code: escrow.order_code, status },
+    )
+}
+
+pub fn adjudge_partial(escrow: &Escrow, amount: u64) -> Instruction {
+    instruction(
+        accounts::AdjudgePartial {
+            judge: escrow.judge,
+            config: config_address().0,
+            buyer: escrow.buyer,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            token_program: spl_token::ID,
+        },
+        ix::AdjudgePartial { order_code: escrow.order_code, amount },
+    )
+}
+
+pub fn adjudge_for_buyer(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::AdjudgeForBuyer {
+            judge: escrow.judge,
+            config: config_address().0,
+            buyer: escrow.buyer,
+            rent_payer: escrow.rent_payer,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            token_program: spl_token::ID,
+        },
+        ix::AdjudgeForBuyer { order_code: escrow.order_code },
+    )
+}
+
+pub fn adjudge_for_seller(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::AdjudgeForSeller {
+            judge: escrow.judge,
+            config: config_address().0,
+            buyer: escrow.buyer,
+            rent_payer: escrow.rent_payer,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+            vault_authority: vault_authority(escrow.order_code),
+            token_program: spl_token::ID,
+        },
+        ix::AdjudgeForSeller { order_code: escrow.order_code },
+    )
+}
+
+pub fn update_trial_day(escrow: &Escrow, trial_day: u16) -> Instruction {
+    instruction(
+        accounts::UpdateTrialDay {
+            judge: escrow.judge,
+            buyer: escrow.buyer,
+            buyer_deposit_token_account: escrow.buyer_deposit_token_account,
+            seller: escrow.seller,
+            seller_receive_token_account: escrow.seller_receive_token_account,
+            escrow_account: escrow.escrow_account,
+            token_program: spl_token::ID,
+        },
+        ix::UpdateTrialDay { order_code: escrow.order_code, trial_day },
+    )
+}
+
+pub fn sync_vault(escrow: &Escrow) -> Instruction {
+    instruction(
+        accounts::SyncVault {
+            judge: escrow.judge,
+            escrow_account: escrow.escrow_account,
+            vault_account: vault_account(escrow.order_code).0,
+        },
+        ix::SyncVault { order_code: escrow.order_code },
+    )
+}
+
+pub fn initialize_config(admin: &Pubkey, compliance_authority: &Pubkey, judge_change_delay: i64) -> Instruction {
+    let (config, config_bump) = config_address();
+    instruction(
+        accounts::InitializeConfig {
+            admin: *admin,
+            compliance_authority: *compliance_authority,
+            config,
+            program_data: program_data_address(),
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        ix::InitializeConfig { _config_bump: config_bump, judge_change_delay },
+    )
+}
+
+pub fn update_config(admin: &Pubkey, new_admin: &Pubkey, compliance_authority: &Pubkey, judge_change_delay: i64) -> Instruction {
+    instruction(
+        accounts::UpdateConfig {
+            admin: *admin,
+            new_admin: *new_admin,
+            compliance_authority: *compliance_authority,
+            config: config_address().0,
+        },
+        ix::UpdateConfig { judge_change_delay },
+    )
+}
+
+pub fn set_paused(admin: &Pubkey, paused: bool) -> Instruction {
+    instruction(
+        accounts::SetPaused { admin: *admin, config: config_address().0 },
+        ix::SetPaused { paused },
+    )
+}
+
+pub fn set_frozen(escrow: &Escrow, compliance_authority: &Pubkey, frozen: bool) -> Instruction {
+    instruction(
+        accounts::SetFrozen {
+            compliance_authority: *compliance_authority,
+            config: config_address().0,
+            escrow_account: escrow.escrow_account,
+        },
+        ix::SetFrozen { order_code: escrow.order_code, frozen },
+    )
+}
+
+pub fn change_judge(escrow: &Escrow, new_judge: &Pubkey) -> Instruction {
+    instruction(
+        accounts::ChangeJudge {
+            buyer: escrow.buyer,
+            seller: escrow.seller,
+            new_judge: *new_judge,
+            escrow_account: escrow.escrow_account,
+        },
+        ix::ChangeJudge { order_code: escrow.order_code },
+    )
+}
+
+pub fn request_judge_change(escrow: &Escrow, admin: &Pubkey, new_judge: &Pubkey) -> Instruction {
+    instruction(
+        accounts::RequestJudgeChange {
+            admin: *admin,
+            config: config_address().0,
+            new_judge: *new_judge,
+            escrow_account: escrow.escrow_account,
+        },
+        ix::RequestJudgeChange { order_code: escrow.order_code },
+    )
+}
+
+pub fn execute_judge_change(escrow: &Escrow, admin: &Pubkey) -> Instruction {
+    instruction(
+        accounts::ExecuteJudgeChange {
+            admin: *admin,
+            config: config_address().0,
+            escrow_account: escrow.escrow_account,
+        },
+        ix::ExecuteJudgeChange { order_code: escrow.order_code },
+    )
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+const ORDER_CODE: u64 = 48;
+const PRICE: u64 = 1000;
+const BUYER_COLLATERAL: u64 = 200;
+const SELLER_COLLATERAL: u64 = 300;
+const LONG_STOP_IN: i64 = 3600;
+const DONATION: u64 = 50;
+
+/// A double-deposit escrow with both collaterals locked, the seller's freshly minted.
+async fn initialize(env: &mut Env, long_stop: i64, long_stop_recipient: Option<&Pubkey>) -> Escrow {
+    let seller_token = env.seller_token;
+    env.mint_to(&seller_token, SELLER_COLLATERAL).await;
+    env.try_initialize_double_deposit(
+        ORDER_CODE,
+        PRICE,
+        BUYER_COLLATERAL,
+        SELLER_COLLATERAL,
+        long_stop,
+        long_stop_recipient,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn settle_pays_both_parties() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let admin = env.admin.pubkey();
+    let result = env.try_initialize_double_deposit(ORDER_CODE, PRICE, BUYER_COLLATERAL, 0, 0, None).await;
+    assert_program_error(result.map(|_| ()), ErrorCode::InvalidCollateral);
+    let escrow = initialize(&mut env, 0, None).await;
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - PRICE - BUYER_COLLATERAL);
+    assert_eq!(env.seller_balance().await, 0);
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert!(escrow_account.double_deposit);
+    assert_eq!(escrow_account.judge_key, Pubkey::default());
+    assert_eq!(escrow_account.amount, PRICE + BUYER_COLLATERAL + SELLER_COLLATERAL);
+
+    // Neither the judge lifecycle nor a judge change applies.
+    let result = env.process(&[cancel(&escrow)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::DoubleDeposit);
+    let judge = env.judge.pubkey();
+    let result = env.process(&[change_judge(&escrow, &judge)], &[&buyer, &seller]).await;
+    assert_program_error(result, ErrorCode::DoubleDeposit);
+
+    env.process(&[with_receipt(settle(&escrow), &escrow.escrow_account, &admin)], &[&buyer, &seller]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - PRICE);
+    assert_eq!(env.seller_balance().await, PRICE + SELLER_COLLATERAL);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+    let receipt = env.receipt(&escrow.escrow_account).await;
+    assert_eq!(
+        (receipt.buyer_payout, receipt.seller_payout, receipt.settlement_path),
+        (BUYER_COLLATERAL, PRICE + SELLER_COLLATERAL, 7)
+    );
+}
+
+#[tokio::test]
+async fn mutual_cancel_returns_the_price_to_the_buyer() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let admin = env.admin.pubkey();
+    let escrow = initialize(&mut env, 0, None).await;
+
+    // Without a long stop nobody but the parties can end the escrow.
+    let mint = env.mint.pubkey();
+    let result = env.process(&[expire_long_stop(&escrow, &mint, None)], &[]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    let instruction = with_receipt(mutual_cancel(&escrow), &escrow.escrow_account, &admin);
+    env.process(&[instruction], &[&buyer, &seller]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    assert_eq!(env.seller_balance().await, SELLER_COLLATERAL);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    let receipt = env.receipt(&escrow.escrow_account).await;
+    assert_eq!(
+        (receipt.buyer_payout, receipt.seller_payout, receipt.settlement_path),
+        (PRICE + BUYER_COLLATERAL, SELLER_COLLATERAL, 8)
+    );
+}
+
+#[tokio::test]
+async fn long_stop_donates_the_escrow() {
+    let mut env = Env::new(0).await;
+    let judge = env.judge.pubkey();
+    let recipient = env.create_token_account(&judge).await;
+    let mint = env.mint.pubkey();
+    let now = env.now().await;
+    let result = env
+        .try_initialize_double_deposit(ORDER_CODE, PRICE, BUYER_COLLATERAL, SELLER_COLLATERAL, 0, Some(&recipient))
+        .await;
+    assert_program_error(result.map(|_| ()), ErrorCode::InvalidLongStop);
+    let escrow = initialize(&mut env, now + LONG_STOP_IN, Some(&recipient)).await;
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.long_stop_recipient, recipient);
+
+    let result = env.process(&[expire_long_stop(&escrow, &mint, Some(&recipient))], &[]).await;
+    assert_program_error(result, ErrorCode::LongStopNotReached);
+
+    env.warp_forward(LONG_STOP_IN).await;
+    let result = env.process(&[expire_long_stop(&escrow, &mint, None)], &[]).await;
+    assert_program_error(result, ErrorCode::InvalidLongStop);
+    env.process(&[expire_long_stop(&escrow, &mint, Some(&recipient))], &[]).await.unwrap();
+    assert_eq!(env.token_balance(&recipient).await, PRICE + BUYER_COLLATERAL + SELLER_COLLATERAL);
+    assert_eq!(env.seller_balance().await, 0);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+}
+
+#[tokio::test]
+async fn long_stop_burns_the_escrow_without_a_recipient() {
+    let mut env = Env::new(0).await;
+    let admin = env.admin.pubkey();
+    let mint = env.mint.pubkey();
+    let now = env.now().await;
+    let escrow = initialize(&mut env, now + LONG_STOP_IN, None).await;
+
+    env.warp_forward(LONG_STOP_IN).await;
+    let instruction = with_receipt(expire_long_stop(&escrow, &mint, None), &escrow.escrow_account, &admin);
+    env.process(&[instruction], &[]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - PRICE - BUYER_COLLATERAL);
+    assert_eq!(env.seller_balance().await, 0);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+    let receipt = env.receipt(&escrow.escrow_account).await;
+    assert_eq!((receipt.buyer_payout, receipt.seller_payout, receipt.settlement_path), (0, 0, 9));
+}
+
+#[tokio::test]
+async fn donations_to_the_vault_are_paid_out() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let admin = env.admin.pubkey();
+    let mint = env.mint.pubkey();
+    let now = env.now().await;
+    let escrow = initialize(&mut env, 0, None).await;
+
+    // Tokens sent straight to the vault, there is no judge to sync_vault them.
+    env.mint_to(&vault_account(ORDER_CODE).0, DONATION).await;
+    env.process(&[with_receipt(settle(&escrow), &escrow.escrow_account, &admin)], &[&buyer, &seller]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - PRICE);
+    assert_eq!(env.seller_balance().await, PRICE + SELLER_COLLATERAL + DONATION);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+    let receipt = env.receipt(&escrow.escrow_account).await;
+    assert_eq!((receipt.buyer_payout, receipt.seller_payout), (BUYER_COLLATERAL, PRICE + SELLER_COLLATERAL + DONATION));
+
+    // The long stop burns the donation with the rest of the vault.
+    let escrow = initialize(&mut env, now + LONG_STOP_IN, None).await;
+    env.mint_to(&vault_account(ORDER_CODE).0, DONATION).await;
+    env.warp_forward(LONG_STOP_IN).await;
+    env.process(&[expire_long_stop(&escrow, &mint, None)], &[]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - 2 * PRICE - BUYER_COLLATERAL);
+    assert_eq!(env.seller_balance().await, PRICE + SELLER_COLLATERAL + DONATION);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+}
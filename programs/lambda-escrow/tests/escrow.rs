@@ -0,0 +1,233 @@
+mod common;
+
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+
+const ORDER_CODE: u64 = 99;
+const AMOUNT: u64 = 1000;
+const AMOUNT_PARTIAL: u64 = 400;
+
+#[tokio::test]
+async fn initialize_and_cancel() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!(escrow_account.buyer_key, env.buyer.pubkey());
+    assert_eq!(escrow_account.seller_key, env.seller.pubkey());
+    assert_eq!(escrow_account.judge_key, env.judge.pubkey());
+    assert_eq!(escrow_account.amount, AMOUNT);
+    assert_eq!(escrow_account.order_code, ORDER_CODE);
+    assert_eq!(escrow_account.status, 0);
+    assert_eq!(env.token_balance(&vault_account(ORDER_CODE).0).await, AMOUNT);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - AMOUNT);
+
+    let buyer = clone_keypair(&env.buyer);
+    env.process(&[cancel(&escrow)], &[&buyer]).await.unwrap();
+
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+}
+
+#[tokio::test]
+async fn shipping_delivered_and_exchange_after_trial() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 3).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.status, 1);
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.status, 2);
+
+    // Still in the 3 day trial.
+    let result = env.process(&[exchange(&escrow)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::InTrialDay);
+    env.warp_forward(2 * SECONDS_PER_DAY).await;
+    let result = env.process(&[exchange(&escrow)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::InTrialDay);
+
+    env.warp_forward(SECONDS_PER_DAY).await;
+    env.process(&[exchange(&escrow)], &[&seller]).await.unwrap();
+
+    assert_eq!(env.seller_balance().await, AMOUNT);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - AMOUNT);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+}
+
+#[tokio::test]
+async fn exchange_before_delivered_fails() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let seller = clone_keypair(&env.seller);
+
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    let result = env.process(&[exchange(&escrow)], &[&seller]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+}
+
+#[tokio::test]
+async fn refund_moves_back_to_new_and_cancel() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    // Buyer can not cancel a shipped order.
+    let result = env.process(&[cancel(&escrow)], &[&buyer]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    env.process(&[refund(&escrow)], &[&seller]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.status, 0);
+
+    env.process(&[cancel(&escrow)], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+}
+
+#[tokio::test]
+async fn cancel_partial_and_charge_more() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let buyer = clone_keypair(&env.buyer);
+
+    env.process(&[cancel_partial(&escrow, AMOUNT_PARTIAL)], &[&buyer]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, AMOUNT - AMOUNT_PARTIAL);
+    assert_eq!(env.token_balance(&vault_account(ORDER_CODE).0).await, AMOUNT - AMOUNT_PARTIAL);
+
+    env.process(&[charge_more(&escrow, 2 * AMOUNT_PARTIAL)], &[&buyer]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, AMOUNT + AMOUNT_PARTIAL);
+    assert_eq!(env.token_balance(&vault_account(ORDER_CODE).0).await, AMOUNT + AMOUNT_PARTIAL);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - AMOUNT - AMOUNT_PARTIAL);
+}
+
+#[tokio::test]
+async fn cancel_partial_can_not_underflow() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let buyer = clone_keypair(&env.buyer);
+
+    // The whole amount goes through cancel, never cancel_partial.
+    let result = env.process(&[cancel_partial(&escrow, AMOUNT)], &[&buyer]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    let result = env.process(&[cancel_partial(&escrow, AMOUNT + 1)], &[&buyer]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    let result = env.process(&[cancel_partial(&escrow, 0)], &[&buyer]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, AMOUNT);
+}
+
+#[tokio::test]
+async fn refund_partial_by_seller() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let seller = clone_keypair(&env.seller);
+
+    // Only once the order is shipped.
+    let result = env.process(&[refund_partial(&escrow, AMOUNT_PARTIAL)], &[&seller]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[refund_partial(&escrow, AMOUNT_PARTIAL)], &[&seller]).await.unwrap();
+
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, AMOUNT - AMOUNT_PARTIAL);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - AMOUNT + AMOUNT_PARTIAL);
+}
+
+#[tokio::test]
+async fn wrong_signer_is_rejected() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let attacker = Keypair::new();
+
+    // Attacker signs as the buyer.
+    let mut as_buyer = escrow;
+    as_buyer.buyer = attacker.pubkey();
+    let result = env.process(&[cancel(&as_buyer)], &[&attacker]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    // Attacker signs as the seller.
+    let mut as_seller = escrow;
+    as_seller.fulfiller = attacker.pubkey();
+    let result = env.process(&[shipping(&as_seller)], &[&attacker]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    // Buyer can not ship its own order.
+    let buyer = clone_keypair(&env.buyer);
+    let mut buyer_as_seller = escrow;
+    buyer_as_seller.fulfiller = env.buyer.pubkey();
+    let result = env.process(&[shipping(&buyer_as_seller)], &[&buyer]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.status, 0);
+}
+
+#[tokio::test]
+async fn wrong_vault_is_rejected() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let other = env.initialize(ORDER_CODE + 1, AMOUNT, 0).await;
+    let buyer = clone_keypair(&env.buyer);
+
+    // Vault of the other order is not owned by this order's vault authority.
+    let mut instruction = cancel(&escrow);
+    instruction.accounts[4].pubkey = vault_account(other.order_code).0;
+    let result = env.process(&[instruction], &[&buyer]).await;
+    assert_custom_error(result, spl_token::error::TokenError::OwnerMismatch as u32);
+
+    assert_eq!(env.token_balance(&vault_account(ORDER_CODE).0).await, AMOUNT);
+    assert_eq!(env.token_balance(&vault_account(ORDER_CODE + 1).0).await, AMOUNT);
+}
+
+#[tokio::test]
+async fn exchange_writes_settlement_receipt() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+    let instruction = with_receipt(exchange(&escrow), &escrow.escrow_account, &seller.pubkey());
+    env.process(&[instruction], &[&seller]).await.unwrap();
+
+    let receipt = env.receipt(&escrow.escrow_account).await;
+    assert_eq!(receipt.order_code, ORDER_CODE);
+    assert_eq!(receipt.escrow_key, escrow.escrow_account);
+    assert_eq!(receipt.buyer_payout, 0);
+    assert_eq!(receipt.seller_payout, AMOUNT);
+    assert_eq!(receipt.settlement_path, 1);
+    assert_eq!(receipt.fee, 0);
+    assert!(receipt.rent_refund > 0);
+}
+
+#[tokio::test]
+async fn receipt_per_escrow_with_a_reused_order_code() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let admin = env.admin.pubkey();
+    env.process(&[with_receipt(cancel(&escrow), &escrow.escrow_account, &admin)], &[&buyer]).await.unwrap();
+
+    // Once closed, the order code can be used again and gets its own receipt.
+    let escrow_keypair = Keypair::new();
+    let reused = env.escrow(&escrow_keypair, ORDER_CODE);
+    env.try_initialize(&reused, &escrow_keypair, AMOUNT_PARTIAL, 0).await.unwrap();
+
+    // Lamports sent to the receipt address beforehand do not block it.
+    let receipt_address = receipt_address(&reused.escrow_account);
+    env.process(&[system_instruction::transfer(&admin, &receipt_address, 1_000_000)], &[]).await.unwrap();
+    env.process(&[with_receipt(cancel(&reused), &reused.escrow_account, &admin)], &[&buyer]).await.unwrap();
+
+    assert_eq!(env.receipt(&escrow.escrow_account).await.buyer_payout, AMOUNT);
+    let receipt = env.receipt(&reused.escrow_account).await;
+    assert_eq!((receipt.escrow_key, receipt.buyer_payout), (reused.escrow_account, AMOUNT_PARTIAL));
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::hash::hash;
+use solana_sdk::signature::Signer;
+
+const ORDER_CODE: u64 = 64;
+const AMOUNT: u64 = 1000;
+const REVEAL_WITHIN: i64 = 3600;
+const SECRET: &[u8] = b"LICENSE-KEY-4F2A-91C0";
+
+#[tokio::test]
+async fn reveal_delivers_and_starts_the_trial() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 1).await;
+    let reveal_deadline = env.now().await + REVEAL_WITHIN;
+    let instruction = ship_hash_locked(&escrow, hash(SECRET).to_bytes(), reveal_deadline);
+    env.process(&[instruction], &[&seller]).await.unwrap();
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!(escrow_account.status, 1);
+    assert_eq!(escrow_account.secret_hash, hash(SECRET).to_bytes());
+    assert_eq!(escrow_account.reveal_deadline, reveal_deadline);
+
+    // Only the seller reveals, and only the committed secret.
+    let result = env.process(&[reveal_and_claim(&escrow, b"LICENSE-KEY-0000")], &[&seller]).await;
+    assert_program_error(result, ErrorCode::SecretMismatch);
+    let as_buyer = Escrow { fulfiller: buyer.pubkey(), ..escrow };
+    let result = env.process(&[reveal_and_claim(&as_buyer, SECRET)], &[&buyer]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    env.warp_forward(60).await;
+    env.process(&[reveal_and_claim(&escrow, SECRET)], &[&seller]).await.unwrap();
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!(escrow_account.status, 2);
+    assert_eq!(escrow_account.delivery_time, env.now().await);
+
+    // Revealed, the escrow settles after its trial like any delivered one.
+    let result = env.process(&[reclaim_unrevealed(&escrow)], &[]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    env.warp_forward(SECONDS_PER_DAY).await;
+    env.process(&[exchange(&escrow)], &[&seller]).await.unwrap();
+    assert_eq!(env.seller_balance().await, AMOUNT);
+}
+
+#[tokio::test]
+async fn unrevealed_escrow_goes_back_to_the_buyer() {
+    let mut env = Env::new(0).await;
+    let seller = clone_keypair(&env.seller);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let now = env.now().await;
+    let result = env.process(&[ship_hash_locked(&escrow, hash(SECRET).to_bytes(), now)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::InvalidHashLock);
+    let result = env.process(&[ship_hash_locked(&escrow, [0; 32], now + REVEAL_WITHIN)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::InvalidHashLock);
+    env.process(&[ship_hash_locked(&escrow, hash(SECRET).to_bytes(), now + REVEAL_WITHIN)], &[&seller])
+        .await
+        .unwrap();
+
+    let result = env.process(&[reclaim_unrevealed(&escrow)], &[]).await;
+    assert_program_error(result, ErrorCode::RevealDeadlineNotPassed);
+
+    env.warp_forward(REVEAL_WITHIN + 1).await;
+    let result = env.process(&[reveal_and_claim(&escrow, SECRET)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::RevealDeadlinePassed);
+
+    // No party has to sign, the admin only pays the fees here.
+    env.next_slot().await;
+    env.process(&[reclaim_unrevealed(&escrow)], &[]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+}
+
+#[tokio::test]
+async fn plain_shipping_is_not_reclaimable() {
+    let mut env = Env::new(0).await;
+    let seller = clone_keypair(&env.seller);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let now = env.now().await;
+
+    // A refund and a plain shipping drop the earlier hash lock.
+    env.process(&[ship_hash_locked(&escrow, hash(SECRET).to_bytes(), now + REVEAL_WITHIN)], &[&seller])
+        .await
+        .unwrap();
+    env.process(&[refund(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.secret_hash, [0; 32]);
+
+    env.warp_forward(REVEAL_WITHIN + 1).await;
+    let result = env.process(&[reclaim_unrevealed(&escrow)], &[]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    let result = env.process(&[reveal_and_claim(&escrow, SECRET)], &[&seller]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use common::*;
+use lambda_escrow::{ErrorCode, Intent};
+use solana_program_test::tokio;
+use solana_sdk::signature::{Keypair, Signer};
+
+const ORDER_CODE: u64 = 55;
+const AMOUNT: u64 = 1000;
+const VALIDITY: i64 = 600;
+
+fn intent(action: u8, amount: u64, nonce: u64, expiry: i64) -> Intent {
+    Intent { action, amount, nonce, expiry }
+}
+
+#[tokio::test]
+async fn relayer_sends_delivered_and_charge_more_for_the_buyer() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let relayer = Keypair::new();
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    let expiry = env.now().await + VALIDITY;
+
+    // The buyer only signs the intent, the relayer signs and pays for the transaction.
+    let delivered = intent(Intent::DELIVERED, 0, 1, expiry);
+    env.process(
+        &[sign_intent(&buyer, &escrow, &delivered), relay_delivered(&escrow, &relayer.pubkey(), delivered.clone())],
+        &[&relayer],
+    )
+    .await
+    .unwrap();
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!(escrow_account.status, 2);
+    assert_eq!(escrow_account.intent_nonce, 1);
+
+    // The deposit has to be approved to the intent delegate once.
+    let approve = spl_token::instruction::approve(
+        &spl_token::ID,
+        &escrow.buyer_deposit_token_account,
+        &intent_delegate(),
+        &escrow.buyer,
+        &[],
+        500,
+    )
+    .unwrap();
+    env.process(&[approve], &[&buyer]).await.unwrap();
+    let charge_more = intent(Intent::CHARGE_MORE, 300, 2, expiry);
+    env.process(
+        &[sign_intent(&buyer, &escrow, &charge_more), relay_charge_more(&escrow, &relayer.pubkey(), charge_more.clone())],
+        &[&relayer],
+    )
+    .await
+    .unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, AMOUNT + 300);
+    assert_eq!(env.token_balance(&vault_account(ORDER_CODE).0).await, AMOUNT + 300);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - AMOUNT - 300);
+
+    // An intent is used once.
+    env.next_slot().await;
+    let result = env
+        .process(
+            &[sign_intent(&buyer, &escrow, &charge_more), relay_charge_more(&escrow, &relayer.pubkey(), charge_more)],
+            &[&relayer],
+        )
+        .await;
+    assert_program_error_at(result, 1, ErrorCode::IntentNonceUsed);
+}
+
+#[tokio::test]
+async fn relayed_charge_more_only_pays_into_the_escrow_vault() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let relayer = Keypair::new();
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let approve = spl_token::instruction::approve(
+        &spl_token::ID,
+        &escrow.buyer_deposit_token_account,
+        &intent_delegate(),
+        &escrow.buyer,
+        &[],
+        300,
+    )
+    .unwrap();
+    env.process(&[approve], &[&buyer]).await.unwrap();
+    let charge_more = intent(Intent::CHARGE_MORE, 300, 1, env.now().await + VALIDITY);
+
+    // The relayer swaps in a token account of its own for the vault.
+    let foreign_vault = env.create_token_account(&relayer.pubkey()).await;
+    let mut relay = relay_charge_more(&escrow, &relayer.pubkey(), charge_more.clone());
+    relay.accounts[5].pubkey = foreign_vault;
+    let result = env.process(&[sign_intent(&buyer, &escrow, &charge_more), relay], &[&relayer]).await;
+    assert_program_error_at(result, 1, ErrorCode::VaultMismatch);
+
+    // Or a program of its own for the token program, which the delegate would sign for.
+    let mut relay = relay_charge_more(&escrow, &relayer.pubkey(), charge_more.clone());
+    relay.accounts[7].pubkey = Keypair::new().pubkey();
+    let result = env.process(&[sign_intent(&buyer, &escrow, &charge_more), relay], &[&relayer]).await;
+    assert_custom_error_at(result, 1, CONSTRAINT_RAW);
+    assert_eq!(env.token_balance(&foreign_vault).await, 0);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - AMOUNT);
+
+    env.process(
+        &[sign_intent(&buyer, &escrow, &charge_more), relay_charge_more(&escrow, &relayer.pubkey(), charge_more)],
+        &[&relayer],
+    )
+    .await
+    .unwrap();
+    assert_eq!(env.token_balance(&vault_account(ORDER_CODE).0).await, AMOUNT + 300);
+}
+
+#[tokio::test]
+async fn relayer_sends_cancel_for_the_buyer() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let relayer = Keypair::new();
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let expiry = env.now().await + VALIDITY;
+
+    let cancel = intent(Intent::CANCEL, 0, 1, expiry);
+    env.process(
+        &[sign_intent(&buyer, &escrow, &cancel), relay_cancel(&escrow, &relayer.pubkey(), cancel)],
+        &[&relayer],
+    )
+    .await
+    .unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+}
+
+#[tokio::test]
+async fn intent_must_be_signed_by_the_buyer_for_this_instruction() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let relayer = Keypair::new();
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let now = env.now().await;
+    let cancel = intent(Intent::CANCEL, 0, 1, now + VALIDITY);
+
+    // No signature, or a valid one by someone else.
+    let result = env.process(&[relay_cancel(&escrow, &relayer.pubkey(), cancel.clone())], &[&relayer]).await;
+    assert_program_error(result, ErrorCode::MissingIntentSignature);
+    let result = env
+        .process(
+            &[sign_intent(&seller, &escrow, &cancel), relay_cancel(&escrow, &relayer.pubkey(), cancel.clone())],
+            &[&relayer],
+        )
+        .await;
+    assert_program_error_at(result, 1, ErrorCode::MissingIntentSignature);
+
+    // The buyer signed a cancel, not a charge of more, nor a later expiry.
+    let result = env
+        .process(
+            &[sign_intent(&buyer, &escrow, &cancel), relay_charge_more(&escrow, &relayer.pubkey(), cancel.clone())],
+            &[&relayer],
+        )
+        .await;
+    assert_program_error_at(result, 1, ErrorCode::InvalidIntent);
+    let extended = intent(Intent::CANCEL, 0, 1, now + 2 * VALIDITY);
+    let result = env
+        .process(
+            &[sign_intent(&buyer, &escrow, &cancel), relay_cancel(&escrow, &relayer.pubkey(), extended)],
+            &[&relayer],
+        )
+        .await;
+    assert_program_error_at(result, 1, ErrorCode::MissingIntentSignature);
+
+    env.warp_forward(VALIDITY).await;
+    let result = env
+        .process(
+            &[sign_intent(&buyer, &escrow, &cancel), relay_cancel(&escrow, &relayer.pubkey(), cancel)],
+            &[&relayer],
+        )
+        .await;
+    assert_program_error_at(result, 1, ErrorCode::IntentExpired);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - AMOUNT);
+}
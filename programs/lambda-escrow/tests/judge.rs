@@ -0,0 +1,180 @@
+mod common;
+
+use common::*;
+use solana_program_test::tokio;
+use solana_sdk::signature::{Keypair, Signer};
+
+const ORDER_CODE: u64 = 99;
+const AMOUNT: u64 = 1000;
+const AMOUNT_PARTIAL: u64 = 400;
+
+#[tokio::test]
+async fn adjudge_sets_status() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let judge = clone_keypair(&env.judge);
+
+    env.process(&[adjudge(&escrow, 2)], &[&judge]).await.unwrap();
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!(escrow_account.status, 2);
+    assert!(escrow_account.delivery_time > 0);
+
+    env.process(&[adjudge(&escrow, 0)], &[&judge]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.status, 0);
+
+    // Closed is not a status the judge can set.
+    env.process(&[adjudge(&escrow, 3)], &[&judge]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.status, 0);
+}
+
+#[tokio::test]
+async fn adjudge_partial_returns_to_buyer() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let judge = clone_keypair(&env.judge);
+
+    env.process(&[adjudge_partial(&escrow, AMOUNT_PARTIAL)], &[&judge]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, AMOUNT - AMOUNT_PARTIAL);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - AMOUNT + AMOUNT_PARTIAL);
+
+    let result = env.process(&[adjudge_partial(&escrow, AMOUNT - AMOUNT_PARTIAL)], &[&judge]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+}
+
+#[tokio::test]
+async fn adjudge_for_buyer_closes_escrow() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let judge = clone_keypair(&env.judge);
+
+    env.process(&[adjudge_for_buyer(&escrow)], &[&judge]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+}
+
+#[tokio::test]
+async fn adjudge_for_seller_ignores_trial() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 7).await;
+    let judge = clone_keypair(&env.judge);
+
+    let instruction = with_receipt(adjudge_for_seller(&escrow), &escrow.escrow_account, &env.admin.pubkey());
+    env.process(&[instruction], &[&judge]).await.unwrap();
+    assert_eq!(env.seller_balance().await, AMOUNT);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+
+    let receipt = env.receipt(&escrow.escrow_account).await;
+    assert_eq!(receipt.judge_key, judge.pubkey());
+    assert_eq!(receipt.seller_payout, AMOUNT);
+    assert_eq!(receipt.settlement_path, 3);
+}
+
+#[tokio::test]
+async fn wrong_judge_is_rejected() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let attacker = Keypair::new();
+    let mut as_judge = escrow;
+    as_judge.judge = attacker.pubkey();
+
+    let result = env.process(&[adjudge(&as_judge, 2)], &[&attacker]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    let result = env.process(&[adjudge_partial(&as_judge, AMOUNT_PARTIAL)], &[&attacker]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    let result = env.process(&[adjudge_for_buyer(&as_judge)], &[&attacker]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    let result = env.process(&[adjudge_for_seller(&as_judge)], &[&attacker]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    let result = env.process(&[update_trial_day(&as_judge, 0)], &[&attacker]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    assert_eq!(env.token_balance(&vault_account(ORDER_CODE).0).await, AMOUNT);
+}
+
+#[tokio::test]
+async fn update_trial_day_unlocks_exchange() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 30).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let judge = clone_keypair(&env.judge);
+
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+    let result = env.process(&[exchange(&escrow)], &[&seller]).await;
+    assert_program_error(result, lambda_escrow::ErrorCode::InTrialDay);
+
+    env.process(&[update_trial_day(&escrow, 0)], &[&judge]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.trial_day, 0);
+    env.process(&[exchange(&escrow)], &[&seller]).await.unwrap();
+    assert_eq!(env.seller_balance().await, AMOUNT);
+}
+
+#[tokio::test]
+async fn change_judge_by_parties() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let judge = clone_keypair(&env.judge);
+    let new_judge = Keypair::new();
+
+    // Both parties have to sign.
+    let mut instruction = change_judge(&escrow, &new_judge.pubkey());
+    instruction.accounts[1].is_signer = false;
+    let result = env.process(&[instruction], &[&buyer]).await;
+    assert_custom_error(result, CONSTRAINT_SIGNER);
+
+    env.process(&[change_judge(&escrow, &new_judge.pubkey())], &[&buyer, &seller]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.judge_key, new_judge.pubkey());
+
+    let result = env.process(&[adjudge_for_buyer(&escrow)], &[&judge]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    let mut escrow = escrow;
+    escrow.judge = new_judge.pubkey();
+    env.process(&[adjudge_for_buyer(&escrow)], &[&new_judge]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+}
+
+#[tokio::test]
+async fn admin_judge_change_waits_for_timelock() {
+    let mut env = Env::new(SECONDS_PER_DAY).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let admin = clone_keypair(&env.admin);
+    let new_judge = Keypair::new();
+
+    // Nothing to execute yet.
+    let result = env.process(&[execute_judge_change(&escrow, &admin.pubkey())], &[&admin]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    env.process(&[request_judge_change(&escrow, &admin.pubkey(), &new_judge.pubkey())], &[&admin])
+        .await
+        .unwrap();
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!(escrow_account.pending_judge_key, new_judge.pubkey());
+    assert_eq!(escrow_account.judge_key, env.judge.pubkey());
+
+    let result = env.process(&[execute_judge_change(&escrow, &admin.pubkey())], &[&admin]).await;
+    assert_program_error(result, lambda_escrow::ErrorCode::InJudgeChangeTimelock);
+
+    env.warp_forward(SECONDS_PER_DAY).await;
+    env.process(&[execute_judge_change(&escrow, &admin.pubkey())], &[&admin]).await.unwrap();
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!(escrow_account.judge_key, new_judge.pubkey());
+    assert_eq!(escrow_account.judge_change_time, 0);
+}
+
+#[tokio::test]
+async fn only_admin_requests_judge_change() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let attacker = Keypair::new();
+
+    let result = env
+        .process(&[request_judge_change(&escrow, &attacker.pubkey(), &attacker.pubkey())], &[&attacker])
+        .await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.judge_change_time, 0);
+}
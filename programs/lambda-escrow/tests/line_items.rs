@@ -0,0 +1,127 @@
+mod common;
+
+use common::*;
+use lambda_escrow::{ErrorCode, NewLineItem};
+use solana_program_test::tokio;
+
+const ORDER_CODE: u64 = 42;
+const AMOUNT: u64 = 1000;
+const TRIAL_DAY: u16 = 2;
+
+fn item(sku: u8, quantity: u32, price: u64) -> NewLineItem {
+    NewLineItem { sku_hash: [sku; 32], quantity, price }
+}
+
+/// 2 x 200, 1 x 500 and 1 x 100, worth `AMOUNT`.
+fn items() -> Vec<NewLineItem> {
+    vec![item(1, 2, 200), item(2, 1, 500), item(3, 1, 100)]
+}
+
+#[tokio::test]
+async fn items_ship_deliver_and_settle_on_their_own() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, TRIAL_DAY).await;
+    env.process(&[set_line_items(&escrow, items())], &[&buyer]).await.unwrap();
+
+    let line_items = env.line_items(ORDER_CODE).await;
+    assert_eq!(line_items.escrow_key, escrow.escrow_account);
+    assert_eq!(line_items.items.len(), 3);
+    assert!(line_items.items.iter().all(|item| item.status == 0));
+
+    env.process(&[ship_items(&escrow, vec![0, 1])], &[&seller]).await.unwrap();
+    env.process(&[deliver_items(&escrow, vec![0])], &[&buyer]).await.unwrap();
+    let result = env.process(&[settle_items(&escrow, vec![0])], &[&seller]).await;
+    assert_program_error(result, ErrorCode::InTrialDay);
+
+    // Item 1 is delivered after item 0's trial is over, its own trial starts then.
+    env.warp_forward(i64::from(TRIAL_DAY) * SECONDS_PER_DAY).await;
+    env.next_slot().await;
+    env.process(&[deliver_items(&escrow, vec![1])], &[&buyer]).await.unwrap();
+    env.process(&[settle_items(&escrow, vec![0])], &[&seller]).await.unwrap();
+    assert_eq!(env.seller_balance().await, 400);
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, 600);
+    let result = env.process(&[settle_items(&escrow, vec![1])], &[&seller]).await;
+    assert_program_error(result, ErrorCode::InTrialDay);
+
+    // Item 2 was never shipped, item 1 is on its way.
+    env.process(&[cancel_items(&escrow, vec![2])], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - 900);
+    let result = env.process(&[cancel_items(&escrow, vec![1])], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::InvalidItemStatus);
+
+    // The last item closes the escrow.
+    env.warp_forward(i64::from(TRIAL_DAY) * SECONDS_PER_DAY).await;
+    env.next_slot().await;
+    env.process(&[settle_items(&escrow, vec![1])], &[&seller]).await.unwrap();
+    assert_eq!(env.seller_balance().await, 900);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - 900);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+    assert!(!env.account_exists(&line_items_address(ORDER_CODE).0).await);
+}
+
+#[tokio::test]
+async fn cancel_every_item_closes_the_escrow() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, TRIAL_DAY).await;
+    env.process(&[set_line_items(&escrow, items())], &[&buyer]).await.unwrap();
+
+    // The same index twice is not paid twice.
+    let result = env.process(&[cancel_items(&escrow, vec![0, 0])], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::InvalidItemStatus);
+    let result = env.process(&[cancel_items(&escrow, vec![3])], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::InvalidLineItems);
+
+    env.process(&[cancel_items(&escrow, vec![0, 1, 2])], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&line_items_address(ORDER_CODE).0).await);
+}
+
+#[tokio::test]
+async fn line_items_must_match_the_escrow() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, TRIAL_DAY).await;
+
+    let result = env.process(&[set_line_items(&escrow, vec![item(1, 1, 999)])], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::InvalidLineItems);
+    let result = env.process(&[set_line_items(&escrow, vec![item(1, 0, 1000), item(2, 1, 1000)])], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::InvalidLineItems);
+    let result = env.process(&[set_line_items(&escrow, Vec::new())], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::InvalidLineItems);
+
+    env.process(&[set_line_items(&escrow, items())], &[&buyer]).await.unwrap();
+    assert!(env.escrow_account(&escrow.escrow_account).await.itemized);
+}
+
+#[tokio::test]
+async fn whole_order_instructions_reject_an_itemized_escrow() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let judge = clone_keypair(&env.judge);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, TRIAL_DAY).await;
+    env.process(&[set_line_items(&escrow, items())], &[&buyer]).await.unwrap();
+
+    for (instruction, signer) in [
+        (cancel(&escrow), &buyer),
+        (cancel_partial(&escrow, 100), &buyer),
+        (charge_more(&escrow, 100), &buyer),
+        (shipping(&escrow), &seller),
+        (refund_partial(&escrow, 100), &seller),
+        (exchange(&escrow), &seller),
+        (sync_vault(&escrow), &judge),
+    ] {
+        let result = env.process(&[instruction], &[signer]).await;
+        assert_program_error(result, ErrorCode::EscrowItemized);
+    }
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, AMOUNT);
+
+    // Undelivered items go back through cancel_items only.
+    env.process(&[cancel_items(&escrow, vec![2])], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - 900);
+}
@@ -0,0 +1,117 @@
+// Random instruction sequences run against both `lambda_escrow_model` and the
+// program, checking they agree and that no tokens are created or lost.
+mod common;
+
+use common::*;
+use lambda_escrow_model::Escrow as Model;
+use proptest::prelude::*;
+use solana_program_test::tokio;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::signature::Keypair;
+
+const ORDER_CODE: u64 = 99;
+
+#[derive(Clone, Debug)]
+enum Op {
+    ChargeMore(u64),
+    CancelPartial(u64),
+    RefundPartial(u64),
+    Shipping,
+    Delivered,
+    SetVesting,
+    Adjudge(u8),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    // Amounts overlap the escrow amount so partials hit both sides of the bound.
+    prop_oneof![
+        (0..1_500u64).prop_map(Op::ChargeMore),
+        (0..1_500u64).prop_map(Op::CancelPartial),
+        (0..1_500u64).prop_map(Op::RefundPartial),
+        Just(Op::Shipping),
+        Just(Op::Delivered),
+        Just(Op::SetVesting),
+        // 3 is not a stored status, adjudge ignores it.
+        (0..4u8).prop_map(Op::Adjudge),
+    ]
+}
+
+async fn check_invariants(env: &mut Env, escrow: &Escrow, model: &Model) {
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!(escrow_account.status, model.status);
+    assert_eq!(escrow_account.amount, model.amount);
+    assert_eq!(escrow_account.delivery_time, model.delivery_time);
+    assert_eq!(escrow_account.trial_day, model.trial_day);
+    assert_eq!(escrow_account.vesting, model.vesting);
+
+    let vault = env.token_balance(&vault_account(escrow.order_code).0).await;
+    assert_eq!(vault, escrow_account.amount);
+    let total = env.buyer_balance().await + vault + env.seller_balance().await;
+    assert_eq!(total, BUYER_TOKENS);
+}
+
+async fn run(amount: u64, trial_day: u16, ops: Vec<Op>, for_buyer: bool) {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let judge = clone_keypair(&env.judge);
+
+    let now = env.now().await;
+    let mut model = Model::initialize(amount, trial_day, now).unwrap();
+    let escrow = env.initialize(ORDER_CODE, amount, trial_day).await;
+    check_invariants(&mut env, &escrow, &model).await;
+
+    for op in ops {
+        env.next_slot().await;
+        let now = env.now().await;
+        let (instruction, signer, expected): (Instruction, &Keypair, bool) = match op {
+            Op::ChargeMore(amount) => {
+                // The buyer can only add what is left in its token account.
+                let expected = amount <= env.buyer_balance().await && model.charge_more(amount).is_ok();
+                (charge_more(&escrow, amount), &buyer, expected)
+            }
+            Op::CancelPartial(amount) => (cancel_partial(&escrow, amount), &buyer, model.cancel_partial(amount).is_ok()),
+            Op::RefundPartial(amount) => (refund_partial(&escrow, amount), &seller, model.refund_partial(amount).is_ok()),
+            Op::Shipping => (shipping(&escrow), &seller, model.shipping().is_ok()),
+            Op::Delivered => (delivered(&escrow), &buyer, model.delivered(now).is_ok()),
+            Op::SetVesting => (set_vesting(&escrow), &buyer, model.set_vesting().is_ok()),
+            Op::Adjudge(status) => (adjudge(&escrow, status), &judge, model.adjudge(status, now).is_ok()),
+        };
+        let result = env.process(&[instruction], &[signer]).await;
+        assert_eq!(result.is_ok(), expected, "{:?} gave {:?}", op, result);
+        check_invariants(&mut env, &escrow, &model).await;
+    }
+
+    // Settle what is left, the totals still have to add up.
+    let buyer_before = env.buyer_balance().await;
+    let seller_before = env.seller_balance().await;
+    if for_buyer {
+        let payout = model.adjudge_for_buyer().unwrap();
+        env.process(&[adjudge_for_buyer(&escrow)], &[&judge]).await.unwrap();
+        assert_eq!(env.buyer_balance().await, buyer_before + payout);
+    } else {
+        let payout = model.adjudge_for_seller().unwrap();
+        env.process(&[adjudge_for_seller(&escrow)], &[&judge]).await.unwrap();
+        assert_eq!(env.seller_balance().await, seller_before + payout);
+    }
+    assert!(model.is_closed());
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+    assert_eq!(env.buyer_balance().await + env.seller_balance().await, BUYER_TOKENS);
+}
+
+proptest! {
+    // Every case boots a bank, keep the count modest.
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn program_matches_model(
+        amount in 1..2_000u64,
+        trial_day in 0..10u16,
+        ops in prop::collection::vec(op(), 1..20),
+        for_buyer in any::<bool>(),
+    ) {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(run(amount, trial_day, ops, for_buyer));
+    }
+}
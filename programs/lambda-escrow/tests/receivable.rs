@@ -0,0 +1,77 @@
+mod common;
+
+use common::*;
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+const ORDER_CODE: u64 = 88;
+const AMOUNT: u64 = 1000;
+
+/// Sells the receivable of `escrow` to a new financier, returns it with its token account.
+async fn sell(env: &mut Env, escrow: &Escrow) -> (Keypair, Pubkey, Escrow) {
+    let seller = clone_keypair(&env.seller);
+    let financier = Keypair::new();
+    let financier_token = env.create_token_account(&financier.pubkey()).await;
+    let instruction = transfer_receivable(escrow, &financier.pubkey(), &financier_token);
+    env.process(&[instruction], &[&seller]).await.unwrap();
+    let sold = Escrow { seller: financier.pubkey(), seller_receive_token_account: financier_token, ..*escrow };
+    (financier, financier_token, sold)
+}
+
+#[tokio::test]
+async fn financier_is_paid_while_the_seller_ships() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let (financier, financier_token, sold) = sell(&mut env, &escrow).await;
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!(escrow_account.seller_key, financier.pubkey());
+    assert_eq!(escrow_account.seller_receive_token_account, financier_token);
+    assert_eq!(escrow_account.fulfiller_key, seller.pubkey());
+
+    // Shipping stays with the seller.
+    let mut as_fulfiller = sold;
+    as_fulfiller.fulfiller = financier.pubkey();
+    let result = env.process(&[shipping(&as_fulfiller)], &[&financier]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    env.process(&[shipping(&sold)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&sold)], &[&buyer]).await.unwrap();
+
+    // The seller no longer owns the payout.
+    let result = env.process(&[exchange(&escrow)], &[&seller]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    let instruction = transfer_receivable(&escrow, &seller.pubkey(), &escrow.seller_receive_token_account);
+    let result = env.process(&[instruction], &[&seller]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    env.process(&[exchange(&sold)], &[&financier]).await.unwrap();
+    assert_eq!(env.token_balance(&financier_token).await, AMOUNT);
+    assert_eq!(env.seller_balance().await, 0);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+}
+
+#[tokio::test]
+async fn judge_pays_the_financier() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let judge = clone_keypair(&env.judge);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+
+    // A financier can sell the receivable on.
+    let (financier, _, sold) = sell(&mut env, &escrow).await;
+    let other = Keypair::new();
+    let other_token = env.create_token_account(&other.pubkey()).await;
+    env.process(&[transfer_receivable(&sold, &other.pubkey(), &other_token)], &[&financier]).await.unwrap();
+    let resold = Escrow { seller: other.pubkey(), seller_receive_token_account: other_token, ..sold };
+
+    let result = env.process(&[adjudge_for_seller(&sold)], &[&judge]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    env.process(&[adjudge_for_seller(&resold)], &[&judge]).await.unwrap();
+    assert_eq!(env.token_balance(&other_token).await, AMOUNT);
+    assert_eq!(env.seller_balance().await, 0);
+}
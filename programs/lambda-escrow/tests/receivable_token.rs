@@ -0,0 +1,118 @@
+mod common;
+
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::program_pack::Pack;
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+const ORDER_CODE: u64 = 91;
+const AMOUNT: u64 = 1000;
+
+/// Sells the receivable token of `escrow` to a new holder, returns it with its receivable and
+/// receive token accounts.
+async fn sell_token(env: &mut Env, escrow: &Escrow) -> (Keypair, Pubkey, Pubkey) {
+    let seller = clone_keypair(&env.seller);
+    let holder = Keypair::new();
+    let holder_receivable = env.create_token_account_of(&receivable_mint_address(escrow.order_code), &holder.pubkey()).await;
+    let holder_token = env.create_token_account(&holder.pubkey()).await;
+    let transfer = spl_token::instruction::transfer(
+        &spl_token::ID,
+        &receivable_token_address(escrow.order_code),
+        &holder_receivable,
+        &seller.pubkey(),
+        &[],
+        1,
+    )
+    .unwrap();
+    env.process(&[transfer], &[&seller]).await.unwrap();
+    (holder, holder_receivable, holder_token)
+}
+
+#[tokio::test]
+async fn initialize_mints_one_receivable_to_the_seller() {
+    let mut env = Env::new(0).await;
+    let seller = clone_keypair(&env.seller);
+    let buyer = clone_keypair(&env.buyer);
+    let escrow = env.initialize_tokenized(ORDER_CODE, AMOUNT, 0).await;
+    let receivable_mint = receivable_mint_address(ORDER_CODE);
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.receivable_mint, receivable_mint);
+    assert_eq!(env.token_balance(&receivable_token_address(ORDER_CODE)).await, 1);
+    let account = env.context.banks_client.get_account(receivable_mint).await.unwrap().unwrap();
+    let mint = spl_token::state::Mint::unpack(&account.data).unwrap();
+    assert_eq!(mint.supply, 1);
+    assert_eq!(mint.mint_authority, COption::None);
+
+    // The receivable accounts have to be the order code's PDAs.
+    let escrow_keypair = Keypair::new();
+    let other = env.escrow(&escrow_keypair, ORDER_CODE + 1);
+    let mut instruction = with_receivable(initialize(&other, &env.mint.pubkey(), AMOUNT, 0), ORDER_CODE + 1);
+    instruction.accounts.swap(13, 14);
+    let result = env.try_initialize_with(&other, &escrow_keypair, instruction, &buyer).await;
+    assert_program_error(result, ErrorCode::InvalidReceivableAccounts);
+
+    // The payout belongs to the token holder, the seller can not give it away by itself.
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    let result = env.process(&[refund(&escrow)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::ReceivableTokenized);
+    let instruction = transfer_receivable(&escrow, &buyer.pubkey(), &escrow.buyer_deposit_token_account);
+    let result = env.process(&[instruction], &[&seller]).await;
+    assert_program_error(result, ErrorCode::ReceivableTokenized);
+}
+
+#[tokio::test]
+async fn exchange_pays_the_token_holder() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let admin = env.admin.pubkey();
+    let escrow = env.initialize_tokenized(ORDER_CODE, AMOUNT, 0).await;
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+    let (holder, holder_receivable, holder_token) = sell_token(&mut env, &escrow).await;
+
+    // settle_batch leaves a tokenized escrow to its holder.
+    env.process(&[settle_batch(&[escrow])], &[&seller]).await.unwrap();
+    assert!(env.account_exists(&escrow.escrow_account).await);
+
+    // The seller no longer holds the token.
+    let result = env.process(&[exchange(&escrow)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::ReceivableNotHeld);
+    let instruction = with_holder(exchange(&escrow), &receivable_token_address(ORDER_CODE));
+    let result = env.process(&[instruction], &[&seller]).await;
+    assert_program_error(result, ErrorCode::ReceivableNotHeld);
+
+    let paid = Escrow { seller: holder.pubkey(), seller_receive_token_account: holder_token, ..escrow };
+    let instruction = with_receipt(with_holder(exchange(&paid), &holder_receivable), &escrow.escrow_account, &admin);
+    env.process(&[instruction], &[&holder]).await.unwrap();
+    assert_eq!(env.token_balance(&holder_token).await, AMOUNT);
+    assert_eq!(env.seller_balance().await, 0);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert_eq!(env.receipt(&escrow.escrow_account).await.seller_key, holder.pubkey());
+}
+
+#[tokio::test]
+async fn judge_pays_the_token_holder() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let judge = clone_keypair(&env.judge);
+    let escrow = env.initialize_tokenized(ORDER_CODE, AMOUNT, 0).await;
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+    let (holder, holder_receivable, holder_token) = sell_token(&mut env, &escrow).await;
+
+    let result = env.process(&[adjudge_for_seller(&escrow)], &[&judge]).await;
+    assert_program_error(result, ErrorCode::ReceivableNotHeld);
+    // The payout has to go to the holder's own token account.
+    let instruction = with_holder(adjudge_for_seller(&escrow), &holder_receivable);
+    let result = env.process(&[instruction], &[&judge]).await;
+    assert_program_error(result, ErrorCode::ReceivableNotHeld);
+
+    let paid = Escrow { seller: holder.pubkey(), seller_receive_token_account: holder_token, ..escrow };
+    env.process(&[with_holder(adjudge_for_seller(&paid), &holder_receivable)], &[&judge]).await.unwrap();
+    assert_eq!(env.token_balance(&holder_token).await, AMOUNT);
+    assert_eq!(env.seller_balance().await, 0);
+}
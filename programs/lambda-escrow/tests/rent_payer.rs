@@ -0,0 +1,96 @@
+mod common;
+
+use common::*;
+use solana_program_test::tokio;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+
+const ORDER_CODE: u64 = 33;
+const AMOUNT: u64 = 1000;
+
+/// A marketplace account with SOL to sponsor the rent of escrows.
+async fn sponsor(env: &mut Env) -> Keypair {
+    let sponsor = Keypair::new();
+    let fund = system_instruction::transfer(&env.admin.pubkey(), &sponsor.pubkey(), 1_000_000_000);
+    env.process(&[fund], &[]).await.unwrap();
+    sponsor
+}
+
+/// Lamports held by the vault and escrow accounts, given back when they are closed.
+async fn escrow_rent(env: &mut Env, escrow: &Escrow) -> u64 {
+    env.lamports(&vault_account(escrow.order_code).0).await + env.lamports(&escrow.escrow_account).await
+}
+
+#[tokio::test]
+async fn cancel_refunds_the_sponsor() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let sponsor = sponsor(&mut env).await;
+    let buyer_lamports = env.lamports(&buyer.pubkey()).await;
+    let escrow = env.initialize_paid_by(ORDER_CODE, AMOUNT, 0, &sponsor).await;
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.rent_payer, sponsor.pubkey());
+
+    // The buyer only signs, the admin pays the fees in these tests.
+    assert_eq!(env.lamports(&buyer.pubkey()).await, buyer_lamports);
+    let sponsor_lamports = env.lamports(&sponsor.pubkey()).await;
+    let rent = escrow_rent(&mut env, &escrow).await;
+    env.process(&[cancel(&escrow)], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    assert_eq!(env.lamports(&sponsor.pubkey()).await, sponsor_lamports + rent);
+    assert_eq!(env.lamports(&buyer.pubkey()).await, buyer_lamports);
+}
+
+#[tokio::test]
+async fn exchange_refunds_the_sponsor_only() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let sponsor = sponsor(&mut env).await;
+    let escrow = env.initialize_paid_by(ORDER_CODE, AMOUNT, 0, &sponsor).await;
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+
+    let mut to_buyer = escrow;
+    to_buyer.rent_payer = buyer.pubkey();
+    let result = env.process(&[exchange(&to_buyer)], &[&seller]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    let sponsor_lamports = env.lamports(&sponsor.pubkey()).await;
+    let rent = escrow_rent(&mut env, &escrow).await;
+    env.process(&[exchange(&escrow)], &[&seller]).await.unwrap();
+    assert_eq!(env.seller_balance().await, AMOUNT);
+    assert_eq!(env.lamports(&sponsor.pubkey()).await, sponsor_lamports + rent);
+}
+
+#[tokio::test]
+async fn adjudge_and_settle_batch_refund_the_sponsor() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let judge = clone_keypair(&env.judge);
+    let sponsor = sponsor(&mut env).await;
+    let adjudged = env.initialize_paid_by(ORDER_CODE, AMOUNT, 0, &sponsor).await;
+    let batched = env.initialize_paid_by(ORDER_CODE + 1, AMOUNT, 0, &sponsor).await;
+    for escrow in &[adjudged, batched] {
+        env.process(&[shipping(escrow)], &[&seller]).await.unwrap();
+        env.process(&[delivered(escrow)], &[&buyer]).await.unwrap();
+    }
+
+    let sponsor_lamports = env.lamports(&sponsor.pubkey()).await;
+    let rent = escrow_rent(&mut env, &adjudged).await;
+    env.process(&[adjudge_for_buyer(&adjudged)], &[&judge]).await.unwrap();
+    assert_eq!(env.lamports(&sponsor.pubkey()).await, sponsor_lamports + rent);
+
+    // A group with the buyer in place of the rent payer is skipped.
+    let mut to_buyer = batched;
+    to_buyer.rent_payer = buyer.pubkey();
+    env.process(&[settle_batch(&[to_buyer])], &[&seller]).await.unwrap();
+    assert!(env.account_exists(&batched.escrow_account).await);
+
+    let sponsor_lamports = env.lamports(&sponsor.pubkey()).await;
+    let rent = escrow_rent(&mut env, &batched).await;
+    env.process(&[settle_batch(&[batched])], &[&seller]).await.unwrap();
+    assert!(!env.account_exists(&batched.escrow_account).await);
+    assert_eq!(env.seller_balance().await, AMOUNT);
+    assert_eq!(env.lamports(&sponsor.pubkey()).await, sponsor_lamports + rent);
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::signature::Signer;
+
+const ORDER_CODE: u64 = 49;
+const DEPOSIT: u64 = 1000;
+const DAMAGES: u64 = 300;
+const RENTAL: i64 = 3 * SECONDS_PER_DAY;
+const CLAIM_WINDOW_DAY: u16 = 2;
+const EVIDENCE_HASH: [u8; 32] = [7; 32];
+
+/// A deposit locked for a rental ending in `RENTAL` seconds.
+async fn initialize(env: &mut Env) -> Escrow {
+    let rental_end = env.now().await + RENTAL;
+    env.try_initialize_rental(ORDER_CODE, DEPOSIT, rental_end, CLAIM_WINDOW_DAY).await.unwrap()
+}
+
+#[tokio::test]
+async fn accepted_damages_split_the_deposit() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let admin = env.admin.pubkey();
+    let rental_end = env.now().await + RENTAL;
+    let result = env.try_initialize_rental(ORDER_CODE, DEPOSIT, rental_end, 0).await;
+    assert_program_error(result.map(|_| ()), ErrorCode::InvalidRentalPeriod);
+    let escrow = initialize(&mut env).await;
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - DEPOSIT);
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert!(escrow_account.rental);
+    assert_eq!((escrow_account.delivery_time, escrow_account.trial_day), (rental_end, CLAIM_WINDOW_DAY));
+
+    // The purchase lifecycle does not apply.
+    let result = env.process(&[cancel(&escrow)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::RentalEscrow);
+
+    let result = env.process(&[claim_damages(&escrow, DAMAGES, EVIDENCE_HASH)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::RentalNotEnded);
+    env.warp_forward(RENTAL).await;
+    let result = env.process(&[claim_damages(&escrow, DEPOSIT + 1, EVIDENCE_HASH)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::InvalidDamages);
+    env.process(&[claim_damages(&escrow, DAMAGES, EVIDENCE_HASH)], &[&seller]).await.unwrap();
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!((escrow_account.damages_claim, escrow_account.damages_evidence_hash), (DAMAGES, EVIDENCE_HASH));
+    let result = env.process(&[claim_damages(&escrow, DEPOSIT, EVIDENCE_HASH)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::DamagesAlreadyClaimed);
+
+    // Claimed, the deposit no longer goes back by itself.
+    env.warp_forward(i64::from(CLAIM_WINDOW_DAY) * SECONDS_PER_DAY).await;
+    let result = env.process(&[return_deposit(&escrow)], &[]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    env.process(&[with_receipt(accept_damages(&escrow), &escrow.escrow_account, &admin)], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - DAMAGES);
+    assert_eq!(env.seller_balance().await, DAMAGES);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+    let receipt = env.receipt(&escrow.escrow_account).await;
+    assert_eq!(
+        (receipt.buyer_payout, receipt.seller_payout, receipt.settlement_path),
+        (DEPOSIT - DAMAGES, DAMAGES, 10)
+    );
+}
+
+#[tokio::test]
+async fn disputed_damages_are_settled_by_the_judge() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let judge = clone_keypair(&env.judge);
+    let escrow = initialize(&mut env).await;
+
+    let result = env.process(&[dispute_damages(&escrow)], &[&buyer]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    env.warp_forward(RENTAL).await;
+    env.process(&[claim_damages(&escrow, DAMAGES, EVIDENCE_HASH)], &[&seller]).await.unwrap();
+    env.process(&[dispute_damages(&escrow)], &[&buyer]).await.unwrap();
+    assert!(env.escrow_account(&escrow.escrow_account).await.dispute_time > 0);
+    let result = env.process(&[accept_damages(&escrow)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::EscrowDisputed);
+
+    // The judge halves the claim: the buyer gets the rest back, the seller what remains.
+    let ruling = DAMAGES / 2;
+    env.process(&[adjudge_partial(&escrow, DEPOSIT - ruling), adjudge_for_seller(&escrow)], &[&judge]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - ruling);
+    assert_eq!(env.seller_balance().await, ruling);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+}
+
+#[tokio::test]
+async fn unanswered_damages_are_settled_as_accepted() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let admin = env.admin.pubkey();
+    let escrow = initialize(&mut env).await;
+
+    env.warp_forward(RENTAL).await;
+    env.process(&[claim_damages(&escrow, DAMAGES, EVIDENCE_HASH)], &[&seller]).await.unwrap();
+    let response_window = i64::from(CLAIM_WINDOW_DAY) * SECONDS_PER_DAY;
+    let deadline = env.escrow_account(&escrow.escrow_account).await.damages_response_deadline;
+    assert!(deadline >= env.now().await + response_window);
+    let result = env.process(&[settle_damages(&escrow)], &[]).await;
+    assert_program_error(result, ErrorCode::ResponseWindowOpen);
+
+    // The buyer let the response window close, it can no longer escalate the claim.
+    env.warp_forward(response_window).await;
+    let result = env.process(&[dispute_damages(&escrow)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::ResponseWindowClosed);
+
+    // No party has to sign, the admin stands in for a keeper and pays the fees.
+    env.process(&[with_receipt(settle_damages(&escrow), &escrow.escrow_account, &admin)], &[]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - DAMAGES);
+    assert_eq!(env.seller_balance().await, DAMAGES);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+    let receipt = env.receipt(&escrow.escrow_account).await;
+    assert_eq!(
+        (receipt.buyer_payout, receipt.seller_payout, receipt.settlement_path),
+        (DEPOSIT - DAMAGES, DAMAGES, 12)
+    );
+}
+
+#[tokio::test]
+async fn unclaimed_deposit_returns_to_the_buyer() {
+    let mut env = Env::new(0).await;
+    let seller = clone_keypair(&env.seller);
+    let admin = env.admin.pubkey();
+    let escrow = initialize(&mut env).await;
+
+    env.warp_forward(RENTAL).await;
+    let result = env.process(&[return_deposit(&escrow)], &[]).await;
+    assert_program_error(result, ErrorCode::ClaimWindowOpen);
+
+    env.warp_forward(i64::from(CLAIM_WINDOW_DAY) * SECONDS_PER_DAY).await;
+    let result = env.process(&[claim_damages(&escrow, DAMAGES, EVIDENCE_HASH)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::ClaimWindowClosed);
+
+    // No party has to sign, the admin only pays the fees here.
+    env.process(&[with_receipt(return_deposit(&escrow), &escrow.escrow_account, &admin)], &[]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    assert_eq!(env.seller_balance().await, 0);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    let receipt = env.receipt(&escrow.escrow_account).await;
+    assert_eq!((receipt.buyer_payout, receipt.settlement_path), (DEPOSIT, 11));
+}
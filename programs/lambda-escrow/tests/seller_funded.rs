@@ -0,0 +1,94 @@
+mod common;
+
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::signature::Signer;
+
+const ORDER_CODE: u64 = 47;
+const AMOUNT: u64 = 1000;
+const PAYMENT_WINDOW: i64 = 1800;
+
+/// The seller's tokens, locked into a new seller-funded escrow.
+async fn initialize(env: &mut Env) -> Escrow {
+    let seller_token = env.seller_token;
+    env.mint_to(&seller_token, AMOUNT).await;
+    env.try_initialize_seller_funded(ORDER_CODE, AMOUNT, PAYMENT_WINDOW).await.unwrap()
+}
+
+#[tokio::test]
+async fn release_pays_the_buyer() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let admin = env.admin.pubkey();
+    let result = env.try_initialize_seller_funded(ORDER_CODE, AMOUNT, 0).await;
+    assert_program_error(result.map(|_| ()), ErrorCode::InvalidPaymentWindow);
+    let escrow = initialize(&mut env).await;
+    assert_eq!(env.seller_balance().await, 0);
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert!(escrow_account.seller_funded);
+    assert_eq!(escrow_account.payment_deadline, env.now().await + PAYMENT_WINDOW);
+
+    // The buyer-funded lifecycle does not apply.
+    let result = env.process(&[cancel(&escrow)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::SellerFunded);
+    let result = env.process(&[shipping(&escrow)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::SellerFunded);
+
+    let as_seller = Escrow { buyer: seller.pubkey(), ..escrow };
+    let result = env.process(&[mark_paid(&as_seller)], &[&seller]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    env.process(&[mark_paid(&escrow)], &[&buyer]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.status, 1);
+
+    // Marked paid, the seller can no longer take the tokens back.
+    env.warp_forward(PAYMENT_WINDOW).await;
+    let result = env.process(&[reclaim_unpaid(&escrow)], &[]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    let result = env.process(&[refund(&escrow)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::SellerFunded);
+
+    env.process(&[with_receipt(release(&escrow), &escrow.escrow_account, &admin)], &[&seller]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS + AMOUNT);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+    let receipt = env.receipt(&escrow.escrow_account).await;
+    assert_eq!((receipt.buyer_payout, receipt.settlement_path), (AMOUNT, 5));
+}
+
+#[tokio::test]
+async fn unpaid_escrow_goes_back_to_the_seller() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let escrow = initialize(&mut env).await;
+
+    let result = env.process(&[reclaim_unpaid(&escrow)], &[]).await;
+    assert_program_error(result, ErrorCode::PaymentDeadlineNotPassed);
+
+    env.warp_forward(PAYMENT_WINDOW).await;
+    let result = env.process(&[mark_paid(&escrow)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::PaymentDeadlinePassed);
+
+    // No party has to sign, the admin only pays the fees here.
+    env.next_slot().await;
+    env.process(&[reclaim_unpaid(&escrow)], &[]).await.unwrap();
+    assert_eq!(env.seller_balance().await, AMOUNT);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+}
+
+#[tokio::test]
+async fn judge_settles_a_contested_payment() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let judge = clone_keypair(&env.judge);
+    let escrow = initialize(&mut env).await;
+    env.process(&[mark_paid(&escrow)], &[&buyer]).await.unwrap();
+
+    // The seller does not release, the judge finds the payment was made.
+    env.process(&[adjudge_for_buyer(&escrow)], &[&judge]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS + AMOUNT);
+    assert_eq!(env.seller_balance().await, 0);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+}
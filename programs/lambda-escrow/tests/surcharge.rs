@@ -0,0 +1,71 @@
+mod common;
+
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+
+const ORDER_CODE: u64 = 50;
+const AMOUNT: u64 = 1000;
+const SURCHARGE: u64 = 150;
+const REASON_HASH: [u8; 32] = [5; 32];
+const EXPIRES_IN: i64 = 3600;
+
+#[tokio::test]
+async fn approved_surcharge_funds_the_escrow() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let deadline = env.now().await + EXPIRES_IN;
+
+    let result = env.process(&[request_surcharge(&escrow, 0, REASON_HASH, deadline)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::InvalidSurcharge);
+    env.process(&[request_surcharge(&escrow, SURCHARGE, REASON_HASH, deadline)], &[&seller]).await.unwrap();
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!(
+        (escrow_account.surcharge_amount, escrow_account.surcharge_reason_hash, escrow_account.surcharge_deadline),
+        (SURCHARGE, REASON_HASH, deadline)
+    );
+    let result = env.process(&[request_surcharge(&escrow, SURCHARGE * 2, REASON_HASH, deadline)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::SurchargePending);
+
+    // The buyer approves the amount it saw, not whatever is pending.
+    let result = env.process(&[approve_surcharge(&escrow, SURCHARGE * 2)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::SurchargeMismatch);
+    env.process(&[approve_surcharge(&escrow, SURCHARGE)], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - AMOUNT - SURCHARGE);
+    assert_eq!(env.token_balance(&vault_account(ORDER_CODE).0).await, AMOUNT + SURCHARGE);
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!((escrow_account.amount, escrow_account.surcharge_amount), (AMOUNT + SURCHARGE, 0));
+
+    env.next_slot().await;
+    let result = env.process(&[approve_surcharge(&escrow, SURCHARGE)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::NoPendingSurcharge);
+}
+
+#[tokio::test]
+async fn rejected_or_expired_surcharge_is_not_funded() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let deadline = env.now().await + EXPIRES_IN;
+
+    env.process(&[request_surcharge(&escrow, SURCHARGE, REASON_HASH, deadline)], &[&seller]).await.unwrap();
+    env.process(&[reject_surcharge(&escrow)], &[&buyer]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.surcharge_amount, 0);
+    env.next_slot().await;
+    let result = env.process(&[reject_surcharge(&escrow)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::NoPendingSurcharge);
+
+    env.process(&[request_surcharge(&escrow, SURCHARGE * 2, REASON_HASH, deadline)], &[&seller]).await.unwrap();
+    env.warp_forward(EXPIRES_IN).await;
+    let result = env.process(&[approve_surcharge(&escrow, SURCHARGE * 2)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::SurchargeExpired);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - AMOUNT);
+
+    // Expired, it no longer blocks a new request.
+    let deadline = env.now().await + EXPIRES_IN;
+    env.process(&[request_surcharge(&escrow, SURCHARGE, REASON_HASH, deadline)], &[&seller]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.surcharge_deadline, deadline);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+use solana_sdk::signature::{Keypair, Signer};
+
+const ORDER_CODE: u64 = 99;
+const AMOUNT: u64 = 1000;
+const DONATION: u64 = 50;
+
+#[tokio::test]
+async fn donation_blocks_until_sync_vault() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let judge = clone_keypair(&env.judge);
+
+    // Tokens sent straight to the vault, outside the program.
+    env.mint_to(&vault_account(ORDER_CODE).0, DONATION).await;
+
+    let result = env.process(&[cancel_partial(&escrow, 100)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::VaultMismatch);
+    let result = env.process(&[charge_more(&escrow, 100)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::VaultMismatch);
+    let result = env.process(&[cancel(&escrow)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::VaultMismatch);
+
+    env.process(&[sync_vault(&escrow)], &[&judge]).await.unwrap();
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, AMOUNT + DONATION);
+
+    env.process(&[cancel(&escrow)], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS + DONATION);
+    assert!(!env.account_exists(&vault_account(ORDER_CODE).0).await);
+}
+
+#[tokio::test]
+async fn sync_vault_checks_judge_and_vault() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    env.initialize(ORDER_CODE + 1, AMOUNT + DONATION, 0).await;
+    let judge = clone_keypair(&env.judge);
+    let attacker = Keypair::new();
+
+    let mut as_judge = escrow;
+    as_judge.judge = attacker.pubkey();
+    let result = env.process(&[sync_vault(&as_judge)], &[&attacker]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+
+    // Vault of another order.
+    let mut instruction = sync_vault(&escrow);
+    instruction.accounts[2].pubkey = vault_account(ORDER_CODE + 1).0;
+    let result = env.process(&[instruction], &[&judge]).await;
+    assert_program_error(result, ErrorCode::VaultMismatch);
+
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, AMOUNT);
+}
+
+#[tokio::test]
+async fn sync_vault_works_on_a_frozen_escrow() {
+    let mut env = Env::new(0).await;
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, 0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let judge = clone_keypair(&env.judge);
+    let compliance_authority = clone_keypair(&env.compliance_authority);
+
+    env.process(&[set_frozen(&escrow, &compliance_authority.pubkey(), true)], &[&compliance_authority]).await.unwrap();
+    env.mint_to(&vault_account(ORDER_CODE).0, DONATION).await;
+    env.process(&[sync_vault(&escrow)], &[&judge]).await.unwrap();
+
+    env.process(&[set_frozen(&escrow, &compliance_authority.pubkey(), false)], &[&compliance_authority]).await.unwrap();
+    env.process(&[cancel(&escrow)], &[&buyer]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS + DONATION);
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use common::*;
+use lambda_escrow::ErrorCode;
+use solana_program_test::tokio;
+
+const ORDER_CODE: u64 = 77;
+const AMOUNT: u64 = 1000;
+const TRIAL_DAY: u16 = 4;
+
+/// A vesting escrow of `AMOUNT`, Delivered now.
+async fn delivered_vesting(env: &mut Env, order_code: u64) -> Escrow {
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let escrow = env.initialize(order_code, AMOUNT, TRIAL_DAY).await;
+    env.process(&[set_vesting(&escrow)], &[&buyer]).await.unwrap();
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+    escrow
+}
+
+/// What the model says the seller can take from `escrow` right now.
+async fn expected_withdrawable(env: &mut Env, escrow: &Escrow) -> u64 {
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    let now = env.now().await;
+    lambda_escrow_model::withdrawable(
+        escrow_account.amount,
+        escrow_account.withdrawn,
+        escrow_account.delivery_time,
+        escrow_account.trial_day,
+        now,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn withdraw_vested_unlocks_linearly() {
+    let mut env = Env::new(0).await;
+    let seller = clone_keypair(&env.seller);
+    let escrow = delivered_vesting(&mut env, ORDER_CODE).await;
+    assert!(env.escrow_account(&escrow.escrow_account).await.vesting);
+
+    let result = env.process(&[withdraw_vested(&escrow)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::NothingVested);
+
+    // A quarter of the trial unlocks a quarter of the escrow.
+    env.warp_forward(SECONDS_PER_DAY).await;
+    env.next_slot().await;
+    let expected = expected_withdrawable(&mut env, &escrow).await;
+    assert!((AMOUNT / 4..AMOUNT).contains(&expected));
+    env.process(&[withdraw_vested(&escrow)], &[&seller]).await.unwrap();
+    assert_eq!(env.seller_balance().await, expected);
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert_eq!(escrow_account.amount, AMOUNT - expected);
+    assert_eq!(escrow_account.withdrawn, expected);
+    assert_eq!(env.token_balance(&vault_account(ORDER_CODE).0).await, AMOUNT - expected);
+
+    // Once the trial is over everything is withdrawable, exchange closes the empty escrow.
+    env.warp_forward(i64::from(TRIAL_DAY) * SECONDS_PER_DAY).await;
+    env.next_slot().await;
+    env.process(&[withdraw_vested(&escrow)], &[&seller]).await.unwrap();
+    assert_eq!(env.seller_balance().await, AMOUNT);
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, 0);
+    env.process(&[exchange(&escrow)], &[&seller]).await.unwrap();
+    assert_eq!(env.seller_balance().await, AMOUNT);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+}
+
+#[tokio::test]
+async fn dispute_leaves_the_unvested_rest_to_the_judge() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let judge = clone_keypair(&env.judge);
+    let escrow = delivered_vesting(&mut env, ORDER_CODE).await;
+
+    // The dispute pays what vested so far, like a withdrawal would.
+    env.warp_forward(SECONDS_PER_DAY).await;
+    env.next_slot().await;
+    let vested = expected_withdrawable(&mut env, &escrow).await;
+    env.process(&[dispute(&escrow)], &[&buyer]).await.unwrap();
+    assert_eq!(env.seller_balance().await, vested);
+    let escrow_account = env.escrow_account(&escrow.escrow_account).await;
+    assert!(escrow_account.dispute_time > 0);
+    assert_eq!(escrow_account.amount, AMOUNT - vested);
+
+    // Nothing more vests, and the seller can not settle it at the end of the trial.
+    env.warp_forward(i64::from(TRIAL_DAY) * SECONDS_PER_DAY).await;
+    let result = env.process(&[withdraw_vested(&escrow)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::EscrowDisputed);
+    let result = env.process(&[exchange(&escrow)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::EscrowDisputed);
+    env.process(&[settle_batch(&[escrow])], &[&seller]).await.unwrap();
+    assert!(env.account_exists(&escrow.escrow_account).await);
+
+    env.process(&[adjudge_for_buyer(&escrow)], &[&judge]).await.unwrap();
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - vested);
+    assert_eq!(env.seller_balance().await, vested);
+    assert!(!env.account_exists(&escrow.escrow_account).await);
+}
+
+#[tokio::test]
+async fn vesting_is_set_by_the_buyer_before_delivery() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, TRIAL_DAY).await;
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+
+    let result = env.process(&[set_vesting(&escrow)], &[&buyer]).await;
+    assert_custom_error(result, CONSTRAINT_RAW);
+    env.warp_forward(SECONDS_PER_DAY).await;
+    let result = env.process(&[withdraw_vested(&escrow)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::NotVesting);
+    let result = env.process(&[dispute(&escrow)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::NotVesting);
+
+    // A vesting escrow can not be disputed once its trial is over.
+    let vesting = delivered_vesting(&mut env, ORDER_CODE + 1).await;
+    env.warp_forward(i64::from(TRIAL_DAY) * SECONDS_PER_DAY).await;
+    let result = env.process(&[dispute(&vesting)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::TrialEnded);
+}
+
+#[tokio::test]
+async fn vesting_escrow_is_not_topped_up_during_the_trial() {
+    let mut env = Env::new(0).await;
+    let buyer = clone_keypair(&env.buyer);
+    let seller = clone_keypair(&env.seller);
+    let escrow = env.initialize(ORDER_CODE, AMOUNT, TRIAL_DAY).await;
+    env.process(&[set_vesting(&escrow)], &[&buyer]).await.unwrap();
+
+    // Before delivery a top-up vests with the rest of the escrow.
+    env.process(&[charge_more(&escrow, AMOUNT)], &[&buyer]).await.unwrap();
+    env.process(&[shipping(&escrow)], &[&seller]).await.unwrap();
+    let deadline = env.now().await + i64::from(TRIAL_DAY) * SECONDS_PER_DAY;
+    env.process(&[request_surcharge(&escrow, AMOUNT, [1; 32], deadline)], &[&seller]).await.unwrap();
+    env.process(&[delivered(&escrow)], &[&buyer]).await.unwrap();
+
+    // Half way through, a top-up would be half vested at once.
+    env.warp_forward(i64::from(TRIAL_DAY) * SECONDS_PER_DAY / 2).await;
+    let result = env.process(&[charge_more(&escrow, AMOUNT)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::VestingStarted);
+    let result = env.process(&[approve_surcharge(&escrow, AMOUNT)], &[&buyer]).await;
+    assert_program_error(result, ErrorCode::VestingStarted);
+    env.process(&[reject_surcharge(&escrow)], &[&buyer]).await.unwrap();
+    let result = env.process(&[request_surcharge(&escrow, AMOUNT, [1; 32], deadline)], &[&seller]).await;
+    assert_program_error(result, ErrorCode::VestingStarted);
+    assert_eq!(env.buyer_balance().await, BUYER_TOKENS - 2 * AMOUNT);
+    assert_eq!(env.escrow_account(&escrow.escrow_account).await.amount, 2 * AMOUNT);
+}
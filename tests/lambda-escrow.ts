@@ -20,6 +20,7 @@ describe('lambda-escrow', () => {
   let vault_account_pda = null;
   let vault_account_bump = null;
   let vault_authority_pda = null;
+  let config_pda = null;
 
   const amount = 1000;
   const amountPartial = 500;
@@ -31,6 +32,7 @@ describe('lambda-escrow', () => {
   const buyer = anchor.web3.Keypair.generate();
   const seller = anchor.web3.Keypair.generate();
   const judge = anchor.web3.Keypair.generate();
+  const newJudge = anchor.web3.Keypair.generate();
   const escrowAccount = anchor.web3.Keypair.generate();
   const mintAuthority = anchor.web3.Keypair.generate();
   
@@ -91,6 +93,52 @@ describe('lambda-escrow', () => {
     assert.ok(_buyerTokenAccountA.amount.toNumber() == amount);
   }); // buyer: 1000, seller: 0
 
+  it("Initialize program config", async () => {
+    const [_config_pda, _config_bump] = await PublicKey.findProgramAddress(
+      [Buffer.from(anchor.utils.bytes.utf8.encode("config"))],
+      program.programId
+    );
+    config_pda = _config_pda;
+
+    const [_program_data] = await PublicKey.findProgramAddress(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+
+    // The wallet deployed the program, so only it can create the config.
+    await program.rpc.initializeConfig(
+      _config_bump,
+      new anchor.BN(0),
+      {
+        accounts: {
+          admin: provider.wallet.publicKey,
+          complianceAuthority: judge.publicKey,
+          config: config_pda,
+          programData: _program_data,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        },
+      }
+    );
+
+    // payer is the marketplace admin, judge changes apply without delay.
+    await program.rpc.updateConfig(
+      new anchor.BN(0),
+      {
+        accounts: {
+          admin: provider.wallet.publicKey,
+          newAdmin: payer.publicKey,
+          complianceAuthority: judge.publicKey,
+          config: config_pda,
+        },
+      }
+    );
+
+    let _config = await program.account.programConfig.fetch(config_pda);
+    assert.ok(_config.admin.equals(payer.publicKey));
+    assert.ok(_config.judgeChangeDelay.toNumber() == 0);
+  });
+
 
   it("Initialize escrow", async () => {
     // Init vault account
@@ -116,8 +164,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -220,6 +270,8 @@ describe('lambda-escrow', () => {
     await program.rpc.exchange({
       accounts: {
         buyer: buyer.publicKey,
+        rentPayer: buyer.publicKey,
+        config: config_pda,
         buyerDepositTokenAccount: buyerTokenAccountA,
         seller: seller.publicKey,
         sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -259,8 +311,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -283,6 +337,8 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           vaultAccount: vault_account_pda,
           vaultAuthority: vault_authority_pda,
@@ -313,8 +369,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -381,6 +439,8 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           vaultAccount: vault_account_pda,
           vaultAuthority: vault_authority_pda,
@@ -411,8 +471,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -482,6 +544,8 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           vaultAccount: vault_account_pda,
           vaultAuthority: vault_authority_pda,
@@ -512,8 +576,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -581,6 +647,8 @@ describe('lambda-escrow', () => {
     await program.rpc.exchange({
       accounts: {
         buyer: buyer.publicKey,
+        rentPayer: buyer.publicKey,
+        config: config_pda,
         buyerDepositTokenAccount: buyerTokenAccountA,
         seller: seller.publicKey,
         sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -665,8 +733,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda2,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -690,8 +760,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda3,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -714,6 +786,8 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           vaultAccount: vault_account_pda2,
           vaultAuthority: vault_authority_pda2,
@@ -728,6 +802,8 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           vaultAccount: vault_account_pda3,
           vaultAuthority: vault_authority_pda3,
@@ -759,8 +835,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -784,6 +862,7 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           vaultAccount: vault_account_pda,
           vaultAuthority: vault_authority_pda,
@@ -812,6 +891,8 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           vaultAccount: vault_account_pda,
           vaultAuthority: vault_authority_pda,
@@ -840,8 +921,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -881,6 +964,7 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           seller: seller.publicKey,
           sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -926,6 +1010,8 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           vaultAccount: vault_account_pda,
           vaultAuthority: vault_authority_pda,
@@ -954,8 +1040,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -979,6 +1067,7 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           vaultAccount: vault_account_pda,
           vaultAuthority: vault_authority_pda,
@@ -1036,6 +1125,8 @@ describe('lambda-escrow', () => {
     await program.rpc.exchange({
       accounts: {
         buyer: buyer.publicKey,
+        rentPayer: buyer.publicKey,
+        config: config_pda,
         buyerDepositTokenAccount: buyerTokenAccountA,
         seller: seller.publicKey,
         sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -1068,8 +1159,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -1109,6 +1202,7 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           seller: seller.publicKey,
           sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -1152,6 +1246,8 @@ describe('lambda-escrow', () => {
     await program.rpc.exchange({
       accounts: {
         buyer: buyer.publicKey,
+        rentPayer: buyer.publicKey,
+        config: config_pda,
         buyerDepositTokenAccount: buyerTokenAccountA,
         seller: seller.publicKey,
         sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -1184,8 +1280,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -1225,6 +1323,7 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           judge: judge.publicKey,
+          config: config_pda,
           buyer: buyer.publicKey,
           buyerDepositTokenAccount: buyerTokenAccountA,
           seller: seller.publicKey,
@@ -1271,6 +1370,8 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           vaultAccount: vault_account_pda,
           vaultAuthority: vault_authority_pda,
@@ -1299,8 +1400,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -1340,6 +1443,7 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           judge: judge.publicKey,
+          config: config_pda,
           buyer: buyer.publicKey,
           buyerDepositTokenAccount: buyerTokenAccountA,
           seller: seller.publicKey,
@@ -1384,6 +1488,8 @@ describe('lambda-escrow', () => {
     await program.rpc.exchange({
       accounts: {
         buyer: buyer.publicKey,
+        rentPayer: buyer.publicKey,
+        config: config_pda,
         buyerDepositTokenAccount: buyerTokenAccountA,
         seller: seller.publicKey,
         sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -1415,8 +1521,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -1455,7 +1563,9 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           judge: judge.publicKey,
+          config: config_pda,
           buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
           buyerDepositTokenAccount: buyerTokenAccountA,
           seller: seller.publicKey,
           sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -1488,8 +1598,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -1528,7 +1640,9 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           judge: judge.publicKey,
+          config: config_pda,
           buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
           buyerDepositTokenAccount: buyerTokenAccountA,
           seller: seller.publicKey,
           sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -1571,8 +1685,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -1596,6 +1712,7 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           seller: seller.publicKey,
           sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -1626,6 +1743,8 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           vaultAccount: vault_account_pda,
           vaultAuthority: vault_authority_pda,
@@ -1653,8 +1772,10 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          payer: buyer.publicKey,
           seller: seller.publicKey,
           judge: judge.publicKey,
+          config: config_pda,
           mint: mintA.publicKey,
           vaultAccount: vault_account_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
@@ -1694,6 +1815,7 @@ describe('lambda-escrow', () => {
       {
         accounts: {
           buyer: buyer.publicKey,
+          config: config_pda,
           buyerDepositTokenAccount: buyerTokenAccountA,
           seller: seller.publicKey,
           sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -1737,6 +1859,8 @@ describe('lambda-escrow', () => {
     await program.rpc.exchange({
       accounts: {
         buyer: buyer.publicKey,
+        rentPayer: buyer.publicKey,
+        config: config_pda,
         buyerDepositTokenAccount: buyerTokenAccountA,
         seller: seller.publicKey,
         sellerReceiveTokenAccount: sellerTokenAccountA,
@@ -1758,4 +1882,530 @@ describe('lambda-escrow', () => {
     assert.ok(_sellerTokenAccountA.amount.toNumber() == (amount*4 + 2*amountPartial));
   }); // buyer: 0, seller: 5000
 
+  it("Initialize escrow, change judge by Buyer and Seller and adjudge escrow for Buyer", async () => {
+    // Put back tokens into buyer token A account.
+    await mintA.mintTo(
+      buyerTokenAccountA,
+      mintAuthority.publicKey,
+      [mintAuthority],
+      amountPartial
+    );
+
+    // Init account escrow
+    await program.rpc.initialize(
+      vault_account_bump,
+      new anchor.BN(amountPartial),
+      new anchor.BN(orderCode),
+      new anchor.BN(trialDay),
+      {
+        accounts: {
+          buyer: buyer.publicKey,
+          payer: buyer.publicKey,
+          seller: seller.publicKey,
+          judge: judge.publicKey,
+          config: config_pda,
+          mint: mintA.publicKey,
+          vaultAccount: vault_account_pda,
+          buyerDepositTokenAccount: buyerTokenAccountA,
+          sellerReceiveTokenAccount: sellerTokenAccountA,
+          escrowAccount: escrowAccount.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        instructions: [
+          await program.account.escrowAccount.createInstruction(escrowAccount),
+        ],
+        signers: [escrowAccount, buyer],
+      }
+    );
+
+    // Buyer and Seller replace the judge.
+    await program.rpc.changeJudge(
+      new anchor.BN(orderCode),
+      {
+        accounts: {
+          buyer: buyer.publicKey,
+          seller: seller.publicKey,
+          newJudge: newJudge.publicKey,
+          escrowAccount: escrowAccount.publicKey,
+        },
+        signers: [buyer, seller]
+      }
+    );
+    let _escrowAccount = await program.account.escrowAccount.fetch(escrowAccount.publicKey);
+    assert.ok(_escrowAccount.judgeKey.equals(newJudge.publicKey));
+
+    // The old judge can no longer adjudge.
+    let rejected = false;
+    try {
+      await program.rpc.adjudgeForSeller(
+        new anchor.BN(orderCode),
+        {
+          accounts: {
+            judge: judge.publicKey,
+            config: config_pda,
+            buyer: buyer.publicKey,
+            rentPayer: buyer.publicKey,
+            buyerDepositTokenAccount: buyerTokenAccountA,
+            seller: seller.publicKey,
+            sellerReceiveTokenAccount: sellerTokenAccountA,
+            escrowAccount: escrowAccount.publicKey,
+            vaultAccount: vault_account_pda,
+            vaultAuthority: vault_authority_pda,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          },
+          signers: [judge]
+        }
+      );
+    } catch (err) {
+      rejected = true;
+    }
+    assert.ok(rejected);
+
+    // call adjudge for buyer by the new judge
+    await program.rpc.adjudgeForBuyer(
+      new anchor.BN(orderCode),
+      {
+        accounts: {
+          judge: newJudge.publicKey,
+          config: config_pda,
+          buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          buyerDepositTokenAccount: buyerTokenAccountA,
+          seller: seller.publicKey,
+          sellerReceiveTokenAccount: sellerTokenAccountA,
+          escrowAccount: escrowAccount.publicKey,
+          vaultAccount: vault_account_pda,
+          vaultAuthority: vault_authority_pda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [newJudge]
+      }
+    );
+
+    // Get data info from Blockchain.
+    let _buyerTokenAccountA = await mintA.getAccountInfo(buyerTokenAccountA);
+    // Check
+    assert.ok(_buyerTokenAccountA.amount.toNumber() == amountPartial);
+  }); // buyer: 500, seller: 5000
+
+  it("Initialize escrow, request and execute judge change by admin and cancel escrow", async () => {
+    // Init account escrow
+    await program.rpc.initialize(
+      vault_account_bump,
+      new anchor.BN(amountPartial),
+      new anchor.BN(orderCode),
+      new anchor.BN(trialDay),
+      {
+        accounts: {
+          buyer: buyer.publicKey,
+          payer: buyer.publicKey,
+          seller: seller.publicKey,
+          judge: judge.publicKey,
+          config: config_pda,
+          mint: mintA.publicKey,
+          vaultAccount: vault_account_pda,
+          buyerDepositTokenAccount: buyerTokenAccountA,
+          sellerReceiveTokenAccount: sellerTokenAccountA,
+          escrowAccount: escrowAccount.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        instructions: [
+          await program.account.escrowAccount.createInstruction(escrowAccount),
+        ],
+        signers: [escrowAccount, buyer],
+      }
+    );
+
+    // Admin schedules the new judge.
+    await program.rpc.requestJudgeChange(
+      new anchor.BN(orderCode),
+      {
+        accounts: {
+          admin: payer.publicKey,
+          config: config_pda,
+          newJudge: newJudge.publicKey,
+          escrowAccount: escrowAccount.publicKey,
+        },
+        signers: [payer]
+      }
+    );
+    let _escrowAccount = await program.account.escrowAccount.fetch(escrowAccount.publicKey);
+    assert.ok(_escrowAccount.judgeKey.equals(judge.publicKey));
+    assert.ok(_escrowAccount.pendingJudgeKey.equals(newJudge.publicKey));
+
+    // Timelock is 0 in this config, apply it right away.
+    await program.rpc.executeJudgeChange(
+      new anchor.BN(orderCode),
+      {
+        accounts: {
+          admin: payer.publicKey,
+          config: config_pda,
+          escrowAccount: escrowAccount.publicKey,
+        },
+        signers: [payer]
+      }
+    );
+    _escrowAccount = await program.account.escrowAccount.fetch(escrowAccount.publicKey);
+    assert.ok(_escrowAccount.judgeKey.equals(newJudge.publicKey));
+    assert.ok(_escrowAccount.pendingJudgeKey.equals(PublicKey.default));
+    assert.ok(_escrowAccount.judgeChangeTime.toNumber() == 0);
+
+    // Cancel the escrow.
+    await program.rpc.cancel(
+      new anchor.BN(orderCode),
+      {
+        accounts: {
+          buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          config: config_pda,
+          buyerDepositTokenAccount: buyerTokenAccountA,
+          vaultAccount: vault_account_pda,
+          vaultAuthority: vault_authority_pda,
+          escrowAccount: escrowAccount.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [buyer]
+      }
+    );
+    const _buyerTokenAccountA = await mintA.getAccountInfo(buyerTokenAccountA);
+    assert.ok(_buyerTokenAccountA.amount.toNumber() == amountPartial);
+  }); // buyer: 500, seller: 5000
+
+  it("Pause program, initialize escrow rejected and unpause program", async () => {
+    await program.rpc.setPaused(
+      true,
+      {
+        accounts: {
+          admin: payer.publicKey,
+          config: config_pda,
+        },
+        signers: [payer]
+      }
+    );
+    let _config = await program.account.programConfig.fetch(config_pda);
+    assert.ok(_config.paused);
+
+    // Init account escrow is rejected while paused.
+    let rejected = false;
+    try {
+      await program.rpc.initialize(
+        vault_account_bump,
+        new anchor.BN(amountPartial),
+        new anchor.BN(orderCode),
+        new anchor.BN(trialDay),
+        {
+          accounts: {
+            buyer: buyer.publicKey,
+            payer: buyer.publicKey,
+            seller: seller.publicKey,
+            judge: judge.publicKey,
+            config: config_pda,
+            mint: mintA.publicKey,
+            vaultAccount: vault_account_pda,
+            buyerDepositTokenAccount: buyerTokenAccountA,
+            sellerReceiveTokenAccount: sellerTokenAccountA,
+            escrowAccount: escrowAccount.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          },
+          instructions: [
+            await program.account.escrowAccount.createInstruction(escrowAccount),
+          ],
+          signers: [escrowAccount, buyer],
+        }
+      );
+    } catch (err) {
+      rejected = true;
+    }
+    assert.ok(rejected);
+
+    await program.rpc.setPaused(
+      false,
+      {
+        accounts: {
+          admin: payer.publicKey,
+          config: config_pda,
+        },
+        signers: [payer]
+      }
+    );
+    _config = await program.account.programConfig.fetch(config_pda);
+    assert.ok(!_config.paused);
+  }); // buyer: 500, seller: 5000
+
+  it("Initialize escrow, freeze escrow, cancel rejected and adjudge escrow for Buyer", async () => {
+    // Init account escrow
+    await program.rpc.initialize(
+      vault_account_bump,
+      new anchor.BN(amountPartial),
+      new anchor.BN(orderCode),
+      new anchor.BN(trialDay),
+      {
+        accounts: {
+          buyer: buyer.publicKey,
+          payer: buyer.publicKey,
+          seller: seller.publicKey,
+          judge: judge.publicKey,
+          config: config_pda,
+          mint: mintA.publicKey,
+          vaultAccount: vault_account_pda,
+          buyerDepositTokenAccount: buyerTokenAccountA,
+          sellerReceiveTokenAccount: sellerTokenAccountA,
+          escrowAccount: escrowAccount.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        instructions: [
+          await program.account.escrowAccount.createInstruction(escrowAccount),
+        ],
+        signers: [escrowAccount, buyer],
+      }
+    );
+
+    // judge is also the compliance authority in this config.
+    await program.rpc.setFrozen(
+      new anchor.BN(orderCode),
+      true,
+      {
+        accounts: {
+          complianceAuthority: judge.publicKey,
+          config: config_pda,
+          escrowAccount: escrowAccount.publicKey,
+        },
+        signers: [judge]
+      }
+    );
+    let _escrowAccount = await program.account.escrowAccount.fetch(escrowAccount.publicKey);
+    assert.ok(_escrowAccount.frozen);
+
+    // Cancel the escrow is rejected while frozen.
+    let rejected = false;
+    try {
+      await program.rpc.cancel(
+        new anchor.BN(orderCode),
+        {
+          accounts: {
+            buyer: buyer.publicKey,
+            rentPayer: buyer.publicKey,
+            config: config_pda,
+            buyerDepositTokenAccount: buyerTokenAccountA,
+            vaultAccount: vault_account_pda,
+            vaultAuthority: vault_authority_pda,
+            escrowAccount: escrowAccount.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          },
+          signers: [buyer]
+        }
+      );
+    } catch (err) {
+      rejected = true;
+    }
+    assert.ok(rejected);
+
+    // call adjudge for buyer
+    await program.rpc.adjudgeForBuyer(
+      new anchor.BN(orderCode),
+      {
+        accounts: {
+          judge: judge.publicKey,
+          config: config_pda,
+          buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          buyerDepositTokenAccount: buyerTokenAccountA,
+          seller: seller.publicKey,
+          sellerReceiveTokenAccount: sellerTokenAccountA,
+          escrowAccount: escrowAccount.publicKey,
+          vaultAccount: vault_account_pda,
+          vaultAuthority: vault_authority_pda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [judge]
+      }
+    );
+    const _buyerTokenAccountA = await mintA.getAccountInfo(buyerTokenAccountA);
+    assert.ok(_buyerTokenAccountA.amount.toNumber() == amountPartial);
+  }); // buyer: 500, seller: 5000
+
+  it("Initialize escrow and cancel escrow emit events", async () => {
+    let createdEvent = null;
+    let cancelledEvent = null;
+    const createdListener = program.addEventListener("EscrowCreated", (event, slot) => {
+      createdEvent = event;
+    });
+    const cancelledListener = program.addEventListener("Cancelled", (event, slot) => {
+      cancelledEvent = event;
+    });
+
+    // Init account escrow
+    await program.rpc.initialize(
+      vault_account_bump,
+      new anchor.BN(amountPartial),
+      new anchor.BN(orderCode),
+      new anchor.BN(trialDay),
+      {
+        accounts: {
+          buyer: buyer.publicKey,
+          payer: buyer.publicKey,
+          seller: seller.publicKey,
+          judge: judge.publicKey,
+          config: config_pda,
+          mint: mintA.publicKey,
+          vaultAccount: vault_account_pda,
+          buyerDepositTokenAccount: buyerTokenAccountA,
+          sellerReceiveTokenAccount: sellerTokenAccountA,
+          escrowAccount: escrowAccount.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        instructions: [
+          await program.account.escrowAccount.createInstruction(escrowAccount),
+        ],
+        signers: [escrowAccount, buyer],
+      }
+    );
+
+    // Cancel the escrow.
+    await program.rpc.cancel(
+      new anchor.BN(orderCode),
+      {
+        accounts: {
+          buyer: buyer.publicKey,
+          rentPayer: buyer.publicKey,
+          config: config_pda,
+          buyerDepositTokenAccount: buyerTokenAccountA,
+          vaultAccount: vault_account_pda,
+          vaultAuthority: vault_authority_pda,
+          escrowAccount: escrowAccount.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [buyer]
+      }
+    );
+
+    // Give the websocket a moment to deliver the logs.
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+    await program.removeEventListener(createdListener);
+    await program.removeEventListener(cancelledListener);
+
+    assert.ok(createdEvent.orderCode.toNumber() == orderCode);
+    assert.ok(createdEvent.buyerKey.equals(buyer.publicKey));
+    assert.ok(createdEvent.amount.toNumber() == amountPartial);
+    assert.ok(cancelledEvent.orderCode.toNumber() == orderCode);
+    assert.ok(cancelledEvent.amount.toNumber() == amountPartial);
+    assert.ok(cancelledEvent.oldStatus == 0);
+    assert.ok(cancelledEvent.newStatus == 3);
+  }); // buyer: 500, seller: 5000
+
+  it("Initialize escrow, shipping, delivered and exchange escrow with settlement receipt", async () => {
+    const [receipt_pda, _receipt_bump] = await PublicKey.findProgramAddress(
+      [Buffer.from(anchor.utils.bytes.utf8.encode("receipt")), escrowAccount.publicKey.toBuffer()],
+      program.programId
+    );
+
+    // Init account escrow
+    await program.rpc.initialize(
+      vault_account_bump,
+      new anchor.BN(amountPartial),
+      new anchor.BN(orderCode),
+      new anchor.BN(trialDay),
+      {
+        accounts: {
+          buyer: buyer.publicKey,
+          payer: buyer.publicKey,
+          seller: seller.publicKey,
+          judge: judge.publicKey,
+          config: config_pda,
+          mint: mintA.publicKey,
+          vaultAccount: vault_account_pda,
+          buyerDepositTokenAccount: buyerTokenAccountA,
+          sellerReceiveTokenAccount: sellerTokenAccountA,
+          escrowAccount: escrowAccount.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        instructions: [
+          await program.account.escrowAccount.createInstruction(escrowAccount),
+        ],
+        signers: [escrowAccount, buyer],
+      }
+    );
+
+    // call shipping.
+    await program.rpc.shipping(
+      new anchor.BN(orderCode),
+      {
+        accounts: {
+          buyer: buyer.publicKey,
+          buyerDepositTokenAccount: buyerTokenAccountA,
+          seller: seller.publicKey,
+          sellerReceiveTokenAccount: sellerTokenAccountA,
+          escrowAccount: escrowAccount.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [seller]
+      }
+    );
+
+    // call delivered.
+    await program.rpc.delivered(
+      new anchor.BN(orderCode),
+      {
+        accounts: {
+          buyer: buyer.publicKey,
+          buyerDepositTokenAccount: buyerTokenAccountA,
+          seller: seller.publicKey,
+          sellerReceiveTokenAccount: sellerTokenAccountA,
+          escrowAccount: escrowAccount.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [buyer]
+      }
+    );
+
+    // call exchange, seller pays for the receipt.
+    await program.rpc.exchange({
+      accounts: {
+        buyer: buyer.publicKey,
+        rentPayer: buyer.publicKey,
+        config: config_pda,
+        buyerDepositTokenAccount: buyerTokenAccountA,
+        seller: seller.publicKey,
+        sellerReceiveTokenAccount: sellerTokenAccountA,
+        escrowAccount: escrowAccount.publicKey,
+        vaultAccount: vault_account_pda,
+        vaultAuthority: vault_authority_pda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      },
+      remainingAccounts: [
+        { pubkey: receipt_pda, isWritable: true, isSigner: false },
+        { pubkey: seller.publicKey, isWritable: true, isSigner: true },
+        { pubkey: anchor.web3.SystemProgram.programId, isWritable: false, isSigner: false },
+      ],
+      signers: [seller]
+    });
+
+    // The receipt outlives the escrow.
+    let _receipt = await program.account.settlementReceipt.fetch(receipt_pda);
+    assert.ok(_receipt.orderCode.toNumber() == orderCode);
+    assert.ok(_receipt.escrowKey.equals(escrowAccount.publicKey));
+    assert.ok(_receipt.buyerKey.equals(buyer.publicKey));
+    assert.ok(_receipt.sellerKey.equals(seller.publicKey));
+    assert.ok(_receipt.buyerPayout.toNumber() == 0);
+    assert.ok(_receipt.sellerPayout.toNumber() == amountPartial);
+    assert.ok(_receipt.fee.toNumber() == 0);
+    assert.ok(_receipt.rentRefund.toNumber() > 0);
+    assert.ok(_receipt.settlementPath == 1);
+
+    let _sellerTokenAccountA = await mintA.getAccountInfo(sellerTokenAccountA);
+    assert.ok(_sellerTokenAccountA.amount.toNumber() == (amount*5 + amountPartial));
+  }); // buyer: 0, seller: 5500
+
 });